@@ -0,0 +1,121 @@
+use futures::future::{BoxFuture, FutureExt};
+use rand::Rng;
+
+use super::{available, find, Args, Command, CommandError, CommandResult, Context, Scope};
+use crate::constants::privileges::Privileges;
+use crate::structs::bot;
+use crate::structs::player::PLAYERS;
+use crate::structs::ranking::RANKINGS;
+use crate::structs::stats::Stats;
+use crate::structs::user::User;
+use crate::utils::osu::packets::ChoNotification;
+
+register_commands!(
+    #[command(
+        "help",
+        "[command]",
+        "Shows the commands you can use, or how to use one.",
+        Privileges::UNRESTRICTED,
+        Scope::Anywhere
+    )]
+    pub async fn help(ctx: Context, args: Args) -> CommandResult {
+        if let Some(name) = args.optional::<String>("command")? {
+            return match find(name.trim_start_matches(super::PREFIX)) {
+                Some(command) if command.allowed(&ctx.player, &ctx.target) => {
+                    Ok(format!("{} - {}", command.usage(), command.help))
+                }
+                _ => Err(CommandError::Failed(format!(
+                    "there's no command called {}",
+                    name
+                ))),
+            };
+        }
+
+        let lines: Vec<String> = available(&ctx.player, &ctx.target)
+            .into_iter()
+            .map(|c| format!("{} - {}", c.usage(), c.help))
+            .collect();
+        Ok(lines.join("\n"))
+    }
+
+    #[command(
+        "roll",
+        "[max]",
+        "Rolls a number between 0 and max, 100 by default.",
+        Privileges::UNRESTRICTED,
+        Scope::Anywhere
+    )]
+    pub async fn roll(ctx: Context, args: Args) -> CommandResult {
+        let max = args.optional::<u32>("max")?.unwrap_or(100).max(1);
+        let points = rand::thread_rng().gen_range(0..=max);
+
+        Ok(format!("{} rolls {} points!", ctx.player.name(), points))
+    }
+
+    #[command(
+        "stats",
+        "[user]",
+        "Shows your or someone else's stats in the mode you're playing.",
+        Privileges::UNRESTRICTED,
+        Scope::Anywhere
+    )]
+    pub async fn stats(ctx: Context, args: Args) -> CommandResult {
+        let user = match args.optional::<User>("user")? {
+            Some(user) => user,
+            None => User::from_name(ctx.player.name())
+                .ok_or_else(|| CommandError::Failed("couldn't find your account".to_string()))?,
+        };
+
+        let mode = ctx.player.status().game_mode();
+        let stats = Stats::get(user.id, mode);
+        let rank = match RANKINGS.global_rank(user.id, mode) {
+            0 => "unranked".to_string(),
+            rank => format!("#{}", rank),
+        };
+
+        Ok(format!(
+            "{} ({}): {}, {}pp, {:.2}% accuracy, {} plays, level {}",
+            user.name,
+            mode.name(),
+            rank,
+            stats.pp,
+            stats.acc,
+            stats.plays,
+            stats.level() as u32
+        ))
+    }
+
+    #[command(
+        "alert",
+        "<message>",
+        "Sends a notification to everyone online.",
+        Privileges::MODERATOR,
+        Scope::Anywhere
+    )]
+    pub async fn alert(ctx: Context, args: Args) -> CommandResult {
+        let message = args.rest();
+        if message.is_empty() {
+            return Err(CommandError::Usage("missing message".to_string()));
+        }
+
+        PLAYERS.enqueue_all(&ChoNotification::new(message).write());
+        Ok("Sent.".to_string())
+    }
+
+    #[command(
+        "announce",
+        "<message>",
+        "Posts a message in #announce as the bot.",
+        Privileges::MODERATOR,
+        Scope::Anywhere
+    )]
+    pub async fn announce(ctx: Context, args: Args) -> CommandResult {
+        let message = args.rest();
+        if message.is_empty() {
+            return Err(CommandError::Usage("missing message".to_string()));
+        }
+
+        bot::announce(&message);
+        Ok("Announced.".to_string())
+    }
+);
@@ -0,0 +1,360 @@
+use chrono::NaiveDate;
+use futures::future::BoxFuture;
+use std::time::Duration;
+
+use crate::constants::multiplayer::{TeamType, WinCondition};
+use crate::constants::privileges::{Privileged, Privileges};
+use crate::structs::player::{Player, PLAYERS};
+use crate::structs::user::User;
+
+/// Messages starting with this are commands.
+pub const PREFIX: char = '!';
+
+/// Declares commands and a `commands()` listing them. Each takes the context
+/// it was run in and its arguments, and answers with the bot's reply.
+///
+/// `#[command(name, usage, help, privileges, scope)]`
+macro_rules! register_commands {(
+    $(
+        #[command($name:literal, $usage:literal, $help:literal, $privileges:expr, $scope:expr $(,)?)]
+     $( #[$attr:meta] )*
+        $pub:vis
+        async
+        fn $fname:ident ($ctx:ident : Context, $args:ident : Args) -> CommandResult
+        $body:block
+    )*
+) => (
+    $(
+     $( #[$attr] )*
+        #[allow(unused_mut)]
+        $pub
+        fn $fname($ctx: Context, mut $args: Args) -> BoxFuture<'static, CommandResult> {
+            FutureExt::boxed(async move {
+                let _ = (&$ctx, &$args);
+                $body
+            })
+        }
+    )*
+
+    pub fn commands() -> Vec<Command> {
+        vec![
+            $(
+                Command {
+                    name: $name,
+                    usage: $usage,
+                    help: $help,
+                    privileges: $privileges,
+                    scope: $scope,
+                    handler: $fname,
+                },
+            )*
+        ]
+    }
+)}
+
+#[path = "general.rs"]
+pub mod general;
+
+#[path = "moderation.rs"]
+pub mod moderation;
+
+#[path = "multiplayer.rs"]
+pub mod multiplayer;
+
+#[path = "seasonal.rs"]
+pub mod seasonal;
+
+lazy_static::lazy_static! {
+    pub static ref COMMANDS: Vec<Command> = general::commands()
+        .into_iter()
+        .chain(moderation::commands())
+        .chain(multiplayer::commands())
+        .chain(seasonal::commands())
+        .collect();
+}
+
+/// Where a command was sent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Target {
+    Channel(String),
+    /// A private message to the bot.
+    Private,
+}
+
+/// Where a command may be used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scope {
+    Anywhere,
+    /// Only in private messages to the bot.
+    Private,
+    /// Only in the channel with this name.
+    Channel(&'static str),
+}
+
+impl Scope {
+    pub fn allows(&self, target: &Target) -> bool {
+        match (self, target) {
+            (Scope::Anywhere, _) => true,
+            (Scope::Private, Target::Private) => true,
+            (Scope::Channel(name), Target::Channel(channel)) => name == channel,
+            _ => false,
+        }
+    }
+}
+
+pub struct Context {
+    pub player: Player,
+    pub target: Target,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CommandError {
+    /// The arguments were wrong; the usage is shown along with it.
+    Usage(String),
+    Failed(String),
+}
+
+pub type CommandResult = Result<String, CommandError>;
+
+type Handler = fn(Context, Args) -> BoxFuture<'static, CommandResult>;
+
+pub struct Command {
+    pub name: &'static str,
+    /// The arguments it takes, `<required> [optional]`.
+    pub usage: &'static str,
+    pub help: &'static str,
+    pub privileges: Privileges,
+    pub scope: Scope,
+    handler: Handler,
+}
+
+impl Command {
+    pub fn usage(&self) -> String {
+        format!("{}{} {}", PREFIX, self.name, self.usage)
+            .trim_end()
+            .to_string()
+    }
+
+    pub fn allowed(&self, player: &Player, target: &Target) -> bool {
+        player.has(self.privileges) && self.scope.allows(target)
+    }
+
+    /// Staff commands, which are answered only to whoever used them.
+    pub fn hidden(&self) -> bool {
+        !(self.privileges - Privileges::UNRESTRICTED).is_empty()
+    }
+}
+
+pub fn find(name: &str) -> Option<&'static Command> {
+    let name = name.to_lowercase();
+    COMMANDS.iter().find(|c| c.name == name)
+}
+
+/// The commands a player may use in a place.
+pub fn available(player: &Player, target: &Target) -> Vec<&'static Command> {
+    COMMANDS
+        .iter()
+        .filter(|c| c.allowed(player, target))
+        .collect()
+}
+
+/// Something that can be parsed from a command argument.
+pub trait FromArg: Sized {
+    fn from_arg(arg: &str) -> Result<Self, String>;
+}
+
+impl FromArg for String {
+    fn from_arg(arg: &str) -> Result<Self, String> {
+        Ok(arg.to_string())
+    }
+}
+
+macro_rules! from_arg_parse {
+    ($($ty:ty),*) => {
+        $(
+            impl FromArg for $ty {
+                fn from_arg(arg: &str) -> Result<Self, String> {
+                    arg.parse().map_err(|_| format!("{:?} isn't a number", arg))
+                }
+            }
+        )*
+    };
+}
+
+from_arg_parse!(i32, u32, i64, u64, f32);
+
+/// Things with names, like `!mp set teamvs score`.
+macro_rules! from_arg_from_str {
+    ($($ty:ty),*) => {
+        $(
+            impl FromArg for $ty {
+                fn from_arg(arg: &str) -> Result<Self, String> {
+                    arg.parse()
+                }
+            }
+        )*
+    };
+}
+
+from_arg_from_str!(TeamType, WinCondition);
+
+/// Durations like `30s`, `10m`, `2h`, `1d` or `1w`.
+impl FromArg for Duration {
+    fn from_arg(arg: &str) -> Result<Self, String> {
+        let invalid = || format!("{:?} isn't a duration like 30s, 10m, 2h, 1d or 1w", arg);
+
+        let split = arg
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let (amount, unit) = arg.split_at(split);
+        let amount: u64 = amount.parse().map_err(|_| invalid())?;
+        let seconds = match unit {
+            "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            "w" => 7 * 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        Ok(Duration::from_secs(amount * seconds))
+    }
+}
+
+/// Dates like `2026-12-01`.
+impl FromArg for NaiveDate {
+    fn from_arg(arg: &str) -> Result<Self, String> {
+        NaiveDate::parse_from_str(arg, "%Y-%m-%d")
+            .map_err(|_| format!("{:?} isn't a YYYY-MM-DD date", arg))
+    }
+}
+
+/// A privilege by name, like `moderator`.
+impl FromArg for Privileges {
+    fn from_arg(arg: &str) -> Result<Self, String> {
+        Privileges::from_name(arg).ok_or_else(|| format!("there's no privilege called {}", arg))
+    }
+}
+
+/// Any account, by name; spaces in names can be written as underscores.
+impl FromArg for User {
+    fn from_arg(arg: &str) -> Result<Self, String> {
+        User::from_name(arg).ok_or_else(|| format!("no user called {}", arg))
+    }
+}
+
+/// A player who's online, by name.
+impl FromArg for Player {
+    fn from_arg(arg: &str) -> Result<Self, String> {
+        PLAYERS
+            .by_name(arg)
+            .ok_or_else(|| format!("{} isn't online", arg))
+    }
+}
+
+/// A command's arguments, split on whitespace.
+pub struct Args {
+    args: Vec<String>,
+    pos: usize,
+}
+
+impl Args {
+    pub fn new(text: &str) -> Self {
+        Self {
+            args: text.split_whitespace().map(str::to_string).collect(),
+            pos: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.args.len()
+    }
+
+    /// The next argument, which has to be there.
+    pub fn next<T: FromArg>(&mut self, name: &str) -> Result<T, CommandError> {
+        self.optional(name)?
+            .ok_or_else(|| CommandError::Usage(format!("missing {}", name)))
+    }
+
+    pub fn optional<T: FromArg>(&mut self, name: &str) -> Result<Option<T>, CommandError> {
+        let arg = match self.args.get(self.pos) {
+            Some(arg) => arg,
+            None => return Ok(None),
+        };
+
+        let value = T::from_arg(arg)
+            .map_err(|e| CommandError::Usage(format!("invalid {}: {}", name, e)))?;
+        self.pos += 1;
+        Ok(Some(value))
+    }
+
+    /// Everything that's left, e.g. a reason.
+    pub fn rest(&mut self) -> String {
+        let rest = self.args[self.pos.min(self.args.len())..].join(" ");
+        self.pos = self.args.len();
+        rest
+    }
+}
+
+/// A command's answer.
+pub struct Reply {
+    pub command: &'static Command,
+    pub text: String,
+}
+
+/// Runs `text` if it's a command the player may use where they sent it.
+/// Anything else is left to be delivered as a normal message.
+pub async fn run(player: &Player, target: Target, text: &str) -> Option<Reply> {
+    let text = text.strip_prefix(PREFIX)?;
+    let (name, args) = text.split_once(' ').unwrap_or((text, ""));
+
+    let command = find(name)?;
+    if !command.allowed(player, &target) {
+        return None;
+    }
+
+    println!("[commands] {} ran {}{}", player.name(), PREFIX, text);
+
+    let ctx = Context {
+        player: player.clone(),
+        target,
+    };
+    let text = match (command.handler)(ctx, Args::new(args)).await {
+        Ok(text) => text,
+        Err(CommandError::Usage(e)) => format!("{} (usage: {})", e, command.usage()),
+        Err(CommandError::Failed(e)) => e,
+    };
+    Some(Reply { command, text })
+}
+
+#[test]
+fn test_args() {
+    let mut args = Args::new("  100 10m   some reason here");
+    assert_eq!(args.next::<i32>("max"), Ok(100));
+    assert_eq!(
+        args.next::<Duration>("duration"),
+        Ok(Duration::from_secs(600))
+    );
+    assert_eq!(args.rest(), "some reason here");
+    assert!(args.is_empty());
+    assert_eq!(
+        args.next::<String>("reason"),
+        Err(CommandError::Usage("missing reason".to_string()))
+    );
+
+    let mut args = Args::new("abc");
+    assert!(matches!(
+        args.optional::<u32>("max"),
+        Err(CommandError::Usage(_))
+    ));
+    assert!(Duration::from_arg("10y").is_err());
+    assert!(Duration::from_arg("m").is_err());
+    assert_eq!(
+        NaiveDate::from_arg("2026-12-01"),
+        Ok(NaiveDate::from_ymd_opt(2026, 12, 1).unwrap())
+    );
+    assert!(NaiveDate::from_arg("12/01").is_err());
+
+    assert!(Scope::Anywhere.allows(&Target::Private));
+    assert!(!Scope::Private.allows(&Target::Channel("#osu".to_string())));
+    assert!(Scope::Channel("#osu").allows(&Target::Channel("#osu".to_string())));
+}
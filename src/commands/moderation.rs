@@ -0,0 +1,311 @@
+use futures::future::{BoxFuture, FutureExt};
+use std::time::Duration;
+
+use super::{Args, Command, CommandError, CommandResult, Context, Scope};
+use crate::constants::privileges::{Privileged, Privileges};
+use crate::structs::bot;
+use crate::structs::moderation;
+use crate::structs::user::User;
+
+/// The rest of the arguments, which staff actions can't go without.
+fn reason(args: &mut Args) -> Result<String, CommandError> {
+    let reason = args.rest();
+    if reason.is_empty() {
+        return Err(CommandError::Usage("missing reason".to_string()));
+    }
+    Ok(reason)
+}
+
+fn failed(action: &str, user: &User, e: std::io::Error) -> CommandError {
+    CommandError::Failed(format!("couldn't {} {}: {}", action, user.name, e))
+}
+
+/// Staff can only hand out what they have, and restrictions have commands
+/// of their own.
+fn check_privilege(ctx: &Context, privilege: Privileges) -> Result<(), CommandError> {
+    if privilege.contains(Privileges::UNRESTRICTED) {
+        return Err(CommandError::Failed(
+            "use !restrict and !unrestrict for that".to_string(),
+        ));
+    }
+    if !ctx.player.has(privilege) {
+        return Err(CommandError::Failed(format!(
+            "you don't have {} yourself",
+            privilege.names().join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// How senior someone's staff role is, 0 for players.
+fn staff_rank(privileges: Privileges) -> u8 {
+    let privileges = privileges.effective();
+    if privileges.contains(Privileges::DEVELOPER) {
+        3
+    } else if privileges.contains(Privileges::ADMINISTRATOR) {
+        2
+    } else if privileges.contains(Privileges::MODERATOR) {
+        1
+    } else {
+        0
+    }
+}
+
+/// Staff can't act on themselves, the bot, or staff ranked as high as them.
+fn check_target(ctx: &Context, user: &User) -> Result<(), CommandError> {
+    can_act_on(ctx.player.id(), ctx.player.privileges(), bot::id(), user)
+}
+
+fn can_act_on(
+    actor_id: i32,
+    actor: Privileges,
+    bot_id: i32,
+    user: &User,
+) -> Result<(), CommandError> {
+    if user.id == actor_id {
+        return Err(CommandError::Failed(
+            "you can't do that to yourself".to_string(),
+        ));
+    }
+    if user.id == bot_id {
+        return Err(CommandError::Failed(format!(
+            "{} is the server's own account",
+            user.name
+        )));
+    }
+    let rank = staff_rank(user.privileges);
+    if rank > 0 && rank >= staff_rank(actor) {
+        return Err(CommandError::Failed(format!(
+            "{} is staff ranked as high as you",
+            user.name
+        )));
+    }
+    Ok(())
+}
+
+register_commands!(
+    #[command(
+        "silence",
+        "<user> <duration> <reason>",
+        "Keeps someone from chatting for a while, e.g. !silence peppy 1h spam.",
+        Privileges::MODERATOR,
+        Scope::Anywhere
+    )]
+    pub async fn silence(ctx: Context, args: Args) -> CommandResult {
+        let user = args.next::<User>("user")?;
+        let duration = args.next::<Duration>("duration")?;
+        let reason = reason(&mut args)?;
+        check_target(&ctx, &user)?;
+
+        moderation::silence(ctx.player.id(), &user, duration, &reason)
+            .map_err(|e| failed("silence", &user, e))?;
+        Ok(format!("Silenced {}.", user.name))
+    }
+
+    #[command(
+        "unsilence",
+        "<user> <reason>",
+        "Lifts someone's silence.",
+        Privileges::MODERATOR,
+        Scope::Anywhere
+    )]
+    pub async fn unsilence(ctx: Context, args: Args) -> CommandResult {
+        let user = args.next::<User>("user")?;
+        let reason = reason(&mut args)?;
+        if user.silence_remaining() == 0 {
+            return Err(CommandError::Failed(format!(
+                "{} isn't silenced",
+                user.name
+            )));
+        }
+
+        moderation::unsilence(ctx.player.id(), &user, &reason)
+            .map_err(|e| failed("unsilence", &user, e))?;
+        Ok(format!("Unsilenced {}.", user.name))
+    }
+
+    #[command(
+        "restrict",
+        "<user> <reason>",
+        "Hides someone from other players and the rankings.",
+        Privileges::MODERATOR,
+        Scope::Anywhere
+    )]
+    pub async fn restrict(ctx: Context, args: Args) -> CommandResult {
+        let user = args.next::<User>("user")?;
+        let reason = reason(&mut args)?;
+        check_target(&ctx, &user)?;
+        if user.is_restricted() {
+            return Err(CommandError::Failed(format!(
+                "{} is already restricted",
+                user.name
+            )));
+        }
+
+        moderation::restrict(ctx.player.id(), &user, &reason)
+            .map_err(|e| failed("restrict", &user, e))?;
+        Ok(format!("Restricted {}.", user.name))
+    }
+
+    #[command(
+        "unrestrict",
+        "<user> <reason>",
+        "Lifts someone's restriction.",
+        Privileges::MODERATOR,
+        Scope::Anywhere
+    )]
+    pub async fn unrestrict(ctx: Context, args: Args) -> CommandResult {
+        let user = args.next::<User>("user")?;
+        let reason = reason(&mut args)?;
+        if !user.is_restricted() {
+            return Err(CommandError::Failed(format!(
+                "{} isn't restricted",
+                user.name
+            )));
+        }
+        if user.banned {
+            return Err(CommandError::Failed(format!(
+                "{} is banned, unban them first",
+                user.name
+            )));
+        }
+
+        moderation::unrestrict(ctx.player.id(), &user, &reason)
+            .map_err(|e| failed("unrestrict", &user, e))?;
+        Ok(format!("Unrestricted {}.", user.name))
+    }
+
+    #[command(
+        "ban",
+        "<user> <reason>",
+        "Restricts someone and keeps them from logging in.",
+        Privileges::MODERATOR,
+        Scope::Anywhere
+    )]
+    pub async fn ban(ctx: Context, args: Args) -> CommandResult {
+        let user = args.next::<User>("user")?;
+        let reason = reason(&mut args)?;
+        check_target(&ctx, &user)?;
+        if user.banned {
+            return Err(CommandError::Failed(format!(
+                "{} is already banned",
+                user.name
+            )));
+        }
+
+        moderation::ban(ctx.player.id(), &user, &reason).map_err(|e| failed("ban", &user, e))?;
+        Ok(format!("Banned {}.", user.name))
+    }
+
+    #[command(
+        "unban",
+        "<user> <reason>",
+        "Lets someone log in again; they stay restricted.",
+        Privileges::MODERATOR,
+        Scope::Anywhere
+    )]
+    pub async fn unban(ctx: Context, args: Args) -> CommandResult {
+        let user = args.next::<User>("user")?;
+        let reason = reason(&mut args)?;
+        if !user.banned {
+            return Err(CommandError::Failed(format!("{} isn't banned", user.name)));
+        }
+
+        moderation::unban(ctx.player.id(), &user, &reason)
+            .map_err(|e| failed("unban", &user, e))?;
+        Ok(format!("Unbanned {}.", user.name))
+    }
+
+    #[command(
+        "grant",
+        "<user> <privilege> <reason>",
+        "Gives someone a privilege you have yourself, like nominator or tournament.",
+        Privileges::ADMINISTRATOR,
+        Scope::Anywhere
+    )]
+    pub async fn grant(ctx: Context, args: Args) -> CommandResult {
+        let user = args.next::<User>("user")?;
+        let privilege = args.next::<Privileges>("privilege")?;
+        let reason = reason(&mut args)?;
+        check_privilege(&ctx, privilege)?;
+        if user.has(privilege) {
+            return Err(CommandError::Failed(format!(
+                "{} already has {}",
+                user.name,
+                privilege.names().join(", ")
+            )));
+        }
+
+        moderation::set_privileges(ctx.player.id(), &user, privilege, true, &reason)
+            .map_err(|e| failed("update", &user, e))?;
+        Ok(format!(
+            "Granted {} {}.",
+            user.name,
+            privilege.names().join(", ")
+        ))
+    }
+
+    #[command(
+        "revoke",
+        "<user> <privilege> <reason>",
+        "Takes a privilege you have yourself away from someone.",
+        Privileges::ADMINISTRATOR,
+        Scope::Anywhere
+    )]
+    pub async fn revoke(ctx: Context, args: Args) -> CommandResult {
+        let user = args.next::<User>("user")?;
+        let privilege = args.next::<Privileges>("privilege")?;
+        let reason = reason(&mut args)?;
+        check_privilege(&ctx, privilege)?;
+        check_target(&ctx, &user)?;
+        if !user.privileges.contains(privilege) {
+            return Err(CommandError::Failed(format!(
+                "{} doesn't have {}",
+                user.name,
+                privilege.names().join(", ")
+            )));
+        }
+
+        moderation::set_privileges(ctx.player.id(), &user, privilege, false, &reason)
+            .map_err(|e| failed("update", &user, e))?;
+        Ok(format!(
+            "Revoked {} from {}.",
+            privilege.names().join(", "),
+            user.name
+        ))
+    }
+);
+
+#[test]
+fn test_can_act_on() {
+    let user = |id: i32, privileges: Privileges| User {
+        id,
+        name: format!("user{}", id),
+        safe_name: format!("user{}", id),
+        pw_bcrypt: String::new(),
+        country: "XX".to_string(),
+        creation_time: 0,
+        friends: Vec::new(),
+        silence_end: 0,
+        silence_reason: String::new(),
+        privileges,
+        banned: false,
+    };
+    let player = Privileges::default_for_account();
+    let moderator = player | Privileges::MODERATOR;
+    let administrator = player | Privileges::ADMINISTRATOR;
+    let (me, bot_id) = (2, 1);
+
+    assert!(can_act_on(me, moderator, bot_id, &user(3, player)).is_ok());
+    assert!(can_act_on(me, administrator, bot_id, &user(3, moderator)).is_ok());
+
+    // themselves
+    assert!(can_act_on(me, administrator, bot_id, &user(me, administrator)).is_err());
+    // the bot, even for those who outrank its privileges
+    let developer = player | Privileges::DEVELOPER;
+    assert!(can_act_on(me, developer, bot_id, &user(bot_id, player)).is_err());
+    // staff as senior as them, or more
+    assert!(can_act_on(me, moderator, bot_id, &user(3, moderator)).is_err());
+    assert!(can_act_on(me, moderator, bot_id, &user(3, administrator)).is_err());
+    assert!(can_act_on(me, administrator, bot_id, &user(3, developer)).is_err());
+}
@@ -0,0 +1,518 @@
+use futures::future::{BoxFuture, FutureExt};
+use std::sync::Arc;
+
+use super::{Args, Command, CommandError, CommandResult, Context, Scope, Target, PREFIX};
+use crate::constants::privileges::Privileges;
+use crate::structs::multiplayer::{Match, MATCHES};
+
+lazy_static::lazy_static! {
+    static ref SUBCOMMANDS: Vec<Command> = subcommands::commands();
+}
+
+register_commands!(
+    #[command(
+        "mp",
+        "<subcommand> [args]",
+        "Runs a multiplayer match, see !mp help.",
+        Privileges::UNRESTRICTED,
+        Scope::Anywhere
+    )]
+    pub async fn mp(ctx: Context, args: Args) -> CommandResult {
+        let name = args.next::<String>("subcommand")?.to_lowercase();
+        let subcommand = SUBCOMMANDS
+            .iter()
+            .find(|c| c.name == name && c.allowed(&ctx.player, &ctx.target))
+            .ok_or_else(|| {
+                CommandError::Failed(format!(
+                    "there's no {}mp {}, try {}mp help",
+                    PREFIX, name, PREFIX
+                ))
+            })?;
+
+        match (subcommand.handler)(ctx, args).await {
+            Err(CommandError::Usage(e)) => Err(CommandError::Failed(format!(
+                "{} (usage: {})",
+                e,
+                usage(subcommand)
+            ))),
+            result => result,
+        }
+    }
+);
+
+fn usage(subcommand: &Command) -> String {
+    format!("{}mp {} {}", PREFIX, subcommand.name, subcommand.usage)
+        .trim_end()
+        .to_string()
+}
+
+/// The match a command is about: the one whose chat it was sent in, or else
+/// the one the player is in. Only its referees may change it.
+fn current_match(ctx: &Context) -> Result<Arc<Match>, CommandError> {
+    let m = match &ctx.target {
+        Target::Channel(name) => MATCHES.by_channel(name),
+        Target::Private => None,
+    }
+    .or_else(|| MATCHES.of(&ctx.player))
+    .ok_or_else(|| CommandError::Failed("use this in a match's #multiplayer chat".to_string()))?;
+
+    if !m.is_referee(&ctx.player) {
+        return Err(CommandError::Failed(
+            "you're not a referee of this match".to_string(),
+        ));
+    }
+    Ok(m)
+}
+
+mod subcommands {
+    use futures::future::{BoxFuture, FutureExt};
+
+    use super::{current_match, usage, SUBCOMMANDS};
+    use crate::commands::{Args, Command, CommandError, CommandResult, Context, Scope};
+    use crate::constants::mods::Mods;
+    use crate::constants::multiplayer::{SlotStatus, TeamType, WinCondition};
+    use crate::constants::privileges::Privileges;
+    use crate::structs::beatmap::Beatmap;
+    use crate::structs::bot;
+    use crate::structs::multiplayer::{MATCHES, SLOTS};
+    use crate::structs::player::{Player, PLAYERS};
+    use crate::structs::user::User;
+
+    register_commands!(
+        #[command(
+            "help",
+            "",
+            "Lists what !mp can do.",
+            Privileges::UNRESTRICTED,
+            Scope::Anywhere
+        )]
+        pub async fn help(ctx: Context, args: Args) -> CommandResult {
+            let lines: Vec<String> = SUBCOMMANDS
+                .iter()
+                .filter(|c| c.allowed(&ctx.player, &ctx.target))
+                .map(|c| format!("{} - {}", usage(c), c.help))
+                .collect();
+            Ok(lines.join("\n"))
+        }
+
+        #[command(
+            "make",
+            "<name>",
+            "Makes a match run by referees, with you as its first.",
+            Privileges::UNRESTRICTED,
+            Scope::Anywhere
+        )]
+        pub async fn make(ctx: Context, args: Args) -> CommandResult {
+            let name = args.rest();
+            if name.is_empty() {
+                return Err(CommandError::Usage("missing name".to_string()));
+            }
+
+            let m = MATCHES.create(&name, "", &ctx.player);
+            m.make_tournament(&ctx.player);
+            Ok(format!(
+                "Made match #{} (history #{}), its chat is open as #multiplayer.",
+                m.id(),
+                m.history_id()
+            ))
+        }
+
+        #[command(
+            "close",
+            "",
+            "Closes the match.",
+            Privileges::UNRESTRICTED,
+            Scope::Anywhere
+        )]
+        pub async fn close(ctx: Context, args: Args) -> CommandResult {
+            let m = current_match(&ctx)?;
+            MATCHES.dispose(m.id());
+            Ok(format!("Closed match #{}.", m.id()))
+        }
+
+        #[command(
+            "invite",
+            "<player>",
+            "Sends a player a link to the match.",
+            Privileges::UNRESTRICTED,
+            Scope::Anywhere
+        )]
+        pub async fn invite(ctx: Context, args: Args) -> CommandResult {
+            let m = current_match(&ctx)?;
+            let target = args.next::<Player>("player")?;
+
+            target.enqueue(&m.invite_packet(bot::name(), bot::id(), target.name()));
+            Ok(format!("Invited {}.", target.name()))
+        }
+
+        #[command(
+            "lock",
+            "",
+            "Stops players from changing slots and teams.",
+            Privileges::UNRESTRICTED,
+            Scope::Anywhere
+        )]
+        pub async fn lock(ctx: Context, args: Args) -> CommandResult {
+            current_match(&ctx)?.set_locked(true);
+            Ok("Locked the match.".to_string())
+        }
+
+        #[command(
+            "unlock",
+            "",
+            "Lets players change slots and teams again.",
+            Privileges::UNRESTRICTED,
+            Scope::Anywhere
+        )]
+        pub async fn unlock(ctx: Context, args: Args) -> CommandResult {
+            current_match(&ctx)?.set_locked(false);
+            Ok("Unlocked the match.".to_string())
+        }
+
+        #[command(
+            "size",
+            "<slots>",
+            "Opens this many slots and locks the rest.",
+            Privileges::UNRESTRICTED,
+            Scope::Anywhere
+        )]
+        pub async fn size(ctx: Context, args: Args) -> CommandResult {
+            let m = current_match(&ctx)?;
+            let size = args.next::<u32>("slots")? as usize;
+            if !(1..=SLOTS).contains(&size) {
+                return Err(CommandError::Usage(format!("slots must be 1 to {}", SLOTS)));
+            }
+
+            m.set_size(size);
+            Ok(format!("The match now has {} slots.", size))
+        }
+
+        #[command(
+            "set",
+            "<teammode> [wincondition] [slots]",
+            "Sets the team mode (headtohead, tagcoop, teamvs, tagteamvs), win condition (score, accuracy, combo, scorev2) and size.",
+            Privileges::UNRESTRICTED,
+            Scope::Anywhere
+        )]
+        pub async fn set(ctx: Context, args: Args) -> CommandResult {
+            let m = current_match(&ctx)?;
+            let team_type = args.next::<TeamType>("teammode")?;
+            let win_condition = args.optional::<WinCondition>("wincondition")?;
+            let size = args.optional::<u32>("slots")?.map(|s| s as usize);
+            if size.is_some_and(|s| !(1..=SLOTS).contains(&s)) {
+                return Err(CommandError::Usage(format!("slots must be 1 to {}", SLOTS)));
+            }
+
+            m.set_team_type(team_type);
+            if let Some(win_condition) = win_condition {
+                m.set_win_condition(win_condition);
+            }
+            if let Some(size) = size {
+                m.set_size(size);
+            }
+            Ok("Changed the match settings.".to_string())
+        }
+
+        #[command(
+            "map",
+            "<id>",
+            "Picks the map to play.",
+            Privileges::UNRESTRICTED,
+            Scope::Anywhere
+        )]
+        pub async fn map(ctx: Context, args: Args) -> CommandResult {
+            let m = current_match(&ctx)?;
+            let id = args.next::<i32>("id")?;
+            let map = Beatmap::fetch_by_id(id)
+                .await
+                .ok_or_else(|| CommandError::Failed(format!("couldn't find map {}", id)))?;
+
+            m.set_map(&map);
+            Ok(format!(
+                "Changed the map to [https://osu.ppy.sh/b/{} {}].",
+                map.id,
+                map.full_name()
+            ))
+        }
+
+        #[command(
+            "mods",
+            "<mods|freemod|none>",
+            "Sets the mods, e.g. HDDT; with freemod players pick their own.",
+            Privileges::UNRESTRICTED,
+            Scope::Anywhere
+        )]
+        pub async fn mods(ctx: Context, args: Args) -> CommandResult {
+            let m = current_match(&ctx)?;
+            let words = args.rest();
+            if words.is_empty() {
+                return Err(CommandError::Usage("missing mods".to_string()));
+            }
+
+            let mut freemods = false;
+            let mut acronyms = String::new();
+            for word in words.split_whitespace() {
+                match word.to_lowercase().as_str() {
+                    "freemod" | "fm" => freemods = true,
+                    "none" | "nm" => {}
+                    _ => acronyms.push_str(word),
+                }
+            }
+            let mods = Mods::from_acronyms(&acronyms)
+                .ok_or_else(|| CommandError::Usage(format!("invalid mods {}", acronyms)))?;
+
+            m.set_mods(mods, freemods);
+            Ok(match (mods.is_empty(), freemods) {
+                (true, false) => "Cleared the mods.".to_string(),
+                (true, true) => "Enabled freemod.".to_string(),
+                (false, false) => format!("Set the mods to {}.", mods.acronyms()),
+                (false, true) => format!("Enabled freemod with {}.", mods.acronyms()),
+            })
+        }
+
+        #[command(
+            "host",
+            "<player>",
+            "Makes a player in the match host.",
+            Privileges::UNRESTRICTED,
+            Scope::Anywhere
+        )]
+        pub async fn host(ctx: Context, args: Args) -> CommandResult {
+            let m = current_match(&ctx)?;
+            let target = args.next::<Player>("player")?;
+            if target.match_id() != Some(m.id()) {
+                return Err(CommandError::Failed(format!(
+                    "{} isn't in the match",
+                    target.name()
+                )));
+            }
+
+            m.set_host(Some(&target));
+            Ok(format!("{} is now host.", target.name()))
+        }
+
+        #[command(
+            "clearhost",
+            "",
+            "Takes host away from whoever has it.",
+            Privileges::UNRESTRICTED,
+            Scope::Anywhere
+        )]
+        pub async fn clearhost(ctx: Context, args: Args) -> CommandResult {
+            current_match(&ctx)?.set_host(None);
+            Ok("Cleared the host.".to_string())
+        }
+
+        #[command(
+            "start",
+            "[seconds]",
+            "Starts the match, after a countdown if given.",
+            Privileges::UNRESTRICTED,
+            Scope::Anywhere
+        )]
+        pub async fn start(ctx: Context, args: Args) -> CommandResult {
+            let m = current_match(&ctx)?;
+            let seconds = args.optional::<u64>("seconds")?.unwrap_or(0);
+
+            if seconds > 0 {
+                m.start_countdown(seconds);
+                return Ok("Started the countdown.".to_string());
+            }
+            let players = m.start().map_err(CommandError::Failed)?;
+            Ok(format!("Started the match with {} players.", players))
+        }
+
+        #[command(
+            "aborttimer",
+            "",
+            "Stops the countdown to the start.",
+            Privileges::UNRESTRICTED,
+            Scope::Anywhere
+        )]
+        pub async fn aborttimer(ctx: Context, args: Args) -> CommandResult {
+            current_match(&ctx)?.cancel_countdown();
+            Ok("Stopped the countdown.".to_string())
+        }
+
+        #[command(
+            "abort",
+            "",
+            "Ends the game in progress without results.",
+            Privileges::UNRESTRICTED,
+            Scope::Anywhere
+        )]
+        pub async fn abort(ctx: Context, args: Args) -> CommandResult {
+            current_match(&ctx)?.abort().map_err(CommandError::Failed)?;
+            Ok("Aborted the match.".to_string())
+        }
+
+        #[command(
+            "kick",
+            "<player>",
+            "Removes a player from the match.",
+            Privileges::UNRESTRICTED,
+            Scope::Anywhere
+        )]
+        pub async fn kick(ctx: Context, args: Args) -> CommandResult {
+            let m = current_match(&ctx)?;
+            let target = args.next::<Player>("player")?;
+            if target.match_id() != Some(m.id()) {
+                return Err(CommandError::Failed(format!(
+                    "{} isn't in the match",
+                    target.name()
+                )));
+            }
+
+            m.kick(&target);
+            Ok(format!("Kicked {}.", target.name()))
+        }
+
+        #[command(
+            "ban",
+            "<user>",
+            "Kicks a user and keeps them from joining again.",
+            Privileges::UNRESTRICTED,
+            Scope::Anywhere
+        )]
+        pub async fn ban(ctx: Context, args: Args) -> CommandResult {
+            let m = current_match(&ctx)?;
+            let user = args.next::<User>("user")?;
+
+            m.ban(user.id);
+            Ok(format!("Banned {} from the match.", user.name))
+        }
+
+        #[command(
+            "addref",
+            "<user>",
+            "Lets a user run the match with !mp.",
+            Privileges::UNRESTRICTED,
+            Scope::Anywhere
+        )]
+        pub async fn addref(ctx: Context, args: Args) -> CommandResult {
+            let m = current_match(&ctx)?;
+            let user = args.next::<User>("user")?;
+
+            m.add_referee(user.id);
+            if let Some(player) = PLAYERS.by_id(user.id) {
+                m.channel().join(&player);
+            }
+            Ok(format!("{} is now a referee.", user.name))
+        }
+
+        #[command(
+            "removeref",
+            "<user>",
+            "Takes away a user's referee rights.",
+            Privileges::UNRESTRICTED,
+            Scope::Anywhere
+        )]
+        pub async fn removeref(ctx: Context, args: Args) -> CommandResult {
+            let m = current_match(&ctx)?;
+            let user = args.next::<User>("user")?;
+
+            m.remove_referee(user.id);
+            if let Some(player) = PLAYERS.by_id(user.id) {
+                if player.match_id() != Some(m.id()) {
+                    m.channel().kick(&player);
+                }
+            }
+            Ok(format!("{} is no longer a referee.", user.name))
+        }
+
+        #[command(
+            "password",
+            "[password]",
+            "Sets the password, or removes it.",
+            Privileges::UNRESTRICTED,
+            Scope::Anywhere
+        )]
+        pub async fn password(ctx: Context, args: Args) -> CommandResult {
+            let m = current_match(&ctx)?;
+            let password = args.rest();
+
+            m.set_password(&password);
+            Ok(match password.is_empty() {
+                true => "Removed the password.".to_string(),
+                false => "Changed the password.".to_string(),
+            })
+        }
+
+        #[command(
+            "settings",
+            "",
+            "Shows the match's settings and who's in which slot.",
+            Privileges::UNRESTRICTED,
+            Scope::Anywhere
+        )]
+        pub async fn settings(ctx: Context, args: Args) -> CommandResult {
+            let m = current_match(&ctx)?;
+            let state = m.state();
+
+            let mut lines = vec![
+                format!(
+                    "Room name: {}, match #{} (history #{})",
+                    state.name,
+                    m.id(),
+                    m.history_id()
+                ),
+                match state.map_id {
+                    0 => "Beatmap: none".to_string(),
+                    id => format!("Beatmap: [https://osu.ppy.sh/b/{} {}]", id, state.map_name),
+                },
+                format!(
+                    "Team mode: {:?}, win condition: {:?}",
+                    state.team_type, state.win_condition
+                ),
+            ];
+            let mut mods = match state.mods.is_empty() {
+                true => "none".to_string(),
+                false => state.mods.acronyms(),
+            };
+            if state.freemods {
+                mods.push_str(", freemod");
+            }
+            lines.push(format!("Active mods: {}", mods));
+
+            let players = state.slots.iter().filter(|s| s.has_player()).count();
+            lines.push(format!("Players: {}", players));
+            for (i, slot) in state.slots.iter().enumerate() {
+                let player = match &slot.player {
+                    Some(player) => player,
+                    None => continue,
+                };
+
+                let status = match slot.status {
+                    SlotStatus::READY => "Ready",
+                    SlotStatus::NO_MAP => "No Map",
+                    SlotStatus::PLAYING => "Playing",
+                    SlotStatus::COMPLETE => "Complete",
+                    _ => "Not Ready",
+                };
+                let mut extra = Vec::new();
+                if state.host_id == player.id() {
+                    extra.push("Host".to_string());
+                }
+                if state.team_type.has_teams() {
+                    extra.push(format!("Team {:?}", slot.team));
+                }
+                if !slot.mods.is_empty() {
+                    extra.push(format!("+{}", slot.mods.acronyms()));
+                }
+
+                let mut line = format!(
+                    "Slot {} {} [https://osu.ppy.sh/u/{} {}]",
+                    i + 1,
+                    status,
+                    player.id(),
+                    player.name()
+                );
+                if !extra.is_empty() {
+                    line.push_str(&format!(" [{}]", extra.join(" / ")));
+                }
+                lines.push(line);
+            }
+            Ok(lines.join("\n"))
+        }
+    );
+}
@@ -0,0 +1,115 @@
+use chrono::NaiveDate;
+use futures::future::{BoxFuture, FutureExt};
+
+use super::{Args, Command, CommandError, CommandResult, Context, Scope};
+use crate::constants::privileges::Privileges;
+use crate::structs::seasonal::{self, ScheduledArt};
+
+/// Checks and stores something to show, answering with its id.
+fn schedule(ctx: &Context, mut art: ScheduledArt) -> CommandResult {
+    if let Some(problem) = art.problem() {
+        return Err(CommandError::Failed(format!(
+            "can't schedule that: {}",
+            problem
+        )));
+    }
+
+    art.added_by = ctx.player.id();
+    let art = seasonal::schedule(art)
+        .map_err(|e| CommandError::Failed(format!("couldn't schedule that: {}", e)))?;
+    Ok(format!("Scheduled it as #{}.", art.id))
+}
+
+fn dates(art: &ScheduledArt) -> String {
+    match (art.from, art.until) {
+        (Some(from), Some(until)) => format!("{} to {}", from, until),
+        (Some(from), None) => format!("from {}", from),
+        (None, Some(until)) => format!("until {}", until),
+        (None, None) => "always".to_string(),
+    }
+}
+
+register_commands!(
+    #[command(
+        "seasonal",
+        "",
+        "Lists the backgrounds and menu icons scheduled in game.",
+        Privileges::ADMINISTRATOR,
+        Scope::Anywhere
+    )]
+    pub async fn list(ctx: Context, args: Args) -> CommandResult {
+        let scheduled = seasonal::scheduled();
+        if scheduled.is_empty() {
+            return Ok("Nothing's scheduled in game, only what's in the config.".to_string());
+        }
+
+        let lines: Vec<String> = scheduled
+            .iter()
+            .map(|art| match &art.click_url {
+                Some(click_url) => format!(
+                    "#{} menu icon {} -> {}, {}",
+                    art.id,
+                    art.image_url,
+                    click_url,
+                    dates(art)
+                ),
+                None => format!("#{} background {}, {}", art.id, art.image_url, dates(art)),
+            })
+            .collect();
+        Ok(lines.join("\n"))
+    }
+
+    #[command(
+        "background",
+        "<url> [from] [until]",
+        "Adds a main menu background, e.g. !background https://example.com/a.jpg 2026-12-01 2026-12-31.",
+        Privileges::ADMINISTRATOR,
+        Scope::Anywhere
+    )]
+    pub async fn background(ctx: Context, args: Args) -> CommandResult {
+        let url = args.next::<String>("url")?;
+        let from = args.optional::<NaiveDate>("from")?;
+        let until = args.optional::<NaiveDate>("until")?;
+        schedule(&ctx, ScheduledArt::background(&url, from, until))
+    }
+
+    #[command(
+        "menuicon",
+        "<image url> <click url> [from] [until]",
+        "Shows an icon on the main menu, over the config's, and sends it to everyone online.",
+        Privileges::ADMINISTRATOR,
+        Scope::Anywhere
+    )]
+    pub async fn menu_icon(ctx: Context, args: Args) -> CommandResult {
+        let image_url = args.next::<String>("image url")?;
+        let click_url = args.next::<String>("click url")?;
+        let from = args.optional::<NaiveDate>("from")?;
+        let until = args.optional::<NaiveDate>("until")?;
+        schedule(
+            &ctx,
+            ScheduledArt::menu_icon(&image_url, &click_url, from, until),
+        )
+    }
+
+    #[command(
+        "unschedule",
+        "<id>",
+        "Takes down a background or menu icon from !seasonal.",
+        Privileges::ADMINISTRATOR,
+        Scope::Anywhere
+    )]
+    pub async fn unschedule(ctx: Context, args: Args) -> CommandResult {
+        let id = args.next::<i64>("id")?;
+        match seasonal::unschedule(id) {
+            Ok(Some(_)) => Ok(format!("Took down #{}.", id)),
+            Ok(None) => Err(CommandError::Failed(format!(
+                "nothing's scheduled as #{}",
+                id
+            ))),
+            Err(e) => Err(CommandError::Failed(format!(
+                "couldn't take down #{}: {}",
+                id, e
+            ))),
+        }
+    }
+);
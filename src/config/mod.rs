@@ -0,0 +1,633 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use crate::structs::beatmap_source::{DEFAULT_ASSETS, DEFAULT_MIRROR};
+use crate::structs::user::make_safe_name;
+
+/// Where the config is read from unless `BANCHO_CONFIG` says otherwise.
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+/// How often the config file is checked for changes.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(2);
+
+lazy_static::lazy_static! {
+    /// The running config; defaults until [`init`] loads the real one.
+    static ref CONFIG: RwLock<Arc<Config>> = RwLock::new(Arc::new(Config::default()));
+}
+
+/// The current config. Hold on to it only as long as a single request, so
+/// reloads are picked up.
+pub fn get() -> Arc<Config> {
+    CONFIG.read().unwrap().clone()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// Tables are kept in memory and appended to json lines files.
+    Jsonl,
+    /// Nothing is written to disk, everything is gone on restart.
+    Memory,
+}
+
+impl std::str::FromStr for StorageBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "jsonl" => Ok(StorageBackend::Jsonl),
+            "memory" => Ok(StorageBackend::Memory),
+            _ => Err(format!("unknown storage backend {:?}", s)),
+        }
+    }
+}
+
+/// Reads an optional `YYYY-MM-DD` date, quoted or as a toml date.
+fn date<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<NaiveDate>, D::Error> {
+    let text = match toml::Value::deserialize(deserializer)? {
+        toml::Value::String(text) => text,
+        toml::Value::Datetime(date) => date.to_string(),
+        other => {
+            return Err(serde::de::Error::custom(format!(
+                "expected a date, got {}",
+                other.type_str()
+            )))
+        }
+    };
+
+    NaiveDate::parse_from_str(&text, "%Y-%m-%d")
+        .map(Some)
+        .map_err(|_| serde::de::Error::custom(format!("{:?} isn't a YYYY-MM-DD date", text)))
+}
+
+/// Whether `today` falls between two optional dates, both inclusive.
+pub fn in_window(from: Option<NaiveDate>, until: Option<NaiveDate>, today: NaiveDate) -> bool {
+    from.is_none_or(|from| from <= today) && until.is_none_or(|until| today <= until)
+}
+
+pub fn ends_before_start(from: Option<NaiveDate>, until: Option<NaiveDate>) -> bool {
+    matches!((from, until), (Some(from), Some(until)) if until < from)
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeasonalBackground {
+    pub url: String,
+    #[serde(default, deserialize_with = "date")]
+    pub from: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "date")]
+    pub until: Option<NaiveDate>,
+}
+
+impl SeasonalBackground {
+    pub fn is_active(&self, today: NaiveDate) -> bool {
+        in_window(self.from, self.until, today)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MenuIcon {
+    pub image_url: String,
+    pub click_url: String,
+    #[serde(default, deserialize_with = "date")]
+    pub from: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "date")]
+    pub until: Option<NaiveDate>,
+}
+
+impl MenuIcon {
+    pub fn is_active(&self, today: NaiveDate) -> bool {
+        in_window(self.from, self.until, today)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BeatmapsConfig {
+    /// Mirror to look maps up on; none when empty.
+    pub mirror: Option<String>,
+    /// A directory of .osu and .osz files to serve instead of a mirror.
+    pub maps_dir: Option<PathBuf>,
+    /// Where the mirror gets thumbnails and previews we haven't cached; none
+    /// when empty.
+    pub assets: Option<String>,
+}
+
+impl Default for BeatmapsConfig {
+    fn default() -> Self {
+        Self {
+            mirror: Some(DEFAULT_MIRROR.to_string()),
+            maps_dir: None,
+            assets: Some(DEFAULT_ASSETS.to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Features {
+    pub score_submission: bool,
+    pub osu_direct: bool,
+    pub avatar_uploads: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Self {
+            score_submission: true,
+            osu_direct: true,
+            avatar_uploads: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChatConfig {
+    /// Longer messages are cut off, in characters.
+    pub max_length: usize,
+    /// Words replaced wherever they're said on their own, ignoring case,
+    /// and what they're replaced with.
+    pub word_filter: BTreeMap<String, String>,
+    /// Messages a player may send within `rate_window` seconds before
+    /// they're silenced for `flood_silence` seconds.
+    pub user_rate_limit: usize,
+    /// Messages a channel takes from everyone within `rate_window` seconds;
+    /// the rest are dropped.
+    pub channel_rate_limit: usize,
+    pub rate_window: u64,
+    pub flood_silence: u64,
+    /// How many times in a row a player may send the same message.
+    pub max_repeats: usize,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            max_length: 2000,
+            word_filter: BTreeMap::new(),
+            user_rate_limit: 10,
+            channel_rate_limit: 50,
+            rate_window: 10,
+            flood_silence: 60,
+            max_repeats: 3,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind: String,
+    /// Every domain is served as a subdomain of this one, e.g. `c.ppy.sh`.
+    pub domain: String,
+    pub data_dir: PathBuf,
+    pub storage: StorageBackend,
+    pub beatmaps: BeatmapsConfig,
+    /// Name of the server's own account, which answers commands.
+    pub bot_name: String,
+    /// Channels created on startup.
+    pub default_channels: Vec<String>,
+
+    /// Sent to clients in the `bancho-version` header.
+    pub bancho_version: String,
+    /// Sent on login, `{name}` is replaced with the player's name.
+    pub welcome_message: String,
+    /// The first icon whose dates include today is shown in the main menu.
+    pub menu_icons: Vec<MenuIcon>,
+    /// Every background whose dates include today is shown in the main menu.
+    pub seasonal_backgrounds: Vec<SeasonalBackground>,
+    /// Accounts given the tournament privilege whenever they log in, on top
+    /// of those that have it stored with them.
+    pub tournament_accounts: Vec<String>,
+    pub chat: ChatConfig,
+    pub features: Features,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: "127.0.0.1:7272".to_string(),
+            domain: "ppy.sh".to_string(),
+            data_dir: PathBuf::from(".data"),
+            storage: StorageBackend::Jsonl,
+            beatmaps: BeatmapsConfig::default(),
+            bot_name: "BanchoBot".to_string(),
+            default_channels: vec!["#osu".to_string(), "#announce".to_string()],
+            bancho_version: "0.9.0".to_string(),
+            welcome_message: "Welcome back, {name}!".to_string(),
+            menu_icons: Vec::new(),
+            seasonal_backgrounds: Vec::new(),
+            tournament_accounts: Vec::new(),
+            chat: ChatConfig::default(),
+            features: Features::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "couldn't read {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "{} is invalid: {}", path.display(), e),
+            ConfigError::Invalid(problems) => {
+                write!(f, "the config has {} problem(s):", problems.len())?;
+                for problem in problems {
+                    write!(f, "\n  - {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+pub fn is_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        std::env::var("BANCHO_CONFIG")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from(DEFAULT_CONFIG_PATH))
+    }
+
+    /// Reads the config at `path`, falling back to the defaults if there's no
+    /// file, and applies environment overrides on top.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let mut config = match fs::read_to_string(path) {
+            Ok(text) => Config::parse(&text).map_err(|e| ConfigError::Parse(path.into(), e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Config::default(),
+            Err(e) => return Err(ConfigError::Io(path.into(), e)),
+        };
+
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, toml::de::Error> {
+        let mut config: Config = toml::from_str(text)?;
+        if config.beatmaps.mirror.as_deref() == Some("") {
+            config.beatmaps.mirror = None;
+        }
+        if config.beatmaps.assets.as_deref() == Some("") {
+            config.beatmaps.assets = None;
+        }
+        Ok(config)
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        let var = |name: &str| std::env::var(name).ok();
+
+        if let Some(bind) = var("BANCHO_BIND") {
+            self.bind = bind;
+        }
+        if let Some(domain) = var("BANCHO_DOMAIN") {
+            self.domain = domain;
+        }
+        if let Some(dir) = var("BANCHO_DATA_DIR") {
+            self.data_dir = PathBuf::from(dir);
+        }
+        if let Some(storage) = var("BANCHO_STORAGE") {
+            self.storage = storage
+                .parse()
+                .map_err(|e| ConfigError::Invalid(vec![format!("BANCHO_STORAGE: {}", e)]))?;
+        }
+        if let Some(mirror) = var("BANCHO_MIRROR") {
+            self.beatmaps.mirror = Some(mirror).filter(|m| !m.is_empty());
+        }
+        if let Some(dir) = var("BANCHO_MAPS_DIR") {
+            self.beatmaps.maps_dir = Some(PathBuf::from(dir));
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        if self.bind.parse::<SocketAddr>().is_err() {
+            problems.push(format!(
+                "bind {:?} has to be an address and port, like \"127.0.0.1:7272\"",
+                self.bind
+            ));
+        }
+        if self.bot_name.trim().is_empty() {
+            problems.push("bot_name can't be empty".to_string());
+        }
+        if self.domain.is_empty() || self.domain.contains(['/', ':', ' ']) {
+            problems.push(format!(
+                "domain {:?} has to be a bare domain, like \"ppy.sh\"",
+                self.domain
+            ));
+        }
+        if let Some(mirror) = &self.beatmaps.mirror {
+            if !is_url(mirror) {
+                problems.push(format!("beatmaps.mirror {:?} isn't an http(s) url", mirror));
+            }
+        }
+        if let Some(assets) = &self.beatmaps.assets {
+            if !is_url(assets) {
+                problems.push(format!("beatmaps.assets {:?} isn't an http(s) url", assets));
+            }
+        }
+        if let Some(dir) = &self.beatmaps.maps_dir {
+            if !dir.is_dir() {
+                problems.push(format!(
+                    "beatmaps.maps_dir {} isn't a directory",
+                    dir.display()
+                ));
+            }
+        }
+        for channel in &self.default_channels {
+            if !channel.starts_with('#') || channel.len() < 2 {
+                problems.push(format!(
+                    "default channel {:?} has to start with '#'",
+                    channel
+                ));
+            }
+        }
+        for (name, value) in [
+            ("chat.max_length", self.chat.max_length),
+            ("chat.user_rate_limit", self.chat.user_rate_limit),
+            ("chat.channel_rate_limit", self.chat.channel_rate_limit),
+            ("chat.rate_window", self.chat.rate_window as usize),
+            ("chat.max_repeats", self.chat.max_repeats),
+        ] {
+            if value == 0 {
+                problems.push(format!("{} has to be at least 1", name));
+            }
+        }
+        if self
+            .chat
+            .word_filter
+            .keys()
+            .any(|word| word.trim().is_empty())
+        {
+            problems.push("chat.word_filter can't filter an empty word".to_string());
+        }
+        for (i, icon) in self.menu_icons.iter().enumerate() {
+            for (name, url) in [
+                ("image_url", &icon.image_url),
+                ("click_url", &icon.click_url),
+            ] {
+                if !is_url(url) {
+                    problems.push(format!(
+                        "menu icon {}'s {} {:?} isn't an http(s) url",
+                        i + 1,
+                        name,
+                        url
+                    ));
+                }
+            }
+            if ends_before_start(icon.from, icon.until) {
+                problems.push(format!("menu icon {} ends before it starts", i + 1));
+            }
+        }
+        for background in &self.seasonal_backgrounds {
+            if !is_url(&background.url) {
+                problems.push(format!(
+                    "seasonal background {:?} isn't an http(s) url",
+                    background.url
+                ));
+            }
+            if ends_before_start(background.from, background.until) {
+                problems.push(format!(
+                    "seasonal background {:?} ends before it starts",
+                    background.url
+                ));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    /// Takes `new`'s settings, except for those that are only read on startup.
+    /// Returns the names of the ones that would need a restart to change.
+    fn reload(&self, new: Config) -> (Config, Vec<&'static str>) {
+        let mut ignored = Vec::new();
+        if new.bind != self.bind {
+            ignored.push("bind");
+        }
+        if new.domain != self.domain {
+            ignored.push("domain");
+        }
+        if new.data_dir != self.data_dir {
+            ignored.push("data_dir");
+        }
+        if new.storage != self.storage {
+            ignored.push("storage");
+        }
+        if new.beatmaps != self.beatmaps {
+            ignored.push("beatmaps");
+        }
+        if new.bot_name != self.bot_name {
+            ignored.push("bot_name");
+        }
+        if new.default_channels != self.default_channels {
+            ignored.push("default_channels");
+        }
+
+        let config = Config {
+            bind: self.bind.clone(),
+            domain: self.domain.clone(),
+            data_dir: self.data_dir.clone(),
+            storage: self.storage,
+            beatmaps: self.beatmaps.clone(),
+            bot_name: self.bot_name.clone(),
+            default_channels: self.default_channels.clone(),
+            ..new
+        };
+        (config, ignored)
+    }
+
+    /// The background urls to show today.
+    pub fn seasonal_backgrounds(&self, today: NaiveDate) -> Vec<String> {
+        self.seasonal_backgrounds
+            .iter()
+            .filter(|b| b.is_active(today))
+            .map(|b| b.url.clone())
+            .collect()
+    }
+
+    /// The main menu icon to show today.
+    pub fn menu_icon(&self, today: NaiveDate) -> Option<&MenuIcon> {
+        self.menu_icons.iter().find(|i| i.is_active(today))
+    }
+
+    /// The welcome notification for a player.
+    pub fn welcome_message(&self, name: &str) -> String {
+        self.welcome_message.replace("{name}", name)
+    }
+
+    pub fn is_tournament_account(&self, name: &str) -> bool {
+        let safe_name = make_safe_name(name);
+        self.tournament_accounts
+            .iter()
+            .any(|account| make_safe_name(account) == safe_name)
+    }
+}
+
+/// Loads the config; has to run before anything else reads it.
+pub fn init() -> Result<(), ConfigError> {
+    let config = Config::load(&Config::path())?;
+    *CONFIG.write().unwrap() = Arc::new(config);
+    Ok(())
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Reloads the config whenever its file changes. A config that fails to
+/// load is reported and the running one is kept.
+pub fn watch() {
+    let path = Config::path();
+
+    actix_web::rt::spawn(async move {
+        let mut last_modified = modified(&path);
+        let mut interval = actix_web::rt::time::interval(RELOAD_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let now = modified(&path);
+            if now == last_modified {
+                continue;
+            }
+            last_modified = now;
+
+            let new = match Config::load(&path) {
+                Ok(config) => config,
+                Err(e) => {
+                    println!("[config] not reloading: {}", e);
+                    continue;
+                }
+            };
+
+            let (config, ignored) = get().reload(new);
+            if !ignored.is_empty() {
+                println!("[config] changing {} needs a restart", ignored.join(", "));
+            }
+            *CONFIG.write().unwrap() = Arc::new(config);
+            println!("[config] reloaded {}", path.display());
+        }
+    });
+}
+
+#[test]
+fn test_config() {
+    let config = Config::parse(
+        r#"
+        domain = "example.com"
+        welcome_message = "hi {name}"
+        tournament_accounts = ["Some Referee"]
+
+        [[seasonal_backgrounds]]
+        url = "https://example.com/bg.jpg"
+
+        [[seasonal_backgrounds]]
+        url = "https://example.com/winter.jpg"
+        from = 2026-12-01
+        until = "2026-12-31"
+
+        [[menu_icons]]
+        image_url = "https://example.com/winter.png"
+        click_url = "https://example.com"
+        from = 2026-12-24
+        until = 2026-12-26
+
+        [[menu_icons]]
+        image_url = "https://example.com/icon.png"
+        click_url = "https://example.com"
+
+        [beatmaps]
+        mirror = ""
+        assets = ""
+
+        [chat]
+        max_length = 100
+        word_filter = { heck = "h*ck" }
+
+        [features]
+        osu_direct = false
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(config.domain, "example.com");
+    assert_eq!(config.bind, Config::default().bind);
+    assert_eq!(config.beatmaps.mirror, None);
+    assert_eq!(config.beatmaps.assets, None);
+    assert!(!config.features.osu_direct && config.features.score_submission);
+    assert_eq!(config.welcome_message("cmyui"), "hi cmyui");
+    assert!(config.is_tournament_account("some_referee"));
+    assert!(!config.is_tournament_account("cmyui"));
+    assert_eq!(config.chat.max_length, 100);
+    assert_eq!(config.chat.word_filter["heck"], "h*ck");
+    assert_eq!(config.chat.max_repeats, ChatConfig::default().max_repeats);
+    assert!(config.validate().is_ok());
+
+    let date = |text| NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap();
+    assert_eq!(config.seasonal_backgrounds(date("2026-11-30")).len(), 1);
+    assert_eq!(config.seasonal_backgrounds(date("2026-12-31")).len(), 2);
+    assert_eq!(
+        config.menu_icon(date("2026-12-25")).unwrap().image_url,
+        "https://example.com/winter.png"
+    );
+    assert_eq!(
+        config.menu_icon(date("2026-12-27")).unwrap().image_url,
+        "https://example.com/icon.png"
+    );
+
+    assert!(Config::parse("bnid = \"0.0.0.0:80\"").is_err());
+
+    assert!(Config::parse("[[menu_icons]]\nfrom = \"soon\"").is_err());
+
+    let invalid = Config::parse(
+        r#"
+        bind = "localhost"
+        default_channels = ["osu"]
+
+        [[seasonal_backgrounds]]
+        url = "https://example.com/bg.jpg"
+        from = 2026-12-31
+        until = 2026-12-01
+        "#,
+    )
+    .unwrap();
+    match invalid.validate() {
+        Err(ConfigError::Invalid(problems)) => assert_eq!(problems.len(), 3),
+        other => panic!("expected three problems, got {:?}", other),
+    }
+
+    let (reloaded, ignored) = config.reload(Config {
+        default_channels: vec!["#lobby".to_string()],
+        ..Config::default()
+    });
+    assert_eq!(reloaded.domain, "example.com");
+    assert_eq!(reloaded.default_channels, config.default_channels);
+    assert_eq!(reloaded.welcome_message, Config::default().welcome_message);
+    assert_eq!(ignored, vec!["domain", "beatmaps", "default_channels"]);
+}
@@ -0,0 +1,112 @@
+use super::mods::Mods;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+/// Game modes as the server tracks them; relax and autopilot are kept on
+/// their own leaderboards, so they get their own modes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(into = "u8", try_from = "u8")]
+#[repr(u8)]
+pub enum GameMode {
+    VanillaOsu = 0,
+    VanillaTaiko = 1,
+    VanillaCatch = 2,
+    VanillaMania = 3,
+
+    RelaxOsu = 4,
+    RelaxTaiko = 5,
+    RelaxCatch = 6,
+
+    AutopilotOsu = 8,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 8] = [
+        GameMode::VanillaOsu,
+        GameMode::VanillaTaiko,
+        GameMode::VanillaCatch,
+        GameMode::VanillaMania,
+        GameMode::RelaxOsu,
+        GameMode::RelaxTaiko,
+        GameMode::RelaxCatch,
+        GameMode::AutopilotOsu,
+    ];
+
+    /// Combines the mode the client reports with the mods it played.
+    pub fn from_params(mode_vn: u8, mods: Mods) -> Self {
+        let mode = match mode_vn {
+            1 => GameMode::VanillaTaiko,
+            2 => GameMode::VanillaCatch,
+            3 => GameMode::VanillaMania,
+            _ => GameMode::VanillaOsu,
+        };
+
+        if mods.contains(Mods::AUTOPILOT) {
+            if mode == GameMode::VanillaOsu {
+                return GameMode::AutopilotOsu;
+            }
+        } else if mods.contains(Mods::RELAX) && mode != GameMode::VanillaMania {
+            return GameMode::try_from(mode as u8 + 4).unwrap();
+        }
+
+        mode
+    }
+
+    /// Relax and autopilot scores are ranked by pp, since their score isn't
+    /// comparable to vanilla play.
+    pub fn sorts_by_pp(&self) -> bool {
+        matches!(
+            self,
+            GameMode::RelaxOsu
+                | GameMode::RelaxTaiko
+                | GameMode::RelaxCatch
+                | GameMode::AutopilotOsu
+        )
+    }
+
+    /// How the mode is shown to players.
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::VanillaOsu => "osu!",
+            GameMode::VanillaTaiko => "osu!taiko",
+            GameMode::VanillaCatch => "osu!catch",
+            GameMode::VanillaMania => "osu!mania",
+            GameMode::RelaxOsu => "osu! relax",
+            GameMode::RelaxTaiko => "osu!taiko relax",
+            GameMode::RelaxCatch => "osu!catch relax",
+            GameMode::AutopilotOsu => "osu! autopilot",
+        }
+    }
+
+    /// The mode as the client knows it (0-3).
+    pub fn as_vanilla(&self) -> u8 {
+        match self {
+            GameMode::AutopilotOsu => 0,
+            mode => *mode as u8 % 4,
+        }
+    }
+}
+
+impl From<GameMode> for u8 {
+    fn from(mode: GameMode) -> u8 {
+        mode as u8
+    }
+}
+
+impl TryFrom<u8> for GameMode {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => GameMode::VanillaOsu,
+            1 => GameMode::VanillaTaiko,
+            2 => GameMode::VanillaCatch,
+            3 => GameMode::VanillaMania,
+            4 => GameMode::RelaxOsu,
+            5 => GameMode::RelaxTaiko,
+            6 => GameMode::RelaxCatch,
+            8 => GameMode::AutopilotOsu,
+            _ => return Err(format!("invalid game mode {}", value)),
+        })
+    }
+}
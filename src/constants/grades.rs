@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum Grade {
+    XH, // silver SS
+    X,  // SS
+    SH, // silver S
+    S,
+    A,
+    B,
+    C,
+    D,
+    F,
+    N, // not submitted
+}
+
+impl std::str::FromStr for Grade {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "XH" => Grade::XH,
+            "X" => Grade::X,
+            "SH" => Grade::SH,
+            "S" => Grade::S,
+            "A" => Grade::A,
+            "B" => Grade::B,
+            "C" => Grade::C,
+            "D" => Grade::D,
+            "F" => Grade::F,
+            "N" => Grade::N,
+            _ => return Err(format!("invalid grade {}", s)),
+        })
+    }
+}
+
+impl Grade {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Grade::XH => "XH",
+            Grade::X => "X",
+            Grade::SH => "SH",
+            Grade::S => "S",
+            Grade::A => "A",
+            Grade::B => "B",
+            Grade::C => "C",
+            Grade::D => "D",
+            Grade::F => "F",
+            Grade::N => "N",
+        }
+    }
+
+    /// The grade's number in the client's enum, which orders silver grades first.
+    pub fn client_id(&self) -> u8 {
+        match self {
+            Grade::XH => 0,
+            Grade::SH => 1,
+            Grade::X => 2,
+            Grade::S => 3,
+            Grade::A => 4,
+            Grade::B => 5,
+            Grade::C => 6,
+            Grade::D => 7,
+            Grade::F => 8,
+            Grade::N => 9,
+        }
+    }
+}
@@ -0,0 +1,17 @@
+#[path = "countries.rs"]
+pub mod countries;
+
+#[path = "gamemodes.rs"]
+pub mod gamemodes;
+
+#[path = "grades.rs"]
+pub mod grades;
+
+#[path = "mods.rs"]
+pub mod mods;
+
+#[path = "multiplayer.rs"]
+pub mod multiplayer;
+
+#[path = "privileges.rs"]
+pub mod privileges;
@@ -0,0 +1,162 @@
+use bitflags::bitflags;
+
+bitflags! {
+    pub struct Mods: u32 {
+        const NOMOD = 0;
+        const NOFAIL = 1 << 0;
+        const EASY = 1 << 1;
+        const TOUCHSCREEN = 1 << 2;
+        const HIDDEN = 1 << 3;
+        const HARDROCK = 1 << 4;
+        const SUDDENDEATH = 1 << 5;
+        const DOUBLETIME = 1 << 6;
+        const RELAX = 1 << 7;
+        const HALFTIME = 1 << 8;
+        const NIGHTCORE = 1 << 9; // always sent with DOUBLETIME
+        const FLASHLIGHT = 1 << 10;
+        const AUTOPLAY = 1 << 11;
+        const SPUNOUT = 1 << 12;
+        const AUTOPILOT = 1 << 13;
+        const PERFECT = 1 << 14; // always sent with SUDDENDEATH
+        const KEY4 = 1 << 15;
+        const KEY5 = 1 << 16;
+        const KEY6 = 1 << 17;
+        const KEY7 = 1 << 18;
+        const KEY8 = 1 << 19;
+        const FADEIN = 1 << 20;
+        const RANDOM = 1 << 21;
+        const CINEMA = 1 << 22;
+        const TARGET = 1 << 23;
+        const KEY9 = 1 << 24;
+        const KEYCOOP = 1 << 25;
+        const KEY1 = 1 << 26;
+        const KEY3 = 1 << 27;
+        const KEY2 = 1 << 28;
+        const SCOREV2 = 1 << 29;
+        const MIRROR = 1 << 30;
+
+        const SPEED_CHANGING = Self::DOUBLETIME.bits | Self::NIGHTCORE.bits | Self::HALFTIME.bits;
+        const KEY_MODS = Self::KEY1.bits | Self::KEY2.bits | Self::KEY3.bits | Self::KEY4.bits
+            | Self::KEY5.bits | Self::KEY6.bits | Self::KEY7.bits | Self::KEY8.bits | Self::KEY9.bits;
+    }
+}
+
+/// The usual two letter names, e.g. `HD` for hidden.
+const ACRONYMS: &[(Mods, &str)] = &[
+    (Mods::NOFAIL, "NF"),
+    (Mods::EASY, "EZ"),
+    (Mods::TOUCHSCREEN, "TD"),
+    (Mods::HIDDEN, "HD"),
+    (Mods::HARDROCK, "HR"),
+    (Mods::SUDDENDEATH, "SD"),
+    (Mods::DOUBLETIME, "DT"),
+    (Mods::RELAX, "RX"),
+    (Mods::HALFTIME, "HT"),
+    (Mods::NIGHTCORE, "NC"),
+    (Mods::FLASHLIGHT, "FL"),
+    (Mods::AUTOPLAY, "AT"),
+    (Mods::SPUNOUT, "SO"),
+    (Mods::AUTOPILOT, "AP"),
+    (Mods::PERFECT, "PF"),
+    (Mods::FADEIN, "FI"),
+    (Mods::RANDOM, "RD"),
+    (Mods::SCOREV2, "V2"),
+    (Mods::MIRROR, "MR"),
+    (Mods::KEY1, "1K"),
+    (Mods::KEY2, "2K"),
+    (Mods::KEY3, "3K"),
+    (Mods::KEY4, "4K"),
+    (Mods::KEY5, "5K"),
+    (Mods::KEY6, "6K"),
+    (Mods::KEY7, "7K"),
+    (Mods::KEY8, "8K"),
+    (Mods::KEY9, "9K"),
+    (Mods::KEYCOOP, "CO"),
+];
+
+impl Mods {
+    pub fn from_i32(value: i32) -> Self {
+        Mods::from_bits_truncate(value as u32)
+    }
+
+    /// A mod by the name the client uses for it in `/np` messages.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "NoFail" => Mods::NOFAIL,
+            "Easy" => Mods::EASY,
+            "TouchDevice" => Mods::TOUCHSCREEN,
+            "Hidden" => Mods::HIDDEN,
+            "HardRock" => Mods::HARDROCK,
+            "SuddenDeath" => Mods::SUDDENDEATH,
+            "DoubleTime" => Mods::DOUBLETIME,
+            "Relax" => Mods::RELAX,
+            "HalfTime" => Mods::HALFTIME,
+            "Nightcore" => Mods::DOUBLETIME | Mods::NIGHTCORE,
+            "Flashlight" => Mods::FLASHLIGHT,
+            "SpunOut" => Mods::SPUNOUT,
+            "Relax2" => Mods::AUTOPILOT,
+            "Perfect" => Mods::SUDDENDEATH | Mods::PERFECT,
+            "FadeIn" => Mods::FADEIN,
+            "Mirror" => Mods::MIRROR,
+            _ => return None,
+        })
+    }
+
+    /// The mods as they're usually written, e.g. `HDDT`. Implied mods like
+    /// DT under NC aren't repeated.
+    pub fn acronyms(&self) -> String {
+        self.acronym_list().concat()
+    }
+
+    /// Like [`Mods::acronyms`], one acronym per mod.
+    pub fn acronym_list(&self) -> Vec<&'static str> {
+        let mut mods = *self;
+        if mods.contains(Mods::NIGHTCORE) {
+            mods.remove(Mods::DOUBLETIME);
+        }
+        if mods.contains(Mods::PERFECT) {
+            mods.remove(Mods::SUDDENDEATH);
+        }
+
+        ACRONYMS
+            .iter()
+            .filter(|(m, _)| mods.contains(*m))
+            .map(|(_, name)| *name)
+            .collect()
+    }
+
+    /// Reads acronyms written together or apart, e.g. `HDDT` or `HD DT`.
+    pub fn from_acronyms(text: &str) -> Option<Self> {
+        let letters: String = text.split_whitespace().collect::<String>().to_uppercase();
+        if !letters.len().is_multiple_of(2) || !letters.is_ascii() {
+            return None;
+        }
+
+        let mut mods = Mods::empty();
+        for i in (0..letters.len()).step_by(2) {
+            let (m, _) = ACRONYMS
+                .iter()
+                .find(|(_, name)| *name == &letters[i..i + 2])?;
+            mods |= *m;
+        }
+        // nightcore and perfect never come without what they imply
+        if mods.contains(Mods::NIGHTCORE) {
+            mods |= Mods::DOUBLETIME;
+        }
+        if mods.contains(Mods::PERFECT) {
+            mods |= Mods::SUDDENDEATH;
+        }
+        Some(mods)
+    }
+
+    /// Clock rate the map is played at with these mods.
+    pub fn clock_rate(&self) -> f64 {
+        if self.intersects(Mods::DOUBLETIME | Mods::NIGHTCORE) {
+            1.5
+        } else if self.contains(Mods::HALFTIME) {
+            0.75
+        } else {
+            1.0
+        }
+    }
+}
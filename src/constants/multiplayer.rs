@@ -0,0 +1,123 @@
+use bitflags::bitflags;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+bitflags! {
+    pub struct SlotStatus: u8 {
+        const OPEN = 1 << 0;
+        const LOCKED = 1 << 1;
+        const NOT_READY = 1 << 2;
+        const READY = 1 << 3;
+        const NO_MAP = 1 << 4;
+        const PLAYING = 1 << 5;
+        const COMPLETE = 1 << 6;
+        const QUIT = 1 << 7;
+
+        const HAS_PLAYER = Self::NOT_READY.bits | Self::READY.bits | Self::NO_MAP.bits
+            | Self::PLAYING.bits | Self::COMPLETE.bits;
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum Team {
+    Neutral = 0,
+    Blue = 1,
+    Red = 2,
+}
+
+impl TryFrom<u8> for Team {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Team::Neutral,
+            1 => Team::Blue,
+            2 => Team::Red,
+            _ => return Err(format!("invalid team {}", value)),
+        })
+    }
+}
+
+/// How the winner of a game is decided.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum WinCondition {
+    Score = 0,
+    Accuracy = 1,
+    Combo = 2,
+    ScoreV2 = 3,
+}
+
+impl TryFrom<u8> for WinCondition {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => WinCondition::Score,
+            1 => WinCondition::Accuracy,
+            2 => WinCondition::Combo,
+            3 => WinCondition::ScoreV2,
+            _ => return Err(format!("invalid win condition {}", value)),
+        })
+    }
+}
+
+/// Names as in `!mp set`, or the number the client uses.
+impl std::str::FromStr for WinCondition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "0" | "score" => WinCondition::Score,
+            "1" | "accuracy" | "acc" => WinCondition::Accuracy,
+            "2" | "combo" => WinCondition::Combo,
+            "3" | "scorev2" | "v2" => WinCondition::ScoreV2,
+            _ => return Err(format!("invalid win condition {}", s)),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum TeamType {
+    HeadToHead = 0,
+    TagCoop = 1,
+    TeamVs = 2,
+    TagTeamVs = 3,
+}
+
+impl TeamType {
+    pub fn has_teams(&self) -> bool {
+        matches!(self, TeamType::TeamVs | TeamType::TagTeamVs)
+    }
+}
+
+impl TryFrom<u8> for TeamType {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => TeamType::HeadToHead,
+            1 => TeamType::TagCoop,
+            2 => TeamType::TeamVs,
+            3 => TeamType::TagTeamVs,
+            _ => return Err(format!("invalid team type {}", value)),
+        })
+    }
+}
+
+/// Names as in `!mp set`, or the number the client uses.
+impl std::str::FromStr for TeamType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "0" | "headtohead" | "h2h" => TeamType::HeadToHead,
+            "1" | "tagcoop" => TeamType::TagCoop,
+            "2" | "teamvs" => TeamType::TeamVs,
+            "3" | "tagteamvs" => TeamType::TagTeamVs,
+            _ => return Err(format!("invalid team mode {}", s)),
+        })
+    }
+}
@@ -0,0 +1,166 @@
+use bitflags::bitflags;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+bitflags! {
+    /// Privileges as the client understands them, sent in `ChoPrivileges` and
+    /// user presences to decide name colours and what the client allows.
+    pub struct ClientPrivileges: i32 {
+        const PLAYER = 1 << 0;
+        const MODERATOR = 1 << 1;
+        const SUPPORTER = 1 << 2;
+        const OWNER = 1 << 3;
+        const DEVELOPER = 1 << 4;
+        const TOURNAMENT = 1 << 5;
+    }
+}
+
+bitflags! {
+    /// What an account may do on the server, stored with the account.
+    pub struct Privileges: u32 {
+        /// Seen by others and on the rankings; taken away by a restriction.
+        const UNRESTRICTED = 1 << 0;
+        const VERIFIED = 1 << 1;
+        /// Trusted not to be flagged by automatic checks.
+        const WHITELISTED = 1 << 2;
+        const SUPPORTER = 1 << 3;
+        const ALUMNI = 1 << 4;
+        /// May log in with the tournament client.
+        const TOURNAMENT = 1 << 5;
+        const NOMINATOR = 1 << 6;
+        const MODERATOR = 1 << 7;
+        const ADMINISTRATOR = 1 << 8;
+        const DEVELOPER = 1 << 9;
+    }
+}
+
+/// Names staff use for each privilege, e.g. in `!grant`.
+pub const PRIVILEGE_NAMES: &[(&str, Privileges)] = &[
+    ("unrestricted", Privileges::UNRESTRICTED),
+    ("verified", Privileges::VERIFIED),
+    ("whitelisted", Privileges::WHITELISTED),
+    ("supporter", Privileges::SUPPORTER),
+    ("alumni", Privileges::ALUMNI),
+    ("tournament", Privileges::TOURNAMENT),
+    ("nominator", Privileges::NOMINATOR),
+    ("moderator", Privileges::MODERATOR),
+    ("administrator", Privileges::ADMINISTRATOR),
+    ("developer", Privileges::DEVELOPER),
+];
+
+impl Privileges {
+    /// What accounts have unless staff change it; everyone plays with
+    /// supporter.
+    pub fn default_for_account() -> Self {
+        Privileges::UNRESTRICTED | Privileges::SUPPORTER
+    }
+
+    /// Adds what higher staff roles imply: developers are administrators,
+    /// and administrators are moderators.
+    pub fn effective(self) -> Self {
+        let mut privileges = self;
+        if privileges.contains(Privileges::DEVELOPER) {
+            privileges |= Privileges::ADMINISTRATOR;
+        }
+        if privileges.contains(Privileges::ADMINISTRATOR) {
+            privileges |= Privileges::MODERATOR;
+        }
+        privileges
+    }
+
+    /// The bits the client is sent. Restricted accounts get none at all.
+    pub fn client(self) -> ClientPrivileges {
+        let privileges = self.effective();
+        if !privileges.contains(Privileges::UNRESTRICTED) {
+            return ClientPrivileges::empty();
+        }
+
+        let mut client = ClientPrivileges::PLAYER;
+        if privileges.contains(Privileges::SUPPORTER) {
+            client |= ClientPrivileges::SUPPORTER;
+        }
+        if privileges.contains(Privileges::TOURNAMENT) {
+            client |= ClientPrivileges::TOURNAMENT;
+        }
+        if privileges.contains(Privileges::MODERATOR) {
+            client |= ClientPrivileges::MODERATOR;
+        }
+        if privileges.contains(Privileges::ADMINISTRATOR) {
+            client |= ClientPrivileges::DEVELOPER;
+        }
+        if privileges.contains(Privileges::DEVELOPER) {
+            client |= ClientPrivileges::OWNER;
+        }
+        client
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        PRIVILEGE_NAMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, privileges)| *privileges)
+    }
+
+    pub fn names(self) -> Vec<&'static str> {
+        PRIVILEGE_NAMES
+            .iter()
+            .filter(|(_, privileges)| self.contains(*privileges))
+            .map(|(name, _)| *name)
+            .collect()
+    }
+}
+
+// stored as the bare bits
+impl Serialize for Privileges {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(self.bits())
+    }
+}
+
+impl<'de> Deserialize<'de> for Privileges {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Privileges::from_bits_truncate(u32::deserialize(
+            deserializer,
+        )?))
+    }
+}
+
+/// An account, or a session acting for one, whose privileges can be checked.
+pub trait Privileged {
+    fn privileges(&self) -> Privileges;
+
+    /// Whether it has all of `required`, counting what staff roles imply.
+    fn has(&self, required: Privileges) -> bool {
+        self.privileges().effective().contains(required)
+    }
+
+    fn is_restricted(&self) -> bool {
+        !self.has(Privileges::UNRESTRICTED)
+    }
+}
+
+#[test]
+fn test_client_privileges() {
+    let player = Privileges::default_for_account();
+    assert_eq!(
+        player.client(),
+        ClientPrivileges::PLAYER | ClientPrivileges::SUPPORTER
+    );
+
+    let developer = Privileges::UNRESTRICTED | Privileges::DEVELOPER;
+    assert!(developer.effective().contains(Privileges::MODERATOR));
+    assert_eq!(
+        developer.client(),
+        ClientPrivileges::PLAYER
+            | ClientPrivileges::MODERATOR
+            | ClientPrivileges::DEVELOPER
+            | ClientPrivileges::OWNER
+    );
+
+    let restricted = Privileges::SUPPORTER | Privileges::MODERATOR;
+    assert_eq!(restricted.client(), ClientPrivileges::empty());
+    assert_eq!(
+        Privileges::from_name("Nominator"),
+        Some(Privileges::NOMINATOR)
+    );
+}
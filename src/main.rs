@@ -1,10 +1,15 @@
 #![allow(dead_code, unused_variables)]
 
-use actix_web::{get, App, HttpResponse, HttpServer, Responder, web};
+use actix_web::{get, App, HttpResponse, HttpServer, Responder};
 extern crate futures;
 
+mod commands;
+mod config;
+mod constants;
 mod middlewares;
 mod routes;
+mod storage;
+mod structs;
 mod utils;
 
 #[get("/")]
@@ -14,31 +19,53 @@ async fn index() -> impl Responder {
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    // HttpServer::new(|| {
-    //     App::new()
-    //         .route("/", web::post().to(routes::osu::domains::cho::index))
-    //         .route(
-    //             "/web/bancho_connect.php",
-    //             web::get().to(routes::osu::domains::osu::bancho_connect),
-    //         )
-    //         .route(
-    //             "/web/osu-getfriends.php",
-    //             web::get().to(routes::osu::domains::osu::get_friends),
-    //         )
-    //         .route("/web/lastfm.php", web::get().to(routes::osu::domains::osu::lastfm))
-    //         .route(
-    //             "/web/osu-getseasonal.php",
-    //             web::get().to(routes::osu::domains::osu::get_seasonal),
-    //         )
-    //         .route(
-    //             "/web/osu-error.php",
-    //             web::get().to(routes::osu::domains::osu::osu_error),
-    //         )
-    //         .route("/web", web::post().to(routes::osu::domains::cho::index))
-    //         .route("/users", web::post().to(routes::osu::domains::cho::index))
-    // })
-    // .bind("127.0.0.1:7272")?
-    // .run()
-    // .await
-    Ok(())
+    if let Err(e) = config::init() {
+        println!("{}", e);
+        std::process::exit(1);
+    }
+
+    // `bancho-rs recalc` recalculates every score's pp and everyone's stats,
+    // to be run offline after the pp formulas change
+    if std::env::args().nth(1).as_deref() == Some("recalc") {
+        let scores = structs::score::recalculate_all()?;
+        structs::stats::Stats::recalculate_all()?;
+        println!("recalculated {} scores", scores);
+        return Ok(());
+    }
+
+    // `bancho-rs map-status <id> <status>` overrides a map's ranked status
+    if std::env::args().nth(1).as_deref() == Some("map-status") {
+        let args: Vec<String> = std::env::args().skip(2).collect();
+        let (id, status) = match (
+            args.first().and_then(|id| id.parse::<i32>().ok()),
+            args.get(1).map(|s| s.parse::<structs::beatmap::RankedStatus>()),
+        ) {
+            (Some(id), Some(Ok(status))) => (id, status),
+            _ => {
+                println!("usage: bancho-rs map-status <map id> <pending|ranked|approved|qualified|loved>");
+                return Ok(());
+            }
+        };
+
+        match structs::beatmap::Beatmap::set_status(id, status)? {
+            Some(map) => println!("{} is now {:?}", map.full_name(), status),
+            None => println!("no map with id {}", id),
+        }
+        return Ok(());
+    }
+
+    lazy_static::initialize(&structs::ranking::RANKINGS);
+    structs::bot::login();
+
+    config::watch();
+    structs::seasonal::watch();
+
+    let config = config::get();
+    let domain = config.domain.clone();
+    println!("serving *.{} on {}", domain, config.bind);
+
+    HttpServer::new(move || App::new().configure(routes::osu::router::configure(&domain)))
+    .bind(config.bind.as_str())?
+    .run()
+    .await
 }
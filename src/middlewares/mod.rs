@@ -1 +1,28 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::Error;
+use std::time::Instant;
 
+/// Logs each request with the host it was made to, its status and how long
+/// it took.
+pub async fn log_request(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let start = Instant::now();
+    let host = req.connection_info().host().to_string();
+    let method = req.method().clone();
+    let path = req.path().to_string();
+
+    let res = next.call(req).await?;
+    println!(
+        "[{}] {} {} -> {} in {:.2?}",
+        host,
+        method,
+        path,
+        res.status().as_u16(),
+        start.elapsed()
+    );
+    Ok(res)
+}
@@ -1,4 +1,7 @@
 pub mod osu {
+    #[path = "router.rs"]
+    pub mod router;
+
     pub mod domains {
         #[path = "api.rs"]
         pub mod api;
@@ -1 +1,449 @@
+use actix_web::{http::header, web, Error, HttpResponse};
 
+use std::convert::TryFrom;
+
+use crate::constants::gamemodes::GameMode;
+use crate::constants::mods::Mods;
+use crate::constants::multiplayer::{Team, TeamType, WinCondition};
+use crate::constants::privileges::{Privileged, Privileges};
+use crate::storage::{
+    BEATMAPS, CHAT_FILTER_LOG, MATCH_EVENTS, MATCH_HISTORY, MODERATION_LOG, SCORES, USERS,
+};
+use crate::structs::match_history::{EventDetail, Game, GameScore, MatchEvent};
+use crate::structs::ranking::RANKINGS;
+use crate::structs::replay;
+use crate::structs::stats::Stats;
+use crate::structs::user::{self, User};
+
+pub enum AuthError {
+    InvalidCredentials,
+    Forbidden,
+}
+
+impl AuthError {
+    pub fn response(&self) -> HttpResponse {
+        match self {
+            AuthError::InvalidCredentials => HttpResponse::Unauthorized()
+                .json(serde_json::json!({"status": "Invalid credentials."})),
+            AuthError::Forbidden => HttpResponse::Forbidden()
+                .json(serde_json::json!({"status": "You aren't allowed to do that."})),
+        }
+    }
+}
+
+/// The account a request is made with, by name and password md5, if it has
+/// the `required` privileges.
+pub fn authorize(name: &str, pw_md5: &str, required: Privileges) -> Result<User, AuthError> {
+    let user = user::authenticate(name, pw_md5).ok_or(AuthError::InvalidCredentials)?;
+    if !user.has(required) {
+        return Err(AuthError::Forbidden);
+    }
+    Ok(user)
+}
+
+#[derive(serde::Deserialize)]
+pub struct GetReplayParams {
+    id: i64,
+}
+
+/// Serves a score's replay as a complete .osr file.
+pub async fn get_replay(params: web::Query<GetReplayParams>) -> Result<HttpResponse, Error> {
+    let score = match SCORES.get(params.id) {
+        Some(score) => score,
+        None => {
+            return Ok(
+                HttpResponse::NotFound().json(serde_json::json!({"status": "Score not found."}))
+            )
+        }
+    };
+
+    let user = match USERS.get(score.user_id as i64) {
+        Some(user) => user,
+        None => {
+            return Ok(
+                HttpResponse::NotFound().json(serde_json::json!({"status": "Player not found."}))
+            )
+        }
+    };
+
+    let osr = match replay::export_osr(&score, &user) {
+        Some(osr) => osr,
+        None => {
+            return Ok(
+                HttpResponse::NotFound().json(serde_json::json!({"status": "Replay not found."}))
+            )
+        }
+    };
+
+    let map_name = BEATMAPS
+        .find_one(|m| m.md5 == score.map_md5)
+        .map(|m| m.full_name())
+        .unwrap_or_else(|| score.map_md5.clone());
+    let date = chrono::DateTime::from_timestamp(score.play_time, 0)
+        .map(|d| d.format("%Y-%m-%d").to_string())
+        .unwrap_or_default();
+    let filename =
+        format!("{} - {} ({}).osr", user.name, map_name, date).replace(['/', '\\', '"'], "_");
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/octet-stream")
+        .append_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", filename),
+        ))
+        .body(osr))
+}
+
+#[derive(serde::Deserialize)]
+pub struct GetLeaderboardParams {
+    #[serde(default)]
+    mode: u8,
+    /// Two letter country code, the global ranking if not given.
+    country: Option<String>,
+    #[serde(default)]
+    offset: usize,
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+fn default_limit() -> usize {
+    50
+}
+
+/// Serves a page of the global or a country's ranking of a mode.
+pub async fn get_leaderboard(params: web::Query<GetLeaderboardParams>) -> Result<HttpResponse, Error> {
+    let mode = match GameMode::try_from(params.mode) {
+        Ok(mode) => mode,
+        Err(_) => {
+            return Ok(
+                HttpResponse::BadRequest().json(serde_json::json!({"status": "Invalid mode."}))
+            )
+        }
+    };
+
+    let country = params.country.as_ref().map(|c| c.to_lowercase());
+    let limit = params.limit.min(100);
+
+    let leaderboard = RANKINGS
+        .page(mode, country.as_deref(), params.offset, limit)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, (user_id, pp))| {
+            let user = USERS.get(user_id as i64)?;
+            let stats = Stats::get(user_id, mode);
+            Some(serde_json::json!({
+                "rank": params.offset + i + 1,
+                "id": user.id,
+                "name": user.name,
+                "country": user.country,
+                "pp": pp,
+                "acc": stats.acc,
+                "plays": stats.plays,
+                "ranked_score": stats.ranked_score,
+                "total_score": stats.total_score,
+                "level": stats.level(),
+            }))
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "leaderboard": leaderboard,
+    })))
+}
+
+#[derive(serde::Deserialize)]
+pub struct GetMatchParams {
+    id: i64,
+    /// Only events after this one.
+    after: Option<i64>,
+    /// Only events before this one, the latest first if `after` isn't given.
+    before: Option<i64>,
+    #[serde(default = "default_event_limit")]
+    limit: usize,
+}
+
+fn default_event_limit() -> usize {
+    100
+}
+
+fn timestamp(time: i64) -> Option<String> {
+    chrono::DateTime::from_timestamp(time, 0).map(|d| d.to_rfc3339())
+}
+
+fn team_name(team: Team) -> &'static str {
+    match team {
+        Team::Neutral => "none",
+        Team::Blue => "blue",
+        Team::Red => "red",
+    }
+}
+
+fn scoring_type_name(win_condition: WinCondition) -> &'static str {
+    match win_condition {
+        WinCondition::Score => "score",
+        WinCondition::Accuracy => "accuracy",
+        WinCondition::Combo => "combo",
+        WinCondition::ScoreV2 => "scorev2",
+    }
+}
+
+fn team_type_name(team_type: TeamType) -> &'static str {
+    match team_type {
+        TeamType::HeadToHead => "head-to-head",
+        TeamType::TagCoop => "tag-coop",
+        TeamType::TeamVs => "team-vs",
+        TeamType::TagTeamVs => "tag-team-vs",
+    }
+}
+
+fn score_json(score: &GameScore) -> serde_json::Value {
+    serde_json::json!({
+        "user_id": score.user_id,
+        "accuracy": score.acc / 100.0,
+        "mods": Mods::from_bits_truncate(score.mods).acronym_list(),
+        "score": score.score,
+        "max_combo": score.max_combo,
+        "perfect": score.perfect,
+        "passed": score.passed,
+        "statistics": {
+            "count_300": score.n300,
+            "count_100": score.n100,
+            "count_50": score.n50,
+            "count_geki": score.ngeki,
+            "count_katu": score.nkatu,
+            "count_miss": score.nmiss,
+        },
+        "match": {
+            "slot": score.slot,
+            "team": team_name(score.team),
+            "pass": score.passed,
+        },
+    })
+}
+
+fn game_json(id: i64, game: &Game) -> serde_json::Value {
+    let mode = GameMode::try_from(game.mode).unwrap_or(GameMode::VanillaOsu);
+    let mode_name = ["osu", "taiko", "fruits", "mania"][mode.as_vanilla() as usize];
+    let team_totals = game.team_totals().map(|totals| {
+        serde_json::json!({
+            "blue": totals.blue,
+            "red": totals.red,
+            "winner": totals.winner().map(team_name),
+        })
+    });
+
+    serde_json::json!({
+        "id": id,
+        "start_time": timestamp(game.start_time),
+        "end_time": game.end_time.and_then(timestamp),
+        "aborted": game.aborted,
+        "mode": mode_name,
+        "mode_int": mode.as_vanilla(),
+        "scoring_type": scoring_type_name(game.win_condition),
+        "team_type": team_type_name(game.team_type),
+        "mods": Mods::from_bits_truncate(game.mods).acronym_list(),
+        "freemod": game.freemods,
+        "beatmap_id": game.map_id,
+        "beatmap": {
+            "id": game.map_id,
+            "checksum": game.map_md5,
+            "title": game.map_name,
+        },
+        "scores": game.scores.iter().map(score_json).collect::<Vec<_>>(),
+        "team_totals": team_totals,
+    })
+}
+
+fn event_json(event: &MatchEvent) -> serde_json::Value {
+    let mut json = serde_json::json!({
+        "id": event.id,
+        "timestamp": timestamp(event.time),
+        "user_id": event.user_id,
+    });
+
+    // the same types as the osu! website, which puts games under "other"
+    let detail_type = match &event.detail {
+        EventDetail::MatchCreated => "match-created",
+        EventDetail::MatchDisbanded => "match-disbanded",
+        EventDetail::PlayerJoined => "player-joined",
+        EventDetail::PlayerLeft => "player-left",
+        EventDetail::PlayerKicked => "player-kicked",
+        EventDetail::HostChanged => "host-changed",
+        EventDetail::BeatmapChanged {
+            map_id,
+            map_md5,
+            map_name,
+        } => {
+            json["beatmap"] = serde_json::json!({
+                "id": map_id,
+                "checksum": map_md5,
+                "title": map_name,
+            });
+            "beatmap-changed"
+        }
+        EventDetail::Game(game) => {
+            json["game"] = game_json(event.id, game);
+            "other"
+        }
+    };
+    json["detail"] = serde_json::json!({ "type": detail_type });
+    json
+}
+
+/// Serves a match's history like the osu! website's match pages: its events
+/// in order, games with their scores and team totals, and who took part.
+pub async fn get_match(params: web::Query<GetMatchParams>) -> Result<HttpResponse, Error> {
+    let record = match MATCH_HISTORY.get(params.id) {
+        Some(record) => record,
+        None => {
+            return Ok(
+                HttpResponse::NotFound().json(serde_json::json!({"status": "Match not found."}))
+            )
+        }
+    };
+
+    let all = MATCH_EVENTS.find(|e| e.match_id == record.id);
+    let limit = params.limit.clamp(1, 100);
+    let matching: Vec<&MatchEvent> = all
+        .iter()
+        .filter(|e| params.after.is_none_or(|after| e.id > after))
+        .filter(|e| params.before.is_none_or(|before| e.id < before))
+        .collect();
+    let events = match (params.after, params.before) {
+        (None, Some(_)) => &matching[matching.len().saturating_sub(limit)..],
+        _ => &matching[..matching.len().min(limit)],
+    };
+
+    let mut user_ids: Vec<i32> = Vec::new();
+    for event in events {
+        user_ids.extend(event.user_id);
+        if let EventDetail::Game(game) = &event.detail {
+            user_ids.extend(game.scores.iter().map(|s| s.user_id));
+        }
+    }
+    user_ids.sort_unstable();
+    user_ids.dedup();
+    let users = user_ids
+        .into_iter()
+        .filter_map(|id| USERS.get(id as i64))
+        .map(|user| {
+            serde_json::json!({
+                "id": user.id,
+                "username": user.name,
+                "country_code": user.country.to_uppercase(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // a game still being played is the last one without an end
+    let current_game_id = all.iter().rev().find_map(|e| match &e.detail {
+        EventDetail::Game(game) if game.end_time.is_none() && record.end_time.is_none() => {
+            Some(e.id)
+        }
+        _ => None,
+    });
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "match": {
+            "id": record.id,
+            "name": record.name,
+            "start_time": timestamp(record.start_time),
+            "end_time": record.end_time.and_then(timestamp),
+        },
+        "events": events.iter().map(|e| event_json(e)).collect::<Vec<_>>(),
+        "users": users,
+        "first_event_id": all.first().map(|e| e.id),
+        "latest_event_id": all.last().map(|e| e.id),
+        "current_game_id": current_game_id,
+    })))
+}
+
+#[derive(serde::Deserialize)]
+pub struct GetModerationLogParams {
+    u: String,
+    /// md5 of the password.
+    h: String,
+    /// Only actions against this user.
+    user_id: Option<i32>,
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+/// Serves the latest staff actions, newest first, to moderators.
+pub async fn get_moderation_log(
+    params: web::Query<GetModerationLogParams>,
+) -> Result<HttpResponse, Error> {
+    if let Err(e) = authorize(&params.u, &params.h, Privileges::MODERATOR) {
+        return Ok(e.response());
+    }
+
+    let name = |id: i32| USERS.get(id as i64).map(|u| u.name);
+    let entries = MODERATION_LOG
+        .find(|e| params.user_id.is_none_or(|id| e.user_id == id))
+        .into_iter()
+        .rev()
+        .take(params.limit.clamp(1, 100))
+        .map(|e| {
+            serde_json::json!({
+                "id": e.id,
+                "time": timestamp(e.time),
+                "action": e.action,
+                "actor": {"id": e.actor_id, "name": name(e.actor_id)},
+                "user": {"id": e.user_id, "name": name(e.user_id)},
+                "reason": e.reason,
+                "duration": e.duration,
+                "privileges": e.privileges.map(|p| p.names()),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "entries": entries,
+    })))
+}
+
+#[derive(serde::Deserialize)]
+pub struct GetChatFilterLogParams {
+    u: String,
+    /// md5 of the password.
+    h: String,
+    /// Only messages from this user.
+    user_id: Option<i32>,
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+/// Serves the latest messages the chat filters changed or dropped, newest
+/// first, to moderators.
+pub async fn get_chat_filter_log(
+    params: web::Query<GetChatFilterLogParams>,
+) -> Result<HttpResponse, Error> {
+    if let Err(e) = authorize(&params.u, &params.h, Privileges::MODERATOR) {
+        return Ok(e.response());
+    }
+
+    let events = CHAT_FILTER_LOG
+        .find(|e| params.user_id.is_none_or(|id| e.user_id == id))
+        .into_iter()
+        .rev()
+        .take(params.limit.clamp(1, 100))
+        .map(|e| {
+            serde_json::json!({
+                "id": e.id,
+                "time": timestamp(e.time),
+                "user": {"id": e.user_id, "name": USERS.get(e.user_id as i64).map(|u| u.name)},
+                "recipient": e.recipient,
+                "kind": e.kind,
+                "text": e.text,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "events": events,
+    })))
+}
@@ -1 +1,54 @@
+use actix_web::{web, Error, HttpRequest, HttpResponse};
 
+use super::api;
+use super::map::serve;
+use crate::config;
+use crate::constants::privileges::Privileges;
+use crate::structs::avatar;
+
+/// Serves `/<user id>`; the client sometimes appends `_<timestamp>` to get
+/// around its own cache, which we ignore.
+pub async fn get_avatar(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let user_id = path
+        .split(['_', '.'])
+        .next()
+        .and_then(|id| id.parse::<i32>().ok());
+
+    match user_id {
+        Some(user_id) => Ok(serve(&req, avatar::load(user_id), "image/png")),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct UploadAvatarParams {
+    u: String,
+    /// md5 of the password.
+    h: String,
+}
+
+/// Replaces the user's avatar with the image in the request body.
+pub async fn upload_avatar(
+    params: web::Query<UploadAvatarParams>,
+    body: web::Bytes,
+) -> Result<HttpResponse, Error> {
+    // restricted players keep their avatar as it was
+    let user = match api::authorize(&params.u, &params.h, Privileges::UNRESTRICTED) {
+        Ok(user) => user,
+        Err(e) => return Ok(e.response()),
+    };
+    if !config::get().features.avatar_uploads {
+        return Ok(HttpResponse::Forbidden()
+            .json(serde_json::json!({"status": "Avatar uploads are disabled."})));
+    }
+
+    match avatar::save(user.id, &body) {
+        Ok(()) => Ok(HttpResponse::Ok().json(serde_json::json!({"status": "success"}))),
+        Err(avatar::AvatarError::Io(e)) => {
+            println!("[ava] failed to save {}'s avatar: {}", user.name, e);
+            Ok(HttpResponse::InternalServerError()
+                .json(serde_json::json!({"status": "Failed to save avatar."})))
+        }
+        Err(e) => Ok(HttpResponse::BadRequest().json(serde_json::json!({"status": e.to_string()}))),
+    }
+}
@@ -1,44 +1,176 @@
-use actix_web::{error, web, App, Error, HttpRequest, HttpResponse, HttpServer, Responder};
+use actix_web::{web, Error, HttpRequest, HttpResponse};
 use futures_util::stream::StreamExt as _;
+use crate::config;
+use crate::constants::privileges::{Privileged, Privileges};
+use crate::structs::channel::CHANNELS;
+use crate::structs::moderation;
+use crate::structs::multiplayer::MATCHES;
+use crate::structs::player::{LoginData, Player, PLAYERS};
+use crate::structs::seasonal;
+use crate::structs::user;
 use crate::utils::osu::packet_reader::PacketReader;
-use crate::utils::osu::packets::{Player, Packets};
-use crate::utils::osu::packets::PACKET_HANDLERS;
+use crate::utils::osu::packets::{
+    ChoAccountRestricted, ChoChannelInfoEnd, ChoFriendsList, ChoNotification, ChoPrivileges,
+    ChoProtocolVersion, ChoRestart, ChoUserId, ChoUserLogout, Packets, PACKET_HANDLERS,
+    RESTRICTED_PACKET_HANDLERS,
+};
 
 pub async fn packet_router(req: HttpRequest, mut body: web::Payload) -> Result<HttpResponse, Error> {
-    let token = match req.headers().get("osu-token") {
-        Some(token) => token.to_str().unwrap(),
-        None => {
-            return Ok(HttpResponse::Ok().body(b"".to_vec()));
-        }
-    };
-
     let mut bytes = web::BytesMut::new();
     while let Some(item) = body.next().await {
         bytes.extend_from_slice(&item?);
     }
 
     let bytes: Vec<u8> = bytes.to_vec();
-    
-    let res = handle_stream(bytes, Player{}).await?;
+
+    let token = match req.headers().get("osu-token") {
+        Some(token) => token.to_str().unwrap_or_default(),
+        None => return Ok(login(&bytes)),
+    };
+
+    let mut player = match PLAYERS.by_token(token) {
+        Some(player) => player,
+        None => {
+            // most likely the server restarted, so have the client log in again
+            let mut res = ChoNotification::new("Server has restarted.").write();
+            res.extend(ChoRestart::new(0).write());
+            return Ok(HttpResponse::Ok().body(res));
+        }
+    };
+
+    let res = handle_stream(bytes, &mut player).await;
 
     Ok(HttpResponse::Ok().body(res))
 }
 
-pub async fn handle_stream(data: Vec<u8>, mut player: Player) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let mut reader = PacketReader::new(data);
-    let packet = unsafe { std::mem::transmute::<i16, Packets>(reader.read_i16()) };
+fn login(body: &[u8]) -> HttpResponse {
+    let failed = || {
+        HttpResponse::Ok()
+            .append_header(("cho-token", "no"))
+            .body(ChoUserId::new(-1).write())
+    };
+
+    let login = match LoginData::parse(body) {
+        Some(login) => login,
+        None => return failed(),
+    };
 
-    let res;
+    let user = match user::authenticate(&login.name, &login.pw_md5) {
+        Some(user) => user,
+        None => return failed(),
+    };
 
-    if let Some(handler) = PACKET_HANDLERS.get(&packet) {
-        res = handler(&mut player, &mut reader).await?;
-    } else {
-        res = Vec::new();
+    // accounts listed in the config get tournament access on top of what's
+    // stored with them
+    let config = config::get();
+    let mut privileges = user.privileges;
+    if config.is_tournament_account(&user.name) {
+        privileges |= Privileges::TOURNAMENT;
     }
 
-    Ok(res)
+    let tourney = login.is_tourney_client();
+    if tourney && !privileges.contains(Privileges::TOURNAMENT) {
+        println!("[cho] {} isn't allowed to use the tournament client", user.name);
+        return failed();
+    }
+
+    // only one session per user, the newest login wins; tournament clients
+    // run alongside it
+    let old = PLAYERS
+        .sessions(user.id)
+        .into_iter()
+        .find(|p| !p.is_tourney_client());
+    if let Some(old) = old.filter(|_| !tourney) {
+        PLAYERS.remove(&old);
+        MATCHES.leave(&old);
+        MATCHES.part_lobby(&old);
+        CHANNELS.part_all(&old);
+        if old.is_visible() {
+            PLAYERS.enqueue_all(&ChoUserLogout::new(old.id()).write());
+        }
+    }
+
+    let player = Player::new(&user, &login, privileges);
+
+    let mut res = Vec::new();
+    res.extend(ChoProtocolVersion::new(19).write());
+    res.extend(ChoUserId::new(user.id).write());
+    res.extend(ChoPrivileges::new(player.client_privileges().bits()).write());
+    res.extend(ChoNotification::new(config.welcome_message(&user.name)).write());
+    if let Some(icon) = seasonal::menu_icon() {
+        res.extend(seasonal::menu_icon_packet(Some(&icon)));
+    }
+    for channel in CHANNELS.public() {
+        res.extend(channel.info_packet());
+    }
+    res.extend(ChoChannelInfoEnd::write());
+    res.extend(ChoFriendsList::new(user.friends.clone()).write());
+    res.extend(moderation::silence_end_packet(player.silence_remaining()));
+    res.extend(player.presence_packet());
+    res.extend(player.stats_packet());
+    if player.is_restricted() {
+        res.extend(ChoAccountRestricted::write());
+    }
+
+    for other in PLAYERS.all() {
+        res.extend(other.presence_packet());
+        res.extend(other.stats_packet());
+    }
+
+    if player.is_visible() {
+        PLAYERS.enqueue_all(&player.presence_packet());
+        PLAYERS.enqueue_all(&player.stats_packet());
+    }
+    PLAYERS.add(player.clone());
+    // restricted players can't chat, so they aren't put in any channels
+    if !player.is_restricted() {
+        for channel in CHANNELS.public().into_iter().filter(|c| c.auto_join()) {
+            channel.join(&player);
+        }
+    }
+
+    match tourney {
+        true => println!("[cho] {} logged in with the tournament client", user.name),
+        false => println!("[cho] {} logged in", user.name),
+    }
+
+    HttpResponse::Ok()
+        .append_header(("cho-token", player.token()))
+        .body(res)
 }
 
+/// Handles every packet in a request and returns the response along with
+/// anything queued up for the player since their last request.
+pub async fn handle_stream(data: Vec<u8>, player: &mut Player) -> Vec<u8> {
+    let mut reader = PacketReader::new(data);
+    let mut res = Vec::new();
+
+    // header: i16 id, u8 padding, u32 length
+    while reader.remaining() >= 7 {
+        let id = reader.read_i16();
+        reader.read_u8();
+        let len = (reader.read_u32() as usize).min(reader.remaining());
+        let mut payload = PacketReader::new(reader.read_bytes(len));
+
+        // checked for every packet, a restriction takes effect right away
+        let handlers = match player.is_restricted() {
+            true => &*RESTRICTED_PACKET_HANDLERS,
+            false => &*PACKET_HANDLERS,
+        };
+        let handler = match Packets::from_i16(id).and_then(|p| handlers.get(&p)) {
+            Some(handler) => handler,
+            None => continue,
+        };
+
+        match handler(player, &mut payload).await {
+            Ok(data) => res.extend(data),
+            Err(e) => println!("[cho] packet {} from {} failed: {}", id, player.name(), e),
+        }
+    }
+
+    res.extend(player.dequeue());
+    res
+}
 
 // pub async fn handle_stream(data: Vec<u8>, mut player: Player) -> Result<HttpResponse, Error> {
 //     let mut _reader = Reader::new(data);
@@ -1 +1,161 @@
+use actix_web::{http::header, web, Error, HttpRequest, HttpResponse};
+use std::fs;
 
+use crate::config;
+use crate::storage::data_path;
+use crate::structs::beatmap::Beatmap;
+use crate::structs::beatmap_source::{MapQuery, BEATMAP_SOURCE};
+
+/// Responds with a file, or 304 when the client's copy is still current.
+pub fn serve(req: &HttpRequest, data: Vec<u8>, content_type: &str) -> HttpResponse {
+    let etag = format!("\"{:x}\"", md5::compute(&data));
+
+    let cached = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| {
+            v.split(',')
+                .any(|tag| tag.trim() == etag || tag.trim() == "*")
+        });
+    if cached {
+        return HttpResponse::NotModified()
+            .append_header((header::ETAG, etag))
+            .finish();
+    }
+
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .append_header((header::ETAG, etag))
+        .append_header((header::CACHE_CONTROL, "public, max-age=86400"))
+        .body(data)
+}
+
+/// Serves the latest version of a map to clients updating an outdated copy.
+pub async fn get_osu_file(
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let map = match Beatmap::from_filename(&path) {
+        Some(map) => map,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    if map.osu_file().is_none() {
+        Beatmap::fetch_from_source(&MapQuery::Id(map.id)).await;
+    }
+
+    match fs::read(map.osu_file_path()) {
+        Ok(data) => Ok(serve(&req, data, "text/plain; charset=utf-8")),
+        Err(_) => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+/// Serves an asset of a set cached in `.data/<kind>/`, named by the set id
+/// followed by one of the suffixes (`<set id>l.jpg` for large thumbnails).
+/// Assets we don't have yet are fetched from the beatmap source and cached.
+async fn cached_asset(
+    req: &HttpRequest,
+    kind: &str,
+    name: &str,
+    suffixes: &[&str],
+    content_type: &str,
+) -> HttpResponse {
+    // only ever serve files named after a set id, nothing else in the cache
+    let valid = suffixes.iter().any(|suffix| {
+        name.strip_suffix(suffix)
+            .is_some_and(|id| !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()))
+    });
+    if !valid {
+        return HttpResponse::NotFound().finish();
+    }
+
+    let path = data_path().join(kind).join(name);
+    if let Ok(data) = fs::read(&path) {
+        return serve(req, data, content_type);
+    }
+
+    let source = match BEATMAP_SOURCE.as_ref() {
+        Some(source) => source,
+        None => return HttpResponse::NotFound().finish(),
+    };
+    match source.asset(kind, name).await {
+        Ok(Some(data)) => {
+            if let Err(e) = fs::create_dir_all(path.parent().unwrap())
+                .and_then(|_| fs::write(&path, &data))
+            {
+                println!("[map] failed to cache {}: {}", path.display(), e);
+            }
+            serve(req, data, content_type)
+        }
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            println!("[map] failed to fetch {}/{}: {}", kind, name, e);
+            HttpResponse::BadGateway().finish()
+        }
+    }
+}
+
+pub async fn get_thumbnail(
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    Ok(cached_asset(
+        &req,
+        "thumb",
+        &path,
+        &["l.jpg", ".jpg"],
+        "image/jpeg",
+    )
+    .await)
+}
+
+pub async fn get_preview(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    Ok(cached_asset(
+        &req,
+        "preview",
+        &path,
+        &[".mp3"],
+        "audio/mpeg",
+    )
+    .await)
+}
+
+/// Downloads a set's .osz for osu!direct; `/d/<set id>n` asks for it without
+/// the video, which is up to the source.
+pub async fn download_set(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let set_id = match path.trim_end_matches('n').parse::<i32>() {
+        Ok(set_id) => set_id,
+        Err(_) => return Ok(HttpResponse::BadRequest().finish()),
+    };
+    if !config::get().features.osu_direct {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    let source = match BEATMAP_SOURCE.as_ref() {
+        Some(source) => source,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    // let the client download from the mirror itself when it can
+    if let Some(url) = source.osz_url(set_id) {
+        return Ok(HttpResponse::Found()
+            .append_header((header::LOCATION, url))
+            .finish());
+    }
+
+    match source.osz_file(set_id).await {
+        Ok(Some(data)) => Ok(HttpResponse::Ok()
+            .content_type("application/x-osu-beatmap-archive")
+            .append_header((
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.osz\"", set_id),
+            ))
+            .body(data)),
+        Ok(None) => Ok(HttpResponse::NotFound().finish()),
+        Err(e) => {
+            println!("[direct] failed to fetch set {}: {}", set_id, e);
+            Ok(HttpResponse::BadGateway().finish())
+        }
+    }
+}
@@ -1,16 +1,36 @@
-use actix_web::{web::{self, Payload}, App, HttpResponse, HttpServer, Responder, Error, HttpRequest};
-use futures_util::stream::StreamExt as _;
-use std::future::Future;
+use actix_multipart::Multipart;
+use actix_web::{error, web, Error, HttpResponse};
+use futures_util::stream::TryStreamExt as _;
+use std::collections::HashMap;
+
+use crate::config;
+use crate::constants::gamemodes::GameMode;
+use crate::constants::mods::Mods;
+use crate::constants::privileges::Privileged;
+use crate::storage::{BEATMAPS, SCORES, STATS};
+use crate::structs::beatmap::{Beatmap, RankedStatus};
+use crate::structs::beatmap_source::{MapQuery, SearchQuery, BEATMAP_SOURCE};
+use crate::structs::bot;
+use crate::structs::leaderboard::{Leaderboard, LeaderboardType};
+use crate::structs::player::PLAYERS;
+use crate::structs::ranking::RANKINGS;
+use crate::structs::replay;
+use crate::structs::score::{Score, SubmissionStatus};
+use crate::structs::seasonal;
+use crate::structs::stats::Stats;
+use crate::structs::user::{self, User};
+use crate::utils::osu::crypto;
+use crate::utils::osu::replay::ReplayFrame;
 
 #[derive(serde::Serialize)]
-struct bancho_connect_res {
+struct BanchoConnectRes {
     detail: String,
 }
 
 pub async fn bancho_connect() -> Result<HttpResponse, Error> {
     Ok(HttpResponse::Unauthorized()
-        .append_header(("bancho-version", "0.9.0"))
-        .json(bancho_connect_res {
+        .append_header(("bancho-version", config::get().bancho_version.as_str()))
+        .json(BanchoConnectRes {
             detail: "Unauthorized".to_string(),
         }))
 }
@@ -34,7 +54,7 @@ pub async fn get_seasonal() -> Result<HttpResponse, Error> {
     // ):
     //     return "\n".join(map(str, player.seasonal)).encode()
 
-    Ok(HttpResponse::Ok().body("[\"https://i.ytimg.com/vi/IVkM_CreJa8/maxresdefault.jpg\"]"))
+    Ok(HttpResponse::Ok().json(seasonal::backgrounds()))
 }
 
 pub async fn lastfm() -> Result<HttpResponse, Error> {
@@ -52,4 +72,585 @@ pub async fn osu_error() -> Result<HttpResponse, Error> {
     Ok(HttpResponse::Ok().body(b"".to_vec()))
 }
 
+/// Reads a multipart form into its text fields, plus the replay which the
+/// client uploads as a file part sharing the `score` name.
+async fn read_score_form(
+    mut payload: Multipart,
+) -> Result<(HashMap<String, String>, Option<Vec<u8>>), Error> {
+    let mut form = HashMap::new();
+    let mut replay = None;
+
+    while let Some(mut field) = payload.try_next().await? {
+        let (name, is_file) = match field.content_disposition() {
+            Some(cd) => (
+                cd.get_name().unwrap_or_default().to_string(),
+                cd.get_filename().is_some(),
+            ),
+            None => continue,
+        };
+
+        let mut data = Vec::new();
+        while let Some(chunk) = field.try_next().await? {
+            data.extend_from_slice(&chunk);
+        }
+
+        if name == "score" && is_file {
+            replay = Some(data);
+        } else {
+            form.insert(name, String::from_utf8_lossy(&data).into_owned());
+        }
+    }
+
+    Ok((form, replay))
+}
+
+fn chart_entry<T: ToString>(name: &str, before: Option<T>, after: T) -> String {
+    format!(
+        "{}Before:{}|{}After:{}",
+        name,
+        before.map(|b| b.to_string()).unwrap_or_default(),
+        name,
+        after.to_string()
+    )
+}
+
+pub async fn submit_score(payload: Multipart) -> Result<HttpResponse, Error> {
+    let error = |msg: &'static str| Ok(HttpResponse::Ok().body(msg));
+    if !config::get().features.score_submission {
+        return error("error: disabled");
+    }
+
+    let (form, replay) = read_score_form(payload).await?;
+    let field = |name: &str| -> Result<&str, Error> {
+        form.get(name)
+            .map(|s| s.as_str())
+            .ok_or_else(|| error::ErrorBadRequest(format!("missing field {}", name)))
+    };
+
+    let (score_data, client_hash) = match crypto::decrypt_score_data(
+        field("score")?,
+        field("s")?,
+        field("iv")?,
+        field("osuver")?,
+    ) {
+        Ok(decrypted) => decrypted,
+        Err(e) => {
+            println!("[submit] failed to decrypt score: {}", e);
+            return error("error: no");
+        }
+    };
+
+    let mut submitted = match Score::from_submission(&score_data) {
+        Ok(submitted) => submitted,
+        Err(e) => {
+            println!("[submit] malformed score data: {}", e);
+            return error("error: no");
+        }
+    };
+
+    let user = match user::authenticate(&submitted.username, field("pass")?) {
+        Some(user) => user,
+        None => return error("error: pass"),
+    };
+    submitted.score.user_id = user.id;
+
+    let map = match Beatmap::fetch(&submitted.score.map_md5).await {
+        Some(map) => map,
+        None => return error("error: beatmap"),
+    };
+
+    // the md5 of the map file the client actually has loaded
+    if field("bmk")? != map.md5 {
+        println!(
+            "[submit] {} submitted a score with a mismatched map md5",
+            user.name
+        );
+        return error("error: no");
+    }
+
+    // nothing is kept of plays on maps without a leaderboard
+    if !map.has_leaderboard() {
+        return error("error: no");
+    }
+
+    let storyboard_md5 = form.get("sbk").map(|s| s.as_str()).unwrap_or_default();
+    if !storyboard_md5.is_empty()
+        && (storyboard_md5.len() != 32 || !storyboard_md5.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        return error("error: no");
+    }
+
+    // the client hash is `{osu path md5}:{adapters}:{adapters md5}:{uninstall md5}:{disk md5}:`
+    if client_hash.split(':').count() < 5 {
+        println!("[submit] {} submitted a malformed client hash", user.name);
+        return error("error: no");
+    }
+
+    if submitted.online_checksum(&client_hash, storyboard_md5) != submitted.score.online_checksum {
+        println!(
+            "[submit] {} submitted a score with an invalid checksum",
+            user.name
+        );
+        return error("error: no");
+    }
+
+    let mut score = submitted.score;
+
+    let checksum = score.online_checksum.clone();
+    if SCORES.find_one(|s| s.online_checksum == checksum).is_some() {
+        // the client resubmits when it doesn't get a response in time
+        return error("error: no");
+    }
 
+    if score.passed && replay.as_ref().is_none_or(|r| r.len() < 24) {
+        println!("[submit] {} submitted a pass without a replay", user.name);
+        return error("error: no");
+    }
+
+    if let Some(data) = replay.as_ref().filter(|_| score.passed) {
+        match ReplayFrame::parse_all(data) {
+            Ok(frames) if !frames.is_empty() => {}
+            Ok(_) => {
+                println!("[submit] {} submitted a replay with no frames", user.name);
+                return error("error: no");
+            }
+            Err(e) => {
+                println!("[submit] {} submitted an invalid replay: {}", user.name, e);
+                return error("error: no");
+            }
+        }
+    }
+
+    let time_field = if score.passed { "st" } else { "ft" };
+    score.time_elapsed = form
+        .get(time_field)
+        .and_then(|t| t.parse().ok())
+        .unwrap_or(0);
+
+    if let Some(file) = map.osu_file() {
+        score.pp = score.calculate_pp(&file);
+    }
+
+    let prev_best = Score::best_for(user.id, &map.md5, score.mode);
+
+    score.status = if !score.passed {
+        SubmissionStatus::Failed
+    } else if prev_best.as_ref().is_none_or(|p| score.beats(p)) {
+        SubmissionStatus::Best
+    } else {
+        SubmissionStatus::Submitted
+    };
+
+    let prev_rank = prev_best.as_ref().map(|p| p.leaderboard_rank());
+
+    let score = SCORES.insert(score)?;
+    if let Some(data) = replay.as_ref().filter(|_| score.passed) {
+        replay::save(score.id, data)?;
+    }
+    if score.status == SubmissionStatus::Best {
+        if let Some(prev) = &prev_best {
+            SCORES.update(prev.id, |s| s.status = SubmissionStatus::Submitted)?;
+        }
+    }
+
+    BEATMAPS.update(map.id as i64, |m| {
+        m.plays += 1;
+        if score.passed {
+            m.passes += 1;
+        }
+    })?;
+
+    let prev_stats = Stats::get(user.id, score.mode);
+    let prev_global_rank = RANKINGS.global_rank(user.id, score.mode);
+    let stats = STATS.insert(Stats::recalculate(user.id, score.mode))?;
+    if !user.is_restricted() {
+        RANKINGS.update(&stats, &user.country);
+    }
+
+    // restricted players still see their own stats update
+    for player in PLAYERS.sessions(user.id) {
+        let packet = player.stats_packet();
+        match player.is_visible() {
+            true => PLAYERS.enqueue_all(&packet),
+            false => player.enqueue(&packet),
+        }
+    }
+
+    println!(
+        "[submit] {} submitted a {} on {} ({:?})",
+        user.name,
+        score.grade.as_str(),
+        map.full_name(),
+        score.status
+    );
+
+    if !score.passed {
+        return error("error: no");
+    }
+
+    // only announce taking #1, not improving on it
+    if score.status == SubmissionStatus::Best
+        && score.leaderboard_rank() == 1
+        && prev_rank != Some(1)
+    {
+        let domain = &config::get().domain;
+        let mut announcement = format!(
+            "[https://osu.{}/u/{} {}] achieved #1 on [https://osu.{}/b/{} {}]",
+            domain,
+            user.id,
+            user.name,
+            domain,
+            map.id,
+            map.full_name()
+        );
+        if !score.mods().is_empty() {
+            announcement.push_str(&format!(" +{}", score.mods().acronyms()));
+        }
+        announcement.push_str(&format!(" ({}, {:.2}pp)", score.mode.name(), score.pp));
+        bot::announce(&announcement);
+    }
+
+    let prev = prev_best.as_ref();
+    let charts = [
+        // beatmap info chart
+        format!("beatmapId:{}", map.id),
+        format!("beatmapSetId:{}", map.set_id),
+        format!("beatmapPlaycount:{}", map.plays + 1),
+        format!("beatmapPasscount:{}", map.passes + 1),
+        format!("approvedDate:{}", map.last_update),
+        "\n".to_string(),
+        // beatmap ranking chart
+        "chartId:beatmap".to_string(),
+        format!("chartUrl:https://osu.ppy.sh/beatmapsets/{}", map.set_id),
+        "chartName:Beatmap Ranking".to_string(),
+        chart_entry("rank", prev_rank, score.leaderboard_rank()),
+        chart_entry("rankedScore", prev.map(|p| p.score), score.score),
+        chart_entry("totalScore", prev.map(|p| p.score), score.score),
+        chart_entry("maxCombo", prev.map(|p| p.max_combo), score.max_combo),
+        chart_entry(
+            "accuracy",
+            prev.map(|p| format!("{:.2}", p.acc)),
+            format!("{:.2}", score.acc),
+        ),
+        chart_entry(
+            "pp",
+            prev.map(|p| p.pp.round() as i32),
+            score.pp.round() as i32,
+        ),
+        format!("onlineScoreId:{}", score.id),
+        "\n".to_string(),
+        // overall ranking chart
+        "chartId:overall".to_string(),
+        format!("chartUrl:https://osu.ppy.sh/users/{}", user.id),
+        "chartName:Overall Ranking".to_string(),
+        chart_entry(
+            "rank",
+            Some(prev_global_rank),
+            RANKINGS.global_rank(user.id, score.mode),
+        ),
+        chart_entry(
+            "rankedScore",
+            Some(prev_stats.ranked_score),
+            stats.ranked_score,
+        ),
+        chart_entry(
+            "totalScore",
+            Some(prev_stats.total_score),
+            stats.total_score,
+        ),
+        chart_entry("maxCombo", Some(prev_stats.max_combo), stats.max_combo),
+        chart_entry(
+            "accuracy",
+            Some(format!("{:.2}", prev_stats.acc)),
+            format!("{:.2}", stats.acc),
+        ),
+        chart_entry("pp", Some(prev_stats.pp), stats.pp),
+        "achievements-new:".to_string(),
+    ];
+
+    Ok(HttpResponse::Ok().body(charts.join("|")))
+}
+
+#[derive(serde::Deserialize)]
+pub struct GetScoresParams {
+    /// Leaderboard type, see [`LeaderboardType`].
+    v: i32,
+    /// md5 of the map the client has.
+    c: String,
+    /// Filename of the map the client has.
+    f: String,
+    m: u8,
+    /// Beatmap set id, -1 if the client doesn't know it.
+    i: i32,
+    mods: i32,
+    us: String,
+    ha: String,
+}
+
+fn score_listing(score: &Score, user: &User, rank: usize) -> String {
+    format!(
+        "{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|1",
+        score.id,
+        user.name,
+        // relax and autopilot leaderboards show pp in place of score
+        if score.mode.sorts_by_pp() {
+            score.pp.round() as i64
+        } else {
+            score.score
+        },
+        score.max_combo,
+        score.n50,
+        score.n100,
+        score.n300,
+        score.nmiss,
+        score.nkatu,
+        score.ngeki,
+        score.perfect as i32,
+        score.mods,
+        user.id,
+        rank,
+        score.play_time,
+    )
+}
+
+pub async fn get_scores(params: web::Query<GetScoresParams>) -> Result<HttpResponse, Error> {
+    let player = match user::authenticate(&params.us, &params.ha) {
+        Some(player) => player,
+        None => return Ok(HttpResponse::Ok().body("error: pass")),
+    };
+
+    let map = match Beatmap::fetch(&params.c).await {
+        Some(map) => map,
+        None => {
+            // a different version of a map we know means the client's is outdated
+            let outdated = BEATMAPS
+                .find_one(|m| m.filename == params.f && (params.i <= 0 || m.set_id == params.i))
+                .is_some();
+
+            return Ok(HttpResponse::Ok().body(if outdated { "1|false" } else { "-1|false" }));
+        }
+    };
+
+    if !map.has_leaderboard() {
+        return Ok(HttpResponse::Ok().body(format!("{}|false", map.status as i32)));
+    }
+
+    let mods = Mods::from_i32(params.mods);
+    let mode = GameMode::from_params(params.m, mods);
+    let kind = LeaderboardType::from_i32(params.v);
+    let leaderboard = Leaderboard::fetch(&map.md5, mode, kind, mods, &player);
+
+    let mut lines = vec![
+        format!(
+            "{}|false|{}|{}|{}|0|",
+            map.status as i32,
+            map.id,
+            map.set_id,
+            leaderboard.scores.len()
+        ),
+        // offset, display title and rating
+        "0".to_string(),
+        format!("[bold:0,size:20]{}|{}", map.artist, map.title),
+        "10.0".to_string(),
+    ];
+
+    lines.push(match &leaderboard.personal_best {
+        Some((score, rank)) => score_listing(score, &player, *rank),
+        None => String::new(),
+    });
+
+    for (i, (score, user)) in leaderboard.scores.iter().enumerate() {
+        lines.push(score_listing(score, user, i + 1));
+    }
+
+    Ok(HttpResponse::Ok().body(lines.join("\n")))
+}
+
+#[derive(serde::Deserialize)]
+pub struct GetReplayParams {
+    /// The score id.
+    c: i64,
+    u: String,
+    h: String,
+}
+
+pub async fn get_replay(params: web::Query<GetReplayParams>) -> Result<HttpResponse, Error> {
+    let player = match user::authenticate(&params.u, &params.h) {
+        Some(player) => player,
+        None => return Ok(HttpResponse::Unauthorized().finish()),
+    };
+
+    let score = match SCORES.get(params.c) {
+        Some(score) => score,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    let data = match replay::load(score.id) {
+        Some(data) => data,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    // watching your own replays doesn't count
+    if score.user_id != player.id {
+        let mut stats = Stats::get(score.user_id, score.mode);
+        stats.replay_views += 1;
+        STATS.insert(stats)?;
+    }
+
+    Ok(HttpResponse::Ok().body(data))
+}
+
+#[derive(serde::Deserialize)]
+pub struct SearchParams {
+    u: String,
+    h: String,
+    /// osu!direct's status filter, see [`direct_statuses`].
+    r: i32,
+    q: String,
+    /// The client's mode, -1 for any.
+    m: i32,
+    /// Page of 100 sets.
+    p: usize,
+}
+
+/// Statuses included by osu!direct's filter: 0 ranked, 2 pending,
+/// 3 qualified, 4 any, 5 graveyard, 7 played and 8 loved.
+fn direct_statuses(filter: i32) -> Vec<RankedStatus> {
+    match filter {
+        0 | 7 => vec![RankedStatus::Ranked, RankedStatus::Approved],
+        2 | 5 => vec![RankedStatus::Pending],
+        3 => vec![RankedStatus::Qualified],
+        8 => vec![RankedStatus::Loved],
+        _ => Vec::new(),
+    }
+}
+
+/// A set as osu!direct lists it, with its difficulties if given.
+fn direct_set(map: &Beatmap, diffs: &[&Beatmap]) -> String {
+    let mut line = format!(
+        "{}.osz|{}|{}|{}|{}|10.0|{}|{}|0|0|0|0|0",
+        map.set_id,
+        map.artist,
+        map.title,
+        map.creator,
+        map.status.as_api(),
+        map.last_update,
+        map.set_id
+    );
+
+    if !diffs.is_empty() {
+        let diffs = diffs
+            .iter()
+            .map(|m| {
+                format!(
+                    "[{:.2}⭐] {} {{cs: {} / od: {} / ar: {} / hp: {}}}@{}",
+                    m.diff, m.version, m.cs, m.od, m.ar, m.hp, m.mode
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        line.push('|');
+        line.push_str(&diffs);
+    }
+    line
+}
+
+pub async fn osu_search(params: web::Query<SearchParams>) -> Result<HttpResponse, Error> {
+    if user::authenticate(&params.u, &params.h).is_none() {
+        return Ok(HttpResponse::Ok().body("error: pass"));
+    }
+    // shown to the player in place of the results
+    if !config::get().features.osu_direct {
+        return Ok(HttpResponse::Ok().body("osu!direct is disabled on this server"));
+    }
+
+    let query = SearchQuery {
+        // the client's sort tabs come through as queries
+        query: match params.q.as_str() {
+            "Newest" | "Top Rated" | "Most Played" => String::new(),
+            q => q.to_string(),
+        },
+        mode: (0..=3).contains(&params.m).then_some(params.m as u8),
+        statuses: direct_statuses(params.r),
+        offset: params.p * 100,
+        amount: 100,
+    };
+
+    let mut maps = match BEATMAP_SOURCE.as_ref() {
+        Some(source) => source.search(&query).await.unwrap_or_else(|e| {
+            println!("[direct] search for {:?} failed: {}", query.query, e);
+            Vec::new()
+        }),
+        None => Vec::new(),
+    };
+
+    if maps.is_empty() {
+        maps = Beatmap::search(&query);
+    } else {
+        // our statuses win over the source's
+        for map in maps.iter_mut() {
+            if let Some(stored) = Beatmap::from_id(map.id) {
+                map.status = stored.status;
+            }
+        }
+    }
+
+    let mut sets: Vec<(i32, Vec<&Beatmap>)> = Vec::new();
+    for map in &maps {
+        match sets.iter_mut().find(|(id, _)| *id == map.set_id) {
+            Some((_, diffs)) => diffs.push(map),
+            None => sets.push((map.set_id, vec![map])),
+        }
+    }
+
+    // 101 tells the client there's another page
+    let mut lines = vec![if sets.len() >= 100 {
+        "101".to_string()
+    } else {
+        sets.len().to_string()
+    }];
+    for (_, diffs) in &sets {
+        lines.push(direct_set(diffs[0], diffs));
+    }
+
+    Ok(HttpResponse::Ok().body(lines.join("\n")))
+}
+
+#[derive(serde::Deserialize)]
+pub struct SearchSetParams {
+    u: String,
+    h: String,
+    s: Option<i32>,
+    b: Option<i32>,
+}
+
+pub async fn osu_search_set(params: web::Query<SearchSetParams>) -> Result<HttpResponse, Error> {
+    if user::authenticate(&params.u, &params.h).is_none() {
+        return Ok(HttpResponse::Ok().body("error: pass"));
+    }
+    if !config::get().features.osu_direct {
+        return Ok(HttpResponse::Ok().finish());
+    }
+
+    let query = match (params.s, params.b) {
+        (Some(set_id), _) => MapQuery::SetId(set_id),
+        (None, Some(id)) => MapQuery::Id(id),
+        (None, None) => return Ok(HttpResponse::Ok().finish()),
+    };
+
+    let stored = match &query {
+        MapQuery::SetId(set_id) => Beatmap::from_set_id(*set_id).into_iter().next(),
+        MapQuery::Id(id) => Beatmap::from_id(*id),
+        MapQuery::Md5(md5) => Beatmap::from_md5(md5),
+    };
+    let map = match stored {
+        Some(map) => Some(map),
+        None => Beatmap::fetch_from_source(&query).await.into_iter().next(),
+    };
+
+    Ok(match map {
+        Some(map) => HttpResponse::Ok().body(direct_set(&map, &[])),
+        // an empty response makes the client say the map isn't available
+        None => HttpResponse::Ok().finish(),
+    })
+}
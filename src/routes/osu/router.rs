@@ -0,0 +1,135 @@
+use actix_web::{guard, http::header, middleware, web, HttpRequest, HttpResponse};
+
+use super::domains::{api, ava, cho, map, osu};
+use crate::middlewares;
+use crate::structs::avatar::MAX_UPLOAD_SIZE;
+
+/// Subdomains the client talks to bancho on.
+const CHO_SUBDOMAINS: &[&str] = &["c", "c4", "ce"];
+
+/// A guard matching any of `subdomains` under `domain`.
+fn hosts(subdomains: &[&str], domain: &str) -> guard::AnyGuard {
+    let mut hosts = subdomains
+        .iter()
+        .map(|sub| guard::Host(format!("{}.{}", sub, domain)));
+
+    let first = hosts.next().expect("a domain needs at least one subdomain");
+    hosts.fold(guard::Any(first), |any, host| any.or(host))
+}
+
+fn cho_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/", web::post().to(cho::packet_router));
+}
+
+fn osu_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route(
+        "/web/bancho_connect.php",
+        web::get().to(osu::bancho_connect),
+    )
+    .route("/web/osu-getfriends.php", web::get().to(osu::get_friends))
+    .route("/web/lastfm.php", web::get().to(osu::lastfm))
+    .route("/web/osu-getseasonal.php", web::get().to(osu::get_seasonal))
+    .route("/web/osu-error.php", web::get().to(osu::osu_error))
+    .route(
+        "/web/osu-osz2-getscores.php",
+        web::get().to(osu::get_scores),
+    )
+    .route("/web/osu-getreplay.php", web::get().to(osu::get_replay))
+    .route(
+        "/web/osu-submit-modular-selector.php",
+        web::post().to(osu::submit_score),
+    )
+    .route("/web/osu-search.php", web::get().to(osu::osu_search))
+    .route(
+        "/web/osu-search-set.php",
+        web::get().to(osu::osu_search_set),
+    )
+    // the client fetches .osu files and downloads sets from osu. rather
+    // than b.
+    .route("/web/maps/{filename}", web::get().to(map::get_osu_file))
+    .route("/d/{set_id}", web::get().to(map::download_set));
+}
+
+fn ava_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/{user_id}", web::get().to(ava::get_avatar));
+}
+
+fn map_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/thumb/{name}", web::get().to(map::get_thumbnail))
+        .route("/preview/{name}", web::get().to(map::get_preview));
+}
+
+fn api_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/v1/get_replay", web::get().to(api::get_replay))
+        .route("/v1/get_leaderboard", web::get().to(api::get_leaderboard))
+        .route("/v1/get_match", web::get().to(api::get_match))
+        .route(
+            "/v1/get_moderation_log",
+            web::get().to(api::get_moderation_log),
+        )
+        .route(
+            "/v1/get_chat_filter_log",
+            web::get().to(api::get_chat_filter_log),
+        )
+        .service(
+            web::resource("/v1/upload_avatar")
+                .app_data(web::PayloadConfig::new(MAX_UPLOAD_SIZE))
+                .route(web::post().to(ava::upload_avatar)),
+        );
+}
+
+async fn not_found() -> HttpResponse {
+    HttpResponse::NotFound().body("not found")
+}
+
+async fn unknown_host(req: HttpRequest) -> HttpResponse {
+    let host = req.connection_info().host().to_string();
+    HttpResponse::NotFound().body(format!("unknown host {}", host))
+}
+
+/// Routes each subdomain of `domain` to its module, each behind its own
+/// middleware; requests for any other host get a 404.
+pub fn configure(domain: &str) -> impl FnOnce(&mut web::ServiceConfig) {
+    let domain = domain.to_string();
+
+    move |cfg| {
+        cfg.service(
+            web::scope("")
+                .guard(hosts(CHO_SUBDOMAINS, &domain))
+                .wrap(middleware::DefaultHeaders::new().add(("cho-protocol", "19")))
+                .configure(cho_routes)
+                .default_service(web::to(not_found)),
+        )
+        .service(
+            web::scope("")
+                .guard(hosts(&["osu"], &domain))
+                .wrap(middleware::from_fn(middlewares::log_request))
+                .configure(osu_routes)
+                .default_service(web::to(not_found)),
+        )
+        .service(
+            web::scope("")
+                .guard(hosts(&["a"], &domain))
+                .configure(ava_routes)
+                .default_service(web::to(not_found)),
+        )
+        .service(
+            web::scope("")
+                .guard(hosts(&["b"], &domain))
+                .configure(map_routes)
+                .default_service(web::to(not_found)),
+        )
+        .service(
+            web::scope("")
+                .guard(hosts(&["api"], &domain))
+                .wrap(middleware::from_fn(middlewares::log_request))
+                .wrap(
+                    middleware::DefaultHeaders::new()
+                        .add((header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")),
+                )
+                .configure(api_routes)
+                .default_service(web::to(not_found)),
+        )
+        .default_service(web::to(unknown_host));
+    }
+}
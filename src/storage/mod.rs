@@ -0,0 +1,159 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::{Mutex, RwLock};
+
+use crate::config::{self, StorageBackend};
+use crate::structs::beatmap::Beatmap;
+use crate::structs::chat_filter::FilterEvent;
+use crate::structs::match_history::{MatchEvent, MatchRecord};
+use crate::structs::moderation::LogEntry;
+use crate::structs::score::Score;
+use crate::structs::seasonal::ScheduledArt;
+use crate::structs::stats::Stats;
+use crate::structs::user::User;
+
+/// Where tables and files are kept, `data_dir` in the config.
+pub fn data_path() -> PathBuf {
+    config::get().data_dir.clone()
+}
+
+lazy_static::lazy_static! {
+    pub static ref USERS: Table<User> = Table::open("users");
+    pub static ref SCORES: Table<Score> = Table::open("scores");
+    pub static ref STATS: Table<Stats> = Table::open("stats");
+    pub static ref BEATMAPS: Table<Beatmap> = Table::open("beatmaps");
+    pub static ref MATCH_HISTORY: Table<MatchRecord> = Table::open("matches");
+    pub static ref MATCH_EVENTS: Table<MatchEvent> = Table::open("match_events");
+    pub static ref MODERATION_LOG: Table<LogEntry> = Table::open("moderation_log");
+    pub static ref CHAT_FILTER_LOG: Table<FilterEvent> = Table::open("chat_filter_log");
+    pub static ref SEASONAL: Table<ScheduledArt> = Table::open("seasonal");
+}
+
+/// A row that can be kept in a [`Table`].
+pub trait Row: Serialize + DeserializeOwned + Clone + Send + Sync {
+    fn id(&self) -> i64;
+
+    /// Called on insert when the row doesn't carry an id of its own yet.
+    fn set_id(&mut self, id: i64);
+}
+
+/// An in-memory table persisted as an append-only json lines file, one row per
+/// line; rows written later replace earlier ones with the same id on load.
+/// With the memory backend there's no file.
+pub struct Table<T: Row> {
+    path: Option<PathBuf>,
+    rows: RwLock<BTreeMap<i64, T>>,
+    file: Mutex<Option<File>>,
+}
+
+impl<T: Row> Table<T> {
+    pub fn open(name: &str) -> Self {
+        let mut rows = BTreeMap::new();
+        let path = match config::get().storage {
+            StorageBackend::Jsonl => Some(data_path().join(format!("{}.jsonl", name))),
+            StorageBackend::Memory => None,
+        };
+
+        if let Some(Ok(file)) = path.as_ref().map(File::open) {
+            for (i, line) in BufReader::new(file).lines().enumerate() {
+                let line = match line {
+                    Ok(line) if !line.trim().is_empty() => line,
+                    _ => continue,
+                };
+
+                match serde_json::from_str::<T>(&line) {
+                    Ok(row) => {
+                        rows.insert(row.id(), row);
+                    }
+                    Err(e) => println!("[storage] {}.jsonl:{}: {}", name, i + 1, e),
+                }
+            }
+        }
+
+        Self {
+            path,
+            rows: RwLock::new(rows),
+            file: Mutex::new(None),
+        }
+    }
+
+    fn append(&self, row: &T) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let mut file = self.file.lock().unwrap();
+
+        if file.is_none() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            *file = Some(OpenOptions::new().create(true).append(true).open(path)?);
+        }
+
+        let mut line = serde_json::to_vec(row)?;
+        line.push(b'\n');
+        file.as_mut().unwrap().write_all(&line)
+    }
+
+    pub fn get(&self, id: i64) -> Option<T> {
+        self.rows.read().unwrap().get(&id).cloned()
+    }
+
+    pub fn find_one<F: Fn(&T) -> bool>(&self, pred: F) -> Option<T> {
+        self.rows
+            .read()
+            .unwrap()
+            .values()
+            .find(|r| pred(r))
+            .cloned()
+    }
+
+    pub fn find<F: Fn(&T) -> bool>(&self, pred: F) -> Vec<T> {
+        self.rows
+            .read()
+            .unwrap()
+            .values()
+            .filter(|r| pred(r))
+            .cloned()
+            .collect()
+    }
+
+    pub fn all(&self) -> Vec<T> {
+        self.rows.read().unwrap().values().cloned().collect()
+    }
+
+    /// Inserts a row, assigning it the next free id if it has none (id 0).
+    pub fn insert(&self, mut row: T) -> io::Result<T> {
+        let mut rows = self.rows.write().unwrap();
+
+        if row.id() == 0 {
+            let next = rows.keys().next_back().map_or(1, |id| id + 1);
+            row.set_id(next);
+        }
+
+        self.append(&row)?;
+        rows.insert(row.id(), row.clone());
+        Ok(row)
+    }
+
+    /// Applies `f` to the row with the given id, returning the updated row.
+    pub fn update<F: FnOnce(&mut T)>(&self, id: i64, f: F) -> io::Result<Option<T>> {
+        let mut rows = self.rows.write().unwrap();
+
+        let row = match rows.get_mut(&id) {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+
+        let mut updated = row.clone();
+        f(&mut updated);
+        self.append(&updated)?;
+        *row = updated.clone();
+
+        Ok(Some(updated))
+    }
+}
@@ -0,0 +1,141 @@
+use image::imageops::FilterType;
+use image::{ImageFormat, ImageReader, Rgba, RgbaImage};
+use std::fs;
+use std::io::{self, Cursor};
+use std::path::PathBuf;
+
+use crate::storage::data_path;
+
+/// Avatars are stored and served at this size.
+pub const AVATAR_SIZE: u32 = 256;
+/// Largest upload we'll look at, in bytes.
+pub const MAX_UPLOAD_SIZE: usize = 4 * 1024 * 1024;
+const MIN_DIMENSION: u32 = 64;
+const MAX_DIMENSION: u32 = 4096;
+
+lazy_static::lazy_static! {
+    /// Served to users without an avatar, unless `.data/avatars/default.png`
+    /// overrides it.
+    static ref DEFAULT_AVATAR: Vec<u8> = {
+        let image = RgbaImage::from_pixel(AVATAR_SIZE, AVATAR_SIZE, Rgba([128, 128, 128, 255]));
+        encode_png(&image)
+    };
+}
+
+#[derive(Debug)]
+pub enum AvatarError {
+    TooLarge,
+    UnsupportedFormat,
+    InvalidDimensions(u32, u32),
+    InvalidImage(String),
+    Io(io::Error),
+}
+
+impl std::fmt::Display for AvatarError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AvatarError::TooLarge => write!(f, "avatars can be at most {} bytes", MAX_UPLOAD_SIZE),
+            AvatarError::UnsupportedFormat => write!(f, "avatars have to be png, jpeg or gif"),
+            AvatarError::InvalidDimensions(w, h) => write!(
+                f,
+                "avatars have to be between {0}x{0} and {1}x{1}, not {2}x{3}",
+                MIN_DIMENSION, MAX_DIMENSION, w, h
+            ),
+            AvatarError::InvalidImage(e) => write!(f, "invalid image: {}", e),
+            AvatarError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for AvatarError {}
+
+fn avatar_path(name: &str) -> PathBuf {
+    data_path().join("avatars").join(format!("{}.png", name))
+}
+
+fn encode_png(image: &RgbaImage) -> Vec<u8> {
+    let mut data = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+        .expect("encoding into memory can't fail");
+    data
+}
+
+/// Checks an uploaded image and turns it into a square avatar, cropping the
+/// longer side around the centre.
+pub fn process(data: &[u8]) -> Result<Vec<u8>, AvatarError> {
+    if data.len() > MAX_UPLOAD_SIZE {
+        return Err(AvatarError::TooLarge);
+    }
+
+    let open = || {
+        ImageReader::new(Cursor::new(data))
+            .with_guessed_format()
+            .map_err(AvatarError::Io)
+    };
+
+    let reader = open()?;
+    if !matches!(
+        reader.format(),
+        Some(ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif)
+    ) {
+        return Err(AvatarError::UnsupportedFormat);
+    }
+
+    // check the size before decoding, so a tiny file can't make us allocate
+    // a huge image
+    let (width, height) = reader
+        .into_dimensions()
+        .map_err(|e| AvatarError::InvalidImage(e.to_string()))?;
+    let valid = MIN_DIMENSION..=MAX_DIMENSION;
+    if !valid.contains(&width) || !valid.contains(&height) {
+        return Err(AvatarError::InvalidDimensions(width, height));
+    }
+
+    let image = open()?
+        .decode()
+        .map_err(|e| AvatarError::InvalidImage(e.to_string()))?;
+    let avatar = image.resize_to_fill(AVATAR_SIZE, AVATAR_SIZE, FilterType::Lanczos3);
+
+    Ok(encode_png(&avatar.to_rgba8()))
+}
+
+/// Validates, resizes and stores a user's new avatar.
+pub fn save(user_id: i32, data: &[u8]) -> Result<(), AvatarError> {
+    let avatar = process(data)?;
+
+    let path = avatar_path(&user_id.to_string());
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(AvatarError::Io)?;
+    }
+    fs::write(path, avatar).map_err(AvatarError::Io)
+}
+
+/// The user's avatar as a png, or the default one.
+pub fn load(user_id: i32) -> Vec<u8> {
+    fs::read(avatar_path(&user_id.to_string()))
+        .or_else(|_| fs::read(avatar_path("default")))
+        .unwrap_or_else(|_| DEFAULT_AVATAR.clone())
+}
+
+#[test]
+fn test_process_avatar() {
+    let image = RgbaImage::from_pixel(512, 128, Rgba([255, 0, 0, 255]));
+    let avatar = process(&encode_png(&image)).unwrap();
+
+    let avatar = image::load_from_memory(&avatar).unwrap();
+    assert_eq!(
+        (avatar.width(), avatar.height()),
+        (AVATAR_SIZE, AVATAR_SIZE)
+    );
+
+    let tiny = RgbaImage::from_pixel(16, 16, Rgba([0, 0, 0, 255]));
+    assert!(matches!(
+        process(&encode_png(&tiny)),
+        Err(AvatarError::InvalidDimensions(16, 16))
+    ));
+    assert!(matches!(
+        process(b"not an image"),
+        Err(AvatarError::UnsupportedFormat)
+    ));
+}
@@ -0,0 +1,319 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::constants::gamemodes::GameMode;
+use crate::constants::mods::Mods;
+use crate::storage::{data_path, Row, BEATMAPS};
+use crate::structs::beatmap_source::{MapQuery, SearchQuery, BEATMAP_SOURCE};
+use crate::utils::osu::beatmap::BeatmapFile;
+use crate::utils::osu::performance::{self, ScoreState};
+
+/// Ranked status as the client's leaderboards understand it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(into = "i32", from = "i32")]
+pub enum RankedStatus {
+    NotSubmitted = -1,
+    Pending = 0,
+    UpdateAvailable = 1,
+    Ranked = 2,
+    Approved = 3,
+    Qualified = 4,
+    Loved = 5,
+}
+
+impl From<RankedStatus> for i32 {
+    fn from(status: RankedStatus) -> i32 {
+        status as i32
+    }
+}
+
+impl From<i32> for RankedStatus {
+    fn from(value: i32) -> Self {
+        match value {
+            -1 => RankedStatus::NotSubmitted,
+            1 => RankedStatus::UpdateAvailable,
+            2 => RankedStatus::Ranked,
+            3 => RankedStatus::Approved,
+            4 => RankedStatus::Qualified,
+            5 => RankedStatus::Loved,
+            _ => RankedStatus::Pending,
+        }
+    }
+}
+
+impl RankedStatus {
+    /// From the osu! api's statuses: -2 graveyard, -1 wip, 0 pending,
+    /// 1 ranked, 2 approved, 3 qualified, 4 loved.
+    pub fn from_api(status: i32) -> Self {
+        match status {
+            1 => RankedStatus::Ranked,
+            2 => RankedStatus::Approved,
+            3 => RankedStatus::Qualified,
+            4 => RankedStatus::Loved,
+            _ => RankedStatus::Pending,
+        }
+    }
+
+    pub fn as_api(&self) -> i32 {
+        match self {
+            RankedStatus::Ranked => 1,
+            RankedStatus::Approved => 2,
+            RankedStatus::Qualified => 3,
+            RankedStatus::Loved => 4,
+            _ => 0,
+        }
+    }
+}
+
+impl std::str::FromStr for RankedStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "pending" | "unrank" => RankedStatus::Pending,
+            "ranked" | "rank" => RankedStatus::Ranked,
+            "approved" | "approve" => RankedStatus::Approved,
+            "qualified" | "qualify" => RankedStatus::Qualified,
+            "loved" | "love" => RankedStatus::Loved,
+            _ => return Err(format!("invalid status {}", s)),
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Beatmap {
+    pub id: i32,
+    pub set_id: i32,
+    pub md5: String,
+
+    pub artist: String,
+    pub title: String,
+    pub version: String,
+    pub creator: String,
+    pub filename: String,
+    pub last_update: String,
+    pub total_length: i32,
+    pub max_combo: i32,
+
+    pub status: RankedStatus,
+    /// Set when staff changed the status, so refreshing the map from a
+    /// source doesn't undo it.
+    #[serde(default)]
+    pub frozen: bool,
+    pub plays: i32,
+    pub passes: i32,
+    pub mode: u8,
+    pub bpm: f32,
+    pub cs: f32,
+    pub ar: f32,
+    pub od: f32,
+    pub hp: f32,
+    pub diff: f32,
+}
+
+impl Row for Beatmap {
+    fn id(&self) -> i64 {
+        self.id as i64
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = id as i32;
+    }
+}
+
+impl Beatmap {
+    pub fn from_md5(md5: &str) -> Option<Self> {
+        BEATMAPS.find_one(|m| m.md5 == md5)
+    }
+
+    pub fn from_id(id: i32) -> Option<Self> {
+        BEATMAPS.get(id as i64)
+    }
+
+    pub fn from_set_id(set_id: i32) -> Vec<Self> {
+        BEATMAPS.find(|m| m.set_id == set_id)
+    }
+
+    pub fn from_filename(filename: &str) -> Option<Self> {
+        BEATMAPS.find_one(|m| m.filename == filename)
+    }
+
+    /// Searches the maps we have, newest sets first.
+    pub fn search(query: &SearchQuery) -> Vec<Self> {
+        let mut maps = BEATMAPS.find(|m| query.matches(m));
+        maps.sort_by_key(|m| std::cmp::Reverse(m.set_id));
+        query.paginate(maps)
+    }
+
+    /// Looks a map up by md5, asking the beatmap source about maps we don't
+    /// know yet.
+    pub async fn fetch(md5: &str) -> Option<Self> {
+        if let Some(map) = Beatmap::from_md5(md5) {
+            return Some(map);
+        }
+
+        let query = MapQuery::md5(md5)?;
+        Beatmap::fetch_from_source(&query)
+            .await
+            .into_iter()
+            .next()
+    }
+
+    /// Looks a map up by id, asking the beatmap source about maps we don't
+    /// know yet.
+    pub async fn fetch_by_id(id: i32) -> Option<Self> {
+        if let Some(map) = Beatmap::from_id(id) {
+            return Some(map);
+        }
+
+        Beatmap::fetch_from_source(&MapQuery::Id(id))
+            .await
+            .into_iter()
+            .next()
+    }
+
+    /// Looks maps up on the beatmap source, storing them along with their
+    /// .osu files.
+    pub async fn fetch_from_source(query: &MapQuery) -> Vec<Self> {
+        let source = match BEATMAP_SOURCE.as_ref() {
+            Some(source) => source,
+            None => return Vec::new(),
+        };
+
+        let maps = match source.lookup(query).await {
+            Ok(maps) => maps,
+            Err(e) => {
+                println!("[beatmap] failed to look up {:?}: {}", query, e);
+                return Vec::new();
+            }
+        };
+
+        let mut saved = Vec::new();
+        for map in maps {
+            let name = map.full_name();
+            let map = match map.save() {
+                Ok(map) => map,
+                Err(e) => {
+                    println!("[beatmap] failed to save {}: {}", name, e);
+                    continue;
+                }
+            };
+
+            if map.osu_file().is_none() {
+                match source.osu_file(map.id).await {
+                    Ok(Some(data)) => {
+                        let path = map.osu_file_path();
+                        if let Err(e) = fs::create_dir_all(path.parent().unwrap())
+                            .and_then(|_| fs::write(&path, data))
+                        {
+                            println!("[beatmap] failed to save {}: {}", path.display(), e);
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => println!("[beatmap] failed to fetch .osu of {}: {}", map.id, e),
+                }
+            }
+
+            saved.push(map);
+        }
+        saved
+    }
+
+    /// Builds a map from a parsed .osu file; it's pending until staff rank it.
+    pub fn from_file(file: &BeatmapFile, filename: &str, last_update: &str) -> Self {
+        let mode = file.general.mode;
+        let stars = performance::calculate(
+            file,
+            GameMode::from_params(mode, Mods::empty()),
+            Mods::empty(),
+            &ScoreState::default(),
+        )
+        .stars;
+
+        Self {
+            id: file.metadata.beatmap_id,
+            set_id: file.metadata.beatmap_set_id,
+            md5: file.md5.clone(),
+            artist: file.metadata.artist.clone(),
+            title: file.metadata.title.clone(),
+            version: file.metadata.version.clone(),
+            creator: file.metadata.creator.clone(),
+            filename: filename.to_string(),
+            last_update: last_update.to_string(),
+            total_length: (file.length() / 1000.0) as i32,
+            max_combo: file.max_combo() as i32,
+            status: RankedStatus::Pending,
+            frozen: false,
+            plays: 0,
+            passes: 0,
+            mode,
+            bpm: file.bpm() as f32,
+            cs: file.difficulty.cs as f32,
+            ar: file.difficulty.ar as f32,
+            od: file.difficulty.od as f32,
+            hp: file.difficulty.hp as f32,
+            diff: stars as f32,
+        }
+    }
+
+    /// Stores a map from a source, keeping our play counts and any status
+    /// staff have set on it.
+    pub fn save(mut self) -> io::Result<Self> {
+        if let Some(existing) = Beatmap::from_id(self.id) {
+            self.plays = existing.plays;
+            self.passes = existing.passes;
+            if existing.frozen {
+                self.status = existing.status;
+                self.frozen = true;
+            }
+        }
+        BEATMAPS.insert(self)
+    }
+
+    /// Overrides a map's status, keeping it through later refreshes.
+    pub fn set_status(id: i32, status: RankedStatus) -> io::Result<Option<Self>> {
+        BEATMAPS.update(id as i64, |m| {
+            m.status = status;
+            m.frozen = true;
+        })
+    }
+
+    /// Whether scores on this map count towards ranked score and pp.
+    pub fn awards_ranked_pp(&self) -> bool {
+        matches!(self.status, RankedStatus::Ranked | RankedStatus::Approved)
+    }
+
+    pub fn has_leaderboard(&self) -> bool {
+        matches!(
+            self.status,
+            RankedStatus::Ranked
+                | RankedStatus::Approved
+                | RankedStatus::Qualified
+                | RankedStatus::Loved
+        )
+    }
+
+    pub fn osu_file_path(&self) -> PathBuf {
+        data_path().join("osu").join(format!("{}.osu", self.id))
+    }
+
+    /// Parses the map's .osu file, if we have the version matching its md5.
+    pub fn osu_file(&self) -> Option<BeatmapFile> {
+        let data = fs::read(self.osu_file_path()).ok()?;
+
+        match BeatmapFile::parse(&data) {
+            Ok(file) if file.md5 == self.md5 => Some(file),
+            Ok(_) => None,
+            Err(e) => {
+                println!("[beatmap] failed to parse {}: {}", self.full_name(), e);
+                None
+            }
+        }
+    }
+
+    pub fn full_name(&self) -> String {
+        format!("{} - {} [{}]", self.artist, self.title, self.version)
+    }
+}
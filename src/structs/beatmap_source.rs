@@ -0,0 +1,799 @@
+use futures::future::{BoxFuture, FutureExt};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::config;
+use crate::storage::data_path;
+use crate::structs::beatmap::{Beatmap, RankedStatus};
+use crate::utils::osu::beatmap::BeatmapFile;
+
+/// Default mirror to look up maps we don't know; it serves the osu! api v1
+/// `get_beatmaps` endpoint under `/api`, .osu files under `/osu` and .osz
+/// files under `/d`.
+pub const DEFAULT_MIRROR: &str = "https://osu.direct";
+/// Default host of set thumbnails (under `/thumb`) and audio previews (under
+/// `/preview`), which mirrors don't serve.
+pub const DEFAULT_ASSETS: &str = "https://b.ppy.sh";
+
+/// How long looked up metadata is reused before asking the mirror again.
+const METADATA_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long to remember a lookup found nothing.
+const NOT_FOUND_TTL: Duration = Duration::from_secs(10 * 60);
+/// The shortest time between two requests to the mirror.
+const REQUEST_INTERVAL: Duration = Duration::from_millis(100);
+
+lazy_static::lazy_static! {
+    /// Where maps we don't have yet are looked up: `beatmaps.maps_dir` in the
+    /// config if set, otherwise `beatmaps.mirror`.
+    pub static ref BEATMAP_SOURCE: Option<Box<dyn BeatmapSource>> = source_from_config();
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MapQuery {
+    Md5(String),
+    Id(i32),
+    SetId(i32),
+}
+
+impl MapQuery {
+    /// A lookup by md5, `None` unless it's 32 hex digits. The md5 ends up in
+    /// cache paths and mirror urls, so anything else is turned away here.
+    pub fn md5(md5: &str) -> Option<Self> {
+        let valid = md5.len() == 32 && md5.bytes().all(|b| b.is_ascii_hexdigit());
+        valid.then(|| MapQuery::Md5(md5.to_ascii_lowercase()))
+    }
+
+    /// The query as the osu! api v1 takes it.
+    fn api_params(&self) -> String {
+        match self {
+            MapQuery::Md5(md5) => format!("h={}", md5),
+            MapQuery::Id(id) => format!("b={}", id),
+            MapQuery::SetId(set_id) => format!("s={}", set_id),
+        }
+    }
+}
+
+/// A map search as osu!direct makes them.
+#[derive(Clone, Debug, Default)]
+pub struct SearchQuery {
+    /// Words that all have to appear in the map's artist, title, creator or
+    /// difficulty name; empty matches everything.
+    pub query: String,
+    /// The client's mode (0-3), any mode if not given.
+    pub mode: Option<u8>,
+    /// Statuses to include, any status if empty.
+    pub statuses: Vec<RankedStatus>,
+    /// Number of sets to skip.
+    pub offset: usize,
+    /// Number of sets to return.
+    pub amount: usize,
+}
+
+impl SearchQuery {
+    pub fn matches(&self, map: &Beatmap) -> bool {
+        if self.mode.is_some_and(|mode| mode != map.mode) {
+            return false;
+        }
+        if !self.statuses.is_empty() && !self.statuses.contains(&map.status) {
+            return false;
+        }
+
+        let text = format!(
+            "{} {} {} {}",
+            map.artist, map.title, map.creator, map.version
+        )
+        .to_lowercase();
+        self.query
+            .to_lowercase()
+            .split_whitespace()
+            .all(|word| text.contains(word))
+    }
+
+    /// Keeps the maps of the sets in the requested page, in order.
+    pub fn paginate(&self, maps: Vec<Beatmap>) -> Vec<Beatmap> {
+        let mut sets = Vec::new();
+        for map in &maps {
+            if !sets.contains(&map.set_id) {
+                sets.push(map.set_id);
+            }
+        }
+        let page: Vec<i32> = sets
+            .into_iter()
+            .skip(self.offset)
+            .take(self.amount)
+            .collect();
+
+        maps.into_iter()
+            .filter(|m| page.contains(&m.set_id))
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub enum SourceError {
+    Http(String),
+    Io(io::Error),
+    InvalidResponse(String),
+}
+
+impl std::fmt::Display for SourceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SourceError::Http(e) => write!(f, "request failed: {}", e),
+            SourceError::Io(e) => write!(f, "{}", e),
+            SourceError::InvalidResponse(e) => write!(f, "invalid response: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SourceError {}
+
+impl From<io::Error> for SourceError {
+    fn from(e: io::Error) -> Self {
+        SourceError::Io(e)
+    }
+}
+
+impl From<reqwest::Error> for SourceError {
+    fn from(e: reqwest::Error) -> Self {
+        SourceError::Http(e.to_string())
+    }
+}
+
+/// Somewhere to get maps we don't have from. Lookups return every matching
+/// map, and files are `None` when the source doesn't have them.
+pub trait BeatmapSource: Send + Sync {
+    fn lookup<'a>(
+        &'a self,
+        query: &'a MapQuery,
+    ) -> BoxFuture<'a, Result<Vec<Beatmap>, SourceError>>;
+
+    fn osu_file(&self, map_id: i32) -> BoxFuture<'_, Result<Option<Vec<u8>>, SourceError>>;
+
+    fn osz_file(&self, set_id: i32) -> BoxFuture<'_, Result<Option<Vec<u8>>, SourceError>>;
+
+    /// Searches for maps, a page of `query.amount` sets at a time. Sources
+    /// that can't search find nothing.
+    fn search<'a>(
+        &'a self,
+        query: &'a SearchQuery,
+    ) -> BoxFuture<'a, Result<Vec<Beatmap>, SourceError>> {
+        async { Ok(Vec::new()) }.boxed()
+    }
+
+    /// Where clients can download a set's .osz directly, when the source is
+    /// publicly reachable.
+    fn osz_url(&self, set_id: i32) -> Option<String> {
+        None
+    }
+
+    /// A set's thumbnail or audio preview, `kind` being `thumb` or `preview`
+    /// and `name` the file as b.ppy.sh names it, e.g. `1l.jpg`.
+    fn asset<'a>(
+        &'a self,
+        kind: &'a str,
+        name: &'a str,
+    ) -> BoxFuture<'a, Result<Option<Vec<u8>>, SourceError>> {
+        async { Ok(None) }.boxed()
+    }
+}
+
+fn source_from_config() -> Option<Box<dyn BeatmapSource>> {
+    let config = config::get();
+    if let Some(dir) = &config.beatmaps.maps_dir {
+        return Some(Box::new(LocalSource::new(dir)));
+    }
+
+    let url = config.beatmaps.mirror.as_ref()?;
+    Some(Box::new(MirrorSource::new(
+        url,
+        config.beatmaps.assets.as_deref(),
+        data_path().join("mirror"),
+    )))
+}
+
+/// A map as the osu! api v1 returns it, everything as strings.
+#[derive(Deserialize)]
+struct ApiBeatmap {
+    beatmap_id: String,
+    beatmapset_id: String,
+    file_md5: String,
+    artist: String,
+    title: String,
+    version: String,
+    creator: String,
+    approved: String,
+    last_update: String,
+    total_length: String,
+    max_combo: Option<String>,
+    mode: String,
+    bpm: Option<String>,
+    diff_size: String,
+    diff_approach: String,
+    diff_overall: String,
+    diff_drain: String,
+    difficultyrating: String,
+}
+
+impl ApiBeatmap {
+    fn into_beatmap(self) -> Beatmap {
+        let int = |s: &str| s.parse::<f64>().unwrap_or(0.0) as i32;
+        let float = |s: &str| s.parse::<f32>().unwrap_or(0.0);
+
+        let status = RankedStatus::from_api(self.approved.parse().unwrap_or(0));
+
+        Beatmap {
+            id: int(&self.beatmap_id),
+            set_id: int(&self.beatmapset_id),
+            filename: format!(
+                "{} - {} ({}) [{}].osu",
+                self.artist, self.title, self.creator, self.version
+            ),
+            md5: self.file_md5,
+            artist: self.artist,
+            title: self.title,
+            version: self.version,
+            creator: self.creator,
+            last_update: self.last_update,
+            total_length: int(&self.total_length),
+            max_combo: self.max_combo.as_deref().map_or(0, int),
+            status,
+            frozen: false,
+            plays: 0,
+            passes: 0,
+            mode: int(&self.mode) as u8,
+            bpm: self.bpm.as_deref().map_or(0.0, float),
+            cs: float(&self.diff_size),
+            ar: float(&self.diff_approach),
+            od: float(&self.diff_overall),
+            hp: float(&self.diff_drain),
+            diff: float(&self.difficultyrating),
+        }
+    }
+}
+
+/// A set as cheesegull style mirror searches return them.
+#[derive(Deserialize)]
+struct MirrorSet {
+    #[serde(rename = "SetID")]
+    set_id: i32,
+    #[serde(rename = "Artist")]
+    artist: String,
+    #[serde(rename = "Title")]
+    title: String,
+    #[serde(rename = "Creator")]
+    creator: String,
+    #[serde(rename = "RankedStatus")]
+    ranked_status: i32,
+    #[serde(rename = "LastUpdate", default)]
+    last_update: String,
+    #[serde(rename = "ChildrenBeatmaps", default)]
+    children: Vec<MirrorMap>,
+}
+
+#[derive(Deserialize)]
+struct MirrorMap {
+    #[serde(rename = "BeatmapID")]
+    id: i32,
+    #[serde(rename = "FileMD5", default)]
+    md5: String,
+    #[serde(rename = "DiffName")]
+    version: String,
+    #[serde(rename = "Mode", default)]
+    mode: u8,
+    #[serde(rename = "TotalLength", default)]
+    total_length: i32,
+    #[serde(rename = "MaxCombo", default)]
+    max_combo: i32,
+    #[serde(rename = "BPM", default)]
+    bpm: f32,
+    #[serde(rename = "CS", default)]
+    cs: f32,
+    #[serde(rename = "AR", default)]
+    ar: f32,
+    #[serde(rename = "OD", default)]
+    od: f32,
+    #[serde(rename = "HP", default)]
+    hp: f32,
+    #[serde(rename = "DifficultyRating", default)]
+    diff: f32,
+}
+
+impl MirrorSet {
+    fn into_beatmaps(self) -> Vec<Beatmap> {
+        let status = RankedStatus::from_api(self.ranked_status);
+
+        self.children
+            .into_iter()
+            .map(|m| Beatmap {
+                id: m.id,
+                set_id: self.set_id,
+                md5: m.md5,
+                filename: format!(
+                    "{} - {} ({}) [{}].osu",
+                    self.artist, self.title, self.creator, m.version
+                ),
+                artist: self.artist.clone(),
+                title: self.title.clone(),
+                version: m.version,
+                creator: self.creator.clone(),
+                last_update: self.last_update.clone(),
+                total_length: m.total_length,
+                max_combo: m.max_combo,
+                status,
+                frozen: false,
+                plays: 0,
+                passes: 0,
+                mode: m.mode,
+                bpm: m.bpm,
+                cs: m.cs,
+                ar: m.ar,
+                od: m.od,
+                hp: m.hp,
+                diff: m.diff,
+            })
+            .collect()
+    }
+}
+
+/// An osu! api v1 compatible mirror. Responses and files are cached on disk,
+/// lookups that found nothing are remembered for a while, and requests are
+/// spaced out so we don't get rate limited ourselves.
+pub struct MirrorSource {
+    base_url: String,
+    /// Where thumbnails and previews are fetched from, if anywhere.
+    assets_url: Option<String>,
+    cache_dir: PathBuf,
+    client: reqwest::Client,
+    next_request: Mutex<Instant>,
+    not_found: Mutex<HashMap<String, Instant>>,
+}
+
+impl MirrorSource {
+    pub fn new(base_url: &str, assets_url: Option<&str>, cache_dir: PathBuf) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            assets_url: assets_url.map(|url| url.trim_end_matches('/').to_string()),
+            cache_dir,
+            client: reqwest::Client::new(),
+            next_request: Mutex::new(Instant::now()),
+            not_found: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits for our turn to send a request.
+    async fn throttle(&self) {
+        let wait = {
+            let mut next = self.next_request.lock().unwrap();
+            let now = Instant::now();
+            let at = (*next).max(now);
+            *next = at + REQUEST_INTERVAL;
+            at - now
+        };
+
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    fn recently_not_found(&self, key: &str) -> bool {
+        let mut not_found = self.not_found.lock().unwrap();
+        match not_found.get(key) {
+            Some(at) if at.elapsed() < NOT_FOUND_TTL => true,
+            Some(_) => {
+                not_found.remove(key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn mark_not_found(&self, key: &str) {
+        self.not_found
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), Instant::now());
+    }
+
+    /// Fetches a url, `None` on a 404.
+    async fn get(&self, url: &str) -> Result<Option<Vec<u8>>, SourceError> {
+        self.throttle().await;
+
+        let res = self.client.get(url).send().await?;
+        if res.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(res.error_for_status()?.bytes().await?.to_vec()))
+    }
+
+    /// Fetches a file, from the disk cache if we've downloaded it before.
+    async fn cached_file(
+        &self,
+        path: PathBuf,
+        url: String,
+    ) -> Result<Option<Vec<u8>>, SourceError> {
+        if let Ok(data) = fs::read(&path) {
+            return Ok(Some(data));
+        }
+        if self.recently_not_found(&url) {
+            return Ok(None);
+        }
+
+        match self.get(&url).await? {
+            Some(data) if !data.is_empty() => {
+                fs::create_dir_all(path.parent().unwrap())?;
+                fs::write(&path, &data)?;
+                Ok(Some(data))
+            }
+            _ => {
+                self.mark_not_found(&url);
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl BeatmapSource for MirrorSource {
+    fn lookup<'a>(
+        &'a self,
+        query: &'a MapQuery,
+    ) -> BoxFuture<'a, Result<Vec<Beatmap>, SourceError>> {
+        async move {
+            let params = query.api_params();
+            let path = self.cache_dir.join("api").join(format!("{}.json", params));
+
+            let fresh = fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| SystemTime::now().duration_since(t).ok())
+                .is_some_and(|age| age < METADATA_TTL);
+
+            let parse = |data: &[u8]| {
+                serde_json::from_slice::<Vec<ApiBeatmap>>(data)
+                    .map_err(|e| SourceError::InvalidResponse(e.to_string()))
+            };
+
+            if fresh {
+                let maps = parse(&fs::read(&path)?)?;
+                return Ok(maps.into_iter().map(ApiBeatmap::into_beatmap).collect());
+            }
+            if self.recently_not_found(&params) {
+                return Ok(Vec::new());
+            }
+
+            let url = format!("{}/api/get_beatmaps?{}", self.base_url, params);
+            let data = self.get(&url).await?.unwrap_or_else(|| b"[]".to_vec());
+            let maps = parse(&data)?;
+
+            // only found maps are cached on disk, misses are retried sooner
+            if maps.is_empty() {
+                self.mark_not_found(&params);
+            } else {
+                fs::create_dir_all(path.parent().unwrap())?;
+                fs::write(&path, &data)?;
+            }
+
+            Ok(maps.into_iter().map(ApiBeatmap::into_beatmap).collect())
+        }
+        .boxed()
+    }
+
+    fn osu_file(&self, map_id: i32) -> BoxFuture<'_, Result<Option<Vec<u8>>, SourceError>> {
+        let path = self.cache_dir.join("osu").join(format!("{}.osu", map_id));
+        let url = format!("{}/osu/{}", self.base_url, map_id);
+        self.cached_file(path, url).boxed()
+    }
+
+    fn osz_file(&self, set_id: i32) -> BoxFuture<'_, Result<Option<Vec<u8>>, SourceError>> {
+        let path = self.cache_dir.join("osz").join(format!("{}.osz", set_id));
+        let url = format!("{}/d/{}", self.base_url, set_id);
+        self.cached_file(path, url).boxed()
+    }
+
+    fn search<'a>(
+        &'a self,
+        query: &'a SearchQuery,
+    ) -> BoxFuture<'a, Result<Vec<Beatmap>, SourceError>> {
+        async move {
+            let mut params = vec![
+                ("query", query.query.clone()),
+                ("amount", query.amount.to_string()),
+                ("offset", query.offset.to_string()),
+            ];
+            if let Some(mode) = query.mode {
+                params.push(("mode", mode.to_string()));
+            }
+            // cheesegull takes the status repeated for each one wanted, e.g.
+            // ranked and approved for the client's "Ranked"
+            let mut statuses: Vec<i32> = query.statuses.iter().map(|s| s.as_api()).collect();
+            statuses.sort_unstable();
+            statuses.dedup();
+            for status in statuses {
+                params.push(("status", status.to_string()));
+            }
+
+            self.throttle().await;
+            let sets: Vec<MirrorSet> = self
+                .client
+                .get(format!("{}/api/search", self.base_url))
+                .query(&params)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            Ok(sets
+                .into_iter()
+                .flat_map(MirrorSet::into_beatmaps)
+                .filter(|m| query.statuses.is_empty() || query.statuses.contains(&m.status))
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn osz_url(&self, set_id: i32) -> Option<String> {
+        Some(format!("{}/d/{}", self.base_url, set_id))
+    }
+
+    fn asset<'a>(
+        &'a self,
+        kind: &'a str,
+        name: &'a str,
+    ) -> BoxFuture<'a, Result<Option<Vec<u8>>, SourceError>> {
+        async move {
+            let url = match &self.assets_url {
+                Some(assets_url) => format!("{}/{}/{}", assets_url, kind, name),
+                None => return Ok(None),
+            };
+            if self.recently_not_found(&url) {
+                return Ok(None);
+            }
+
+            match self.get(&url).await? {
+                Some(data) if !data.is_empty() => Ok(Some(data)),
+                _ => {
+                    self.mark_not_found(&url);
+                    Ok(None)
+                }
+            }
+        }
+        .boxed()
+    }
+}
+
+/// Serves maps from a directory of .osu files (and `<set id>.osz` files), for
+/// tests and servers running without network access. Maps without an id of
+/// their own are skipped.
+pub struct LocalSource {
+    dir: PathBuf,
+    index: Mutex<Arc<LocalIndex>>,
+}
+
+/// The maps in a local source's directory, parsed once and kept until the
+/// directory changes.
+#[derive(Default)]
+struct LocalIndex {
+    /// When the directory was last modified as of building the index.
+    modified: Option<SystemTime>,
+    /// Maps along with the path of their .osu file, in directory order.
+    maps: Vec<(Beatmap, PathBuf)>,
+    by_md5: HashMap<String, usize>,
+    by_id: HashMap<i32, usize>,
+    by_set_id: HashMap<i32, Vec<usize>>,
+}
+
+impl LocalIndex {
+    fn build(dir: &Path, modified: SystemTime) -> io::Result<Self> {
+        let mut index = LocalIndex {
+            modified: Some(modified),
+            ..Default::default()
+        };
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "osu") {
+                continue;
+            }
+
+            let file = match BeatmapFile::parse(&fs::read(&path)?) {
+                Ok(file) if file.metadata.beatmap_id > 0 => file,
+                Ok(_) => continue,
+                Err(e) => {
+                    println!("[beatmap] failed to parse {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+
+            let map = Beatmap::from_file(&file, &file_name(&path), &last_update(&path)?);
+            let i = index.maps.len();
+            index.by_md5.insert(map.md5.clone(), i);
+            index.by_id.insert(map.id, i);
+            index.by_set_id.entry(map.set_id).or_default().push(i);
+            index.maps.push((map, path));
+        }
+
+        Ok(index)
+    }
+
+    fn lookup(&self, query: &MapQuery) -> Vec<&(Beatmap, PathBuf)> {
+        let found = match query {
+            MapQuery::Md5(md5) => self.by_md5.get(md5).map(std::slice::from_ref),
+            MapQuery::Id(id) => self.by_id.get(id).map(std::slice::from_ref),
+            MapQuery::SetId(set_id) => self.by_set_id.get(set_id).map(Vec::as_slice),
+        };
+        found
+            .unwrap_or_default()
+            .iter()
+            .map(|&i| &self.maps[i])
+            .collect()
+    }
+}
+
+impl LocalSource {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            index: Mutex::new(Arc::default()),
+        }
+    }
+
+    /// The index of the directory, rebuilt first if anything was added to
+    /// or removed from it since it was last built.
+    fn index(&self) -> io::Result<Arc<LocalIndex>> {
+        let modified = fs::metadata(&self.dir)?.modified()?;
+
+        let mut index = self.index.lock().unwrap();
+        if index.modified != Some(modified) {
+            *index = Arc::new(LocalIndex::build(&self.dir, modified)?);
+        }
+        Ok(index.clone())
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap().to_string_lossy().to_string()
+}
+
+fn last_update(path: &Path) -> io::Result<String> {
+    let modified = fs::metadata(path)?.modified()?;
+    Ok(chrono::DateTime::<chrono::Utc>::from(modified)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string())
+}
+
+impl BeatmapSource for LocalSource {
+    fn lookup<'a>(
+        &'a self,
+        query: &'a MapQuery,
+    ) -> BoxFuture<'a, Result<Vec<Beatmap>, SourceError>> {
+        async move {
+            Ok(self
+                .index()?
+                .lookup(query)
+                .into_iter()
+                .map(|(map, _)| map.clone())
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn osu_file(&self, map_id: i32) -> BoxFuture<'_, Result<Option<Vec<u8>>, SourceError>> {
+        async move {
+            let index = self.index()?;
+            match index.lookup(&MapQuery::Id(map_id)).first() {
+                Some((_, path)) => Ok(fs::read(path).ok()),
+                None => Ok(None),
+            }
+        }
+        .boxed()
+    }
+
+    fn osz_file(&self, set_id: i32) -> BoxFuture<'_, Result<Option<Vec<u8>>, SourceError>> {
+        async move { Ok(fs::read(self.dir.join(format!("{}.osz", set_id))).ok()) }.boxed()
+    }
+
+    fn search<'a>(
+        &'a self,
+        query: &'a SearchQuery,
+    ) -> BoxFuture<'a, Result<Vec<Beatmap>, SourceError>> {
+        async move {
+            let maps = self
+                .index()?
+                .maps
+                .iter()
+                .map(|(map, _)| map)
+                .filter(|m| query.matches(m))
+                .cloned()
+                .collect();
+            Ok(query.paginate(maps))
+        }
+        .boxed()
+    }
+}
+
+#[test]
+fn test_api_beatmap() {
+    let maps: Vec<ApiBeatmap> = serde_json::from_str(
+        r#"[{"beatmap_id":"75","beatmapset_id":"1","file_md5":"a5b99395a42bd55bc5eb1d2411cbdf8b",
+            "artist":"Kenji Ninuma","title":"DISCO PRINCE","version":"Normal","creator":"peppy",
+            "approved":"1","last_update":"2007-10-06 17:46:31","total_length":"142",
+            "max_combo":"314","mode":"0","bpm":"119.999","diff_size":"4","diff_approach":"6",
+            "diff_overall":"6","diff_drain":"6","difficultyrating":"2.4069502353668213"}]"#,
+    )
+    .unwrap();
+    let map = maps.into_iter().next().unwrap().into_beatmap();
+
+    assert_eq!(map.id, 75);
+    assert_eq!(map.set_id, 1);
+    assert_eq!(map.status, RankedStatus::Ranked);
+    assert_eq!(map.max_combo, 314);
+    assert_eq!(
+        map.filename,
+        "Kenji Ninuma - DISCO PRINCE (peppy) [Normal].osu"
+    );
+    assert_eq!("love".parse::<RankedStatus>(), Ok(RankedStatus::Loved));
+}
+
+#[test]
+fn test_local_source() {
+    use futures::executor::block_on;
+
+    let source = LocalSource::new("fixtures/maps");
+
+    let maps = block_on(source.lookup(&MapQuery::Id(1001))).unwrap();
+    assert_eq!(maps.len(), 1);
+    assert_eq!(maps[0].title, "Stream");
+    assert_eq!(maps[0].filename, "stream.osu");
+
+    let by_md5 = block_on(source.lookup(&MapQuery::md5(&maps[0].md5).unwrap())).unwrap();
+    assert_eq!(by_md5[0].id, 1001);
+    assert!(MapQuery::md5("../../../../etc/passwd").is_none());
+    assert!(MapQuery::md5("0123456789abcdef0123456789a&s=1").is_none());
+    assert_eq!(
+        block_on(source.lookup(&MapQuery::SetId(105))).unwrap()[0].id,
+        1005
+    );
+    assert!(block_on(source.lookup(&MapQuery::Id(1)))
+        .unwrap()
+        .is_empty());
+
+    let file = block_on(source.osu_file(1003)).unwrap().unwrap();
+    assert_eq!(
+        format!("{:x}", md5::compute(&file)),
+        block_on(source.lookup(&MapQuery::Id(1003))).unwrap()[0].md5
+    );
+    assert!(block_on(source.osz_file(101)).unwrap().is_none());
+
+    let mut query = SearchQuery {
+        query: "STREAM insane".to_string(),
+        amount: 100,
+        ..Default::default()
+    };
+    let found = block_on(source.search(&query)).unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, 1001);
+
+    query.query = String::new();
+    query.mode = Some(3);
+    assert_eq!(block_on(source.search(&query)).unwrap()[0].id, 1005);
+
+    query.mode = None;
+    query.statuses = vec![RankedStatus::Ranked];
+    assert!(block_on(source.search(&query)).unwrap().is_empty());
+
+    query.statuses.clear();
+    query.offset = 4;
+    assert_eq!(block_on(source.search(&query)).unwrap().len(), 1);
+
+    // the directory is only parsed again once it changes
+    assert!(Arc::ptr_eq(
+        &source.index().unwrap(),
+        &source.index().unwrap()
+    ));
+}
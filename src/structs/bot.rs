@@ -0,0 +1,196 @@
+use crate::config;
+use crate::constants::gamemodes::GameMode;
+use crate::constants::mods::Mods;
+use crate::constants::privileges::Privileges;
+use crate::storage::USERS;
+use crate::structs::beatmap::Beatmap;
+use crate::structs::channel::CHANNELS;
+use crate::structs::player::{Player, PLAYERS};
+use crate::structs::user::{make_safe_name, User};
+use crate::utils::osu::packets::ChoSendMessage;
+use crate::utils::osu::performance::{self, ScoreState};
+
+lazy_static::lazy_static! {
+    /// The server's own account, which command output and announcements are
+    /// sent from. It's created the first time the server runs.
+    pub static ref BOT: User = load_or_create();
+}
+
+/// Shown as staff in the user list, like the bot on the official servers.
+const PRIVILEGES: Privileges = Privileges::from_bits_truncate(
+    Privileges::UNRESTRICTED.bits() | Privileges::SUPPORTER.bits() | Privileges::DEVELOPER.bits(),
+);
+
+/// Where new #1 scores and staff announcements are posted.
+const ANNOUNCE_CHANNEL: &str = "#announce";
+
+/// The accuracies `/np` estimates pp at.
+const NP_ACCURACIES: [f64; 4] = [0.95, 0.98, 0.99, 1.0];
+
+fn load_or_create() -> User {
+    let name = config::get().bot_name.clone();
+    if let Some(user) = User::from_name(&name) {
+        return user;
+    }
+
+    // without a password hash nobody can log in as the bot
+    let user = User {
+        id: 0,
+        safe_name: make_safe_name(&name),
+        name,
+        pw_bcrypt: String::new(),
+        // "satellite provider", which the client shows without a flag
+        country: "A2".to_string(),
+        creation_time: chrono::Utc::now().timestamp(),
+        friends: Vec::new(),
+        silence_end: 0,
+        silence_reason: String::new(),
+        privileges: PRIVILEGES,
+        banned: false,
+    };
+    USERS
+        .insert(user)
+        .expect("failed to create the bot's account")
+}
+
+/// Puts the bot online for good, in every channel.
+pub fn login() {
+    let player = Player::bot(&BOT, PRIVILEGES);
+    PLAYERS.add(player.clone());
+
+    for channel in CHANNELS.public() {
+        channel.join(&player);
+    }
+}
+
+pub fn id() -> i32 {
+    BOT.id
+}
+
+pub fn name() -> &'static str {
+    &BOT.name
+}
+
+/// Whether a private message to `name` is meant for the bot.
+pub fn is_bot(name: &str) -> bool {
+    make_safe_name(name) == BOT.safe_name
+}
+
+/// A message from the bot, to a channel or to a player by name.
+pub fn message(recipient: &str, text: &str) -> Vec<u8> {
+    ChoSendMessage::new(name(), text, recipient, id()).write()
+}
+
+/// Posts a message in the announcements channel.
+pub fn announce(text: &str) {
+    match CHANNELS.get(ANNOUNCE_CHANNEL) {
+        Some(channel) => channel.send(name(), id(), text),
+        None => println!("[bot] no {} channel to announce in", ANNOUNCE_CHANNEL),
+    }
+}
+
+/// What a player's `/np` says they're playing.
+#[derive(Debug, PartialEq)]
+pub struct NowPlaying {
+    pub map_id: i32,
+    pub mods: Mods,
+    /// Set when playing a convert in another mode.
+    pub mode: Option<u8>,
+}
+
+impl NowPlaying {
+    /// Reads an `/np` action, e.g. `\x01ACTION is listening to
+    /// [https://osu.ppy.sh/b/123 Artist - Title [Diff]] +Hidden <Taiko>\x01`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let action = text
+            .strip_prefix("\x01ACTION is ")?
+            .trim_end_matches('\x01');
+        let rest = ["playing ", "listening to ", "watching ", "editing "]
+            .iter()
+            .find_map(|verb| action.strip_prefix(verb))?;
+
+        let link = rest.strip_prefix('[')?;
+        let (url, _) = link.split_once(' ')?;
+        // /b/123, /beatmaps/123 or /beatmapsets/1#osu/123
+        let map_id = url.rsplit('/').next()?.parse().ok()?;
+
+        let mut mods = Mods::empty();
+        let mut mode = None;
+        let extras = &rest[rest.rfind(']')? + 1..];
+        for word in extras.split_whitespace() {
+            match word {
+                "<Taiko>" => mode = Some(1),
+                "<CatchTheBeat>" => mode = Some(2),
+                "<osu!mania>" => mode = Some(3),
+                word => {
+                    let name = word.trim_matches(|c| matches!(c, '+' | '-' | '~' | '|'));
+                    if let Some(m) = Mods::from_name(name) {
+                        mods |= m;
+                    }
+                }
+            }
+        }
+
+        Some(Self { map_id, mods, mode })
+    }
+}
+
+/// Answers an `/np` with the map's pp at a few accuracies, or `None` if the
+/// message isn't one.
+pub async fn now_playing(text: &str) -> Option<String> {
+    let np = NowPlaying::parse(text)?;
+
+    let map = Beatmap::fetch_by_id(np.map_id).await;
+    let (map, file) = match map.and_then(|m| m.osu_file().map(|f| (m, f))) {
+        Some(found) => found,
+        None => return Some("I couldn't find that map.".to_string()),
+    };
+
+    let mode = GameMode::from_params(np.mode.unwrap_or(map.mode), np.mods);
+    let mut estimates = Vec::new();
+    let mut stars = 0.0;
+    for acc in NP_ACCURACIES {
+        let state = ScoreState::for_accuracy(&file, mode, np.mods, acc);
+        let result = performance::calculate(&file, mode, np.mods, &state);
+        stars = result.stars;
+        estimates.push(format!("{:.0}%: {:.0}pp", acc * 100.0, result.pp));
+    }
+
+    let mut name = map.full_name();
+    if !np.mods.is_empty() {
+        name.push_str(&format!(" +{}", np.mods.acronyms()));
+    }
+    Some(format!(
+        "{} ({:.2}*) | {}",
+        name,
+        stars,
+        estimates.join(" | ")
+    ))
+}
+
+#[test]
+fn test_now_playing() {
+    let np = NowPlaying::parse(
+        "\x01ACTION is listening to [https://osu.ppy.sh/b/123 Artist - Title [Insane]] +Hidden +DoubleTime <Taiko>\x01",
+    );
+    assert_eq!(
+        np,
+        Some(NowPlaying {
+            map_id: 123,
+            mods: Mods::HIDDEN | Mods::DOUBLETIME,
+            mode: Some(1),
+        })
+    );
+
+    let np = NowPlaying::parse(
+        "\x01ACTION is playing [https://osu.ppy.sh/beatmapsets/1#osu/456 Artist - Title [Hard]] ~Relax~\x01",
+    )
+    .unwrap();
+    assert_eq!((np.map_id, np.mods, np.mode), (456, Mods::RELAX, None));
+
+    assert_eq!(NowPlaying::parse("\x01ACTION waves\x01"), None);
+    assert_eq!(
+        NowPlaying::parse("is playing [https://osu.ppy.sh/b/1 a]"),
+        None
+    );
+}
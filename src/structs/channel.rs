@@ -0,0 +1,206 @@
+use std::sync::{Arc, RwLock};
+
+use crate::config;
+use crate::structs::player::{Player, PLAYERS};
+use crate::utils::osu::packets::{
+    ChoChannelInfo, ChoChannelJoinSuccess, ChoChannelKick, ChoSendMessage,
+};
+
+lazy_static::lazy_static! {
+    pub static ref CHANNELS: ChannelList = ChannelList::load();
+}
+
+pub struct Channel {
+    name: String,
+    /// What the client calls it; the same as `name` except for channels that
+    /// exist once per match, which all show up as e.g. `#multiplayer`.
+    client_name: String,
+    topic: String,
+    /// Joined by everyone when they log in.
+    auto_join: bool,
+    members: RwLock<Vec<Player>>,
+}
+
+impl Channel {
+    pub fn new(name: &str, topic: &str, auto_join: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            client_name: name.to_string(),
+            topic: topic.to_string(),
+            auto_join,
+            members: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// A channel belonging to something like a match, which isn't listed and
+    /// only lets in who it's for.
+    pub fn instance(name: &str, client_name: &str, topic: &str) -> Self {
+        Self {
+            client_name: client_name.to_string(),
+            ..Self::new(name, topic, false)
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn client_name(&self) -> &str {
+        &self.client_name
+    }
+
+    pub fn is_instance(&self) -> bool {
+        self.name != self.client_name
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    pub fn auto_join(&self) -> bool {
+        self.auto_join
+    }
+
+    pub fn members(&self) -> Vec<Player> {
+        self.members.read().unwrap().clone()
+    }
+
+    pub fn has(&self, player: &Player) -> bool {
+        self.members
+            .read()
+            .unwrap()
+            .iter()
+            .any(|p| p.token() == player.token())
+    }
+
+    pub fn info_packet(&self) -> Vec<u8> {
+        let count = self.members.read().unwrap().len();
+        ChoChannelInfo::new(self.client_name(), self.topic(), count as u16).write()
+    }
+
+    /// Adds the player, telling everyone about the new member count.
+    pub fn join(&self, player: &Player) {
+        if self.has(player) {
+            return;
+        }
+
+        self.members.write().unwrap().push(player.clone());
+        player.enqueue(&ChoChannelJoinSuccess::new(self.client_name()).write());
+        self.update_info();
+    }
+
+    pub fn part(&self, player: &Player) {
+        if !self.has(player) {
+            return;
+        }
+
+        self.members
+            .write()
+            .unwrap()
+            .retain(|p| p.token() != player.token());
+        self.update_info();
+    }
+
+    /// Tells everyone who can see the channel its member count.
+    fn update_info(&self) {
+        if self.is_instance() {
+            for member in self.members() {
+                member.enqueue(&self.info_packet());
+            }
+        } else {
+            PLAYERS.enqueue_all(&self.info_packet());
+        }
+    }
+
+    /// Removes the player and closes the channel in their client.
+    pub fn kick(&self, player: &Player) {
+        self.part(player);
+        player.enqueue(&ChoChannelKick::new(self.client_name()).write());
+    }
+
+    /// Delivers a message to every member, except the one who sent it since
+    /// their client already shows it.
+    pub fn send(&self, sender_name: &str, sender_id: i32, text: &str) {
+        let packet = ChoSendMessage::new(sender_name, text, self.client_name(), sender_id).write();
+
+        for member in self.members.read().unwrap().iter() {
+            if member.id() != sender_id {
+                member.enqueue(&packet);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ChannelList {
+    channels: RwLock<Vec<Arc<Channel>>>,
+}
+
+impl ChannelList {
+    /// The channels in the config, which everyone joins on login.
+    pub fn load() -> Self {
+        let list = ChannelList::default();
+        for name in &config::get().default_channels {
+            list.add(Channel::new(name, "", true));
+        }
+        list
+    }
+
+    pub fn add(&self, channel: Channel) -> Arc<Channel> {
+        let channel = Arc::new(channel);
+        self.channels.write().unwrap().push(channel.clone());
+        channel
+    }
+
+    /// Takes a channel away, closing it for everyone in it.
+    pub fn remove(&self, name: &str) {
+        let channel = match self.get(name) {
+            Some(channel) => channel,
+            None => return,
+        };
+
+        self.channels.write().unwrap().retain(|c| c.name() != name);
+        let members = std::mem::take(&mut *channel.members.write().unwrap());
+        for member in members {
+            member.enqueue(&ChoChannelKick::new(channel.client_name()).write());
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<Channel>> {
+        self.channels
+            .read()
+            .unwrap()
+            .iter()
+            .find(|c| c.name() == name)
+            .cloned()
+    }
+
+    /// A channel by the name a player's client knows it by, which for
+    /// instance channels means one they're in.
+    pub fn get_for(&self, player: &Player, name: &str) -> Option<Arc<Channel>> {
+        self.get(name).filter(|c| !c.is_instance()).or_else(|| {
+            self.all()
+                .into_iter()
+                .find(|c| c.is_instance() && c.client_name() == name && c.has(player))
+        })
+    }
+
+    /// The channels everyone can see and join.
+    pub fn public(&self) -> Vec<Arc<Channel>> {
+        self.all()
+            .into_iter()
+            .filter(|c| !c.is_instance())
+            .collect()
+    }
+
+    pub fn all(&self) -> Vec<Arc<Channel>> {
+        self.channels.read().unwrap().clone()
+    }
+
+    /// Takes a player out of every channel, e.g. when they log out.
+    pub fn part_all(&self, player: &Player) {
+        for channel in self.all() {
+            channel.part(player);
+        }
+    }
+}
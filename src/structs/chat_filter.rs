@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::{self, ChatConfig};
+use crate::storage::{Row, CHAT_FILTER_LOG, USERS};
+use crate::structs::bot;
+use crate::structs::moderation;
+use crate::structs::player::Player;
+
+lazy_static::lazy_static! {
+    static ref RECENT: Mutex<Recent> = Mutex::new(Recent::default());
+}
+
+/// Why a message was changed or dropped.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterKind {
+    ControlCharacters,
+    Truncated,
+    WordFilter,
+    /// The player sent too many messages and was silenced.
+    Flooding,
+    /// The channel took too many messages.
+    ChannelRateLimit,
+    Repeated,
+}
+
+/// A message the filters changed or dropped, kept for moderators.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FilterEvent {
+    pub id: i64,
+    pub time: i64,
+    pub user_id: i32,
+    /// The channel, or the player the message was for.
+    pub recipient: String,
+    pub kind: FilterKind,
+    /// The message as it was sent.
+    pub text: String,
+}
+
+impl Row for FilterEvent {
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = id;
+    }
+}
+
+#[derive(Default)]
+struct Recent {
+    /// When each player sent their latest messages.
+    users: HashMap<i32, VecDeque<Instant>>,
+    /// When each channel took its latest messages.
+    channels: HashMap<String, VecDeque<Instant>>,
+    /// Each player's last message and how many times in a row they sent it.
+    last: HashMap<i32, (String, usize)>,
+}
+
+/// Counts a message within the window, returning whether it's over `limit`.
+fn over_limit(times: &mut VecDeque<Instant>, window: Duration, limit: usize) -> bool {
+    let now = Instant::now();
+    while times
+        .front()
+        .is_some_and(|t| now.duration_since(*t) > window)
+    {
+        times.pop_front();
+    }
+    times.push_back(now);
+    times.len() > limit
+}
+
+/// Runs a message through the filters before it's delivered: the rate limits
+/// and repeat check, then the cleanup. `None` if it shouldn't be delivered.
+pub fn filter(player: &Player, recipient: &str, text: &str) -> Option<String> {
+    let config = config::get();
+    let chat = &config.chat;
+    let window = Duration::from_secs(chat.rate_window);
+
+    let dropped = {
+        let mut recent = RECENT.lock().unwrap();
+        let user_times = recent.users.entry(player.id()).or_default();
+        if over_limit(user_times, window, chat.user_rate_limit) {
+            user_times.clear();
+            Some(FilterKind::Flooding)
+        } else if recipient.starts_with('#')
+            && over_limit(
+                recent.channels.entry(recipient.to_string()).or_default(),
+                window,
+                chat.channel_rate_limit,
+            )
+        {
+            Some(FilterKind::ChannelRateLimit)
+        } else {
+            let last = recent.last.entry(player.id()).or_default();
+            match last.0 == text {
+                true => last.1 += 1,
+                false => *last = (text.to_string(), 1),
+            }
+            Some(FilterKind::Repeated).filter(|_| last.1 > chat.max_repeats)
+        }
+    };
+    if let Some(kind) = dropped {
+        record(player, recipient, kind, text);
+        if kind == FilterKind::Flooding {
+            silence_for_flooding(player, chat.flood_silence);
+        }
+        return None;
+    }
+
+    let (clean, kinds) = clean(text, chat);
+    for kind in kinds {
+        record(player, recipient, kind, text);
+    }
+    Some(clean).filter(|text| !text.trim().is_empty())
+}
+
+/// Strips control characters, cuts the message to length and replaces
+/// filtered words, returning what was done to it.
+pub fn clean(text: &str, chat: &ChatConfig) -> (String, Vec<FilterKind>) {
+    let mut kinds = Vec::new();
+
+    // \x01 marks /me actions like /np
+    let mut clean: String = text
+        .chars()
+        .filter(|c| !c.is_control() || *c == '\x01')
+        .collect();
+    if clean.len() != text.len() {
+        kinds.push(FilterKind::ControlCharacters);
+    }
+
+    if let Some((cut, _)) = clean.char_indices().nth(chat.max_length) {
+        clean.truncate(cut);
+        kinds.push(FilterKind::Truncated);
+    }
+
+    let mut replaced = false;
+    for (word, replacement) in &chat.word_filter {
+        if let Some(new) = replace_word(&clean, word, replacement) {
+            clean = new;
+            replaced = true;
+        }
+    }
+    if replaced {
+        kinds.push(FilterKind::WordFilter);
+    }
+
+    (clean, kinds)
+}
+
+/// Replaces `word` where it stands on its own, ignoring case. `None` if it
+/// isn't there.
+fn replace_word(text: &str, word: &str, replacement: &str) -> Option<String> {
+    let is_word = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+    let mut out = String::with_capacity(text.len());
+    let mut found = false;
+
+    let mut i = 0;
+    while let Some(c) = text[i..].chars().next() {
+        let end = i + word.len();
+        let matched = text
+            .get(i..end)
+            .is_some_and(|m| m.eq_ignore_ascii_case(word));
+        if matched
+            && !is_word(text[..i].chars().next_back())
+            && !is_word(text[end..].chars().next())
+        {
+            out.push_str(replacement);
+            i = end;
+            found = true;
+        } else {
+            out.push(c);
+            i += c.len_utf8();
+        }
+    }
+
+    Some(out).filter(|_| found)
+}
+
+fn record(player: &Player, recipient: &str, kind: FilterKind, text: &str) {
+    let event = FilterEvent {
+        id: 0,
+        time: chrono::Utc::now().timestamp(),
+        user_id: player.id(),
+        recipient: recipient.to_string(),
+        kind,
+        text: text.to_string(),
+    };
+    if let Err(e) = CHAT_FILTER_LOG.insert(event) {
+        println!(
+            "[chat] failed to log {:?} from {}: {}",
+            kind,
+            player.name(),
+            e
+        );
+    }
+}
+
+fn silence_for_flooding(player: &Player, seconds: u64) {
+    let user = match USERS.get(player.id() as i64) {
+        Some(user) => user,
+        None => return,
+    };
+    let duration = Duration::from_secs(seconds);
+    if let Err(e) = moderation::silence(bot::id(), &user, duration, "flooding the chat") {
+        println!("[chat] failed to silence {}: {}", player.name(), e);
+    }
+}
+
+#[test]
+fn test_clean() {
+    let mut chat = ChatConfig {
+        max_length: 12,
+        ..ChatConfig::default()
+    };
+    chat.word_filter
+        .insert("heck".to_string(), "h*ck".to_string());
+
+    let (text, kinds) = clean("Heck, hecking\x07 heck", &chat);
+    assert_eq!(text, "h*ck, heckin");
+    assert_eq!(
+        kinds,
+        vec![
+            FilterKind::ControlCharacters,
+            FilterKind::Truncated,
+            FilterKind::WordFilter
+        ]
+    );
+
+    let (text, kinds) = clean(
+        "\x01ACTION is listening to heck\x01",
+        &ChatConfig::default(),
+    );
+    assert_eq!(text, "\x01ACTION is listening to heck\x01");
+    assert!(kinds.is_empty());
+}
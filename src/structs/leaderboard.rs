@@ -0,0 +1,86 @@
+use crate::constants::gamemodes::GameMode;
+use crate::constants::mods::Mods;
+use crate::constants::privileges::Privileged;
+use crate::storage::{SCORES, USERS};
+use crate::structs::score::{Score, SubmissionStatus};
+use crate::structs::user::User;
+
+pub const LEADERBOARD_SIZE: usize = 50;
+
+/// The leaderboard tabs of the in-game song select.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LeaderboardType {
+    Local = 0,
+    Top = 1,
+    Mods = 2,
+    Friends = 3,
+    Country = 4,
+}
+
+impl LeaderboardType {
+    pub fn from_i32(value: i32) -> Self {
+        match value {
+            0 => LeaderboardType::Local,
+            2 => LeaderboardType::Mods,
+            3 => LeaderboardType::Friends,
+            4 => LeaderboardType::Country,
+            _ => LeaderboardType::Top,
+        }
+    }
+}
+
+pub struct Leaderboard {
+    pub scores: Vec<(Score, User)>,
+    /// The requesting player's best score and its position, if they have one.
+    pub personal_best: Option<(Score, usize)>,
+}
+
+impl Leaderboard {
+    /// Builds the leaderboard of best scores on a map as `player` would see
+    /// it for the given tab.
+    pub fn fetch(
+        map_md5: &str,
+        mode: GameMode,
+        kind: LeaderboardType,
+        mods: Mods,
+        player: &User,
+    ) -> Self {
+        let matches_tab = |score: &Score, owner: &User| match kind {
+            LeaderboardType::Mods => score.mods == mods.bits(),
+            LeaderboardType::Friends => owner.id == player.id || player.friends.contains(&owner.id),
+            LeaderboardType::Country => owner.country == player.country,
+            LeaderboardType::Local | LeaderboardType::Top => true,
+        };
+
+        let mut scores: Vec<(Score, User)> = SCORES
+            .find(|s| s.map_md5 == map_md5 && s.mode == mode && s.status == SubmissionStatus::Best)
+            .into_iter()
+            .filter_map(|s| USERS.get(s.user_id as i64).map(|u| (s, u)))
+            // restricted players only see their own scores
+            .filter(|(_, u)| !u.is_restricted() || u.id == player.id)
+            .filter(|(s, u)| matches_tab(s, u))
+            .collect();
+
+        if mode.sorts_by_pp() {
+            scores.sort_by(|(a, _), (b, _)| {
+                b.pp.total_cmp(&a.pp).then(a.play_time.cmp(&b.play_time))
+            });
+        } else {
+            scores.sort_by(|(a, _), (b, _)| {
+                b.score.cmp(&a.score).then(a.play_time.cmp(&b.play_time))
+            });
+        }
+
+        let personal_best = scores
+            .iter()
+            .position(|(s, _)| s.user_id == player.id)
+            .map(|i| (scores[i].0.clone(), i + 1));
+
+        scores.truncate(LEADERBOARD_SIZE);
+
+        Self {
+            scores,
+            personal_best,
+        }
+    }
+}
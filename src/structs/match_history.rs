@@ -0,0 +1,337 @@
+use serde::{Deserialize, Serialize};
+
+use crate::constants::gamemodes::GameMode;
+use crate::constants::mods::Mods;
+use crate::constants::multiplayer::{Team, TeamType, WinCondition};
+use crate::storage::{Row, MATCH_EVENTS, MATCH_HISTORY};
+use crate::utils::osu::packet_reader::ScoreFrame;
+use crate::utils::osu::performance::ScoreState;
+
+/// A multiplayer match as it's archived, from creation until it's closed.
+/// Its id is its own, as live match ids are reused.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MatchRecord {
+    pub id: i64,
+    pub name: String,
+    pub start_time: i64,
+    /// Unset while the match is still open.
+    pub end_time: Option<i64>,
+}
+
+impl Row for MatchRecord {
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = id;
+    }
+}
+
+/// Something that happened in a match, in the order it happened.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MatchEvent {
+    pub id: i64,
+    pub match_id: i64,
+    pub time: i64,
+    /// Who the event is about, if anyone.
+    pub user_id: Option<i32>,
+    pub detail: EventDetail,
+}
+
+impl Row for MatchEvent {
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = id;
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum EventDetail {
+    MatchCreated,
+    MatchDisbanded,
+    PlayerJoined,
+    PlayerLeft,
+    PlayerKicked,
+    HostChanged,
+    BeatmapChanged {
+        map_id: i32,
+        map_md5: String,
+        map_name: String,
+    },
+    Game(Game),
+}
+
+/// A game played in a match; its scores are filled in when it ends.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Game {
+    pub map_id: i32,
+    pub map_md5: String,
+    pub map_name: String,
+    pub mode: u8,
+    /// The mods everyone played with; with freemods each score has its own.
+    pub mods: u32,
+    pub freemods: bool,
+    pub win_condition: WinCondition,
+    pub team_type: TeamType,
+    pub start_time: i64,
+    pub end_time: Option<i64>,
+    pub aborted: bool,
+    pub scores: Vec<GameScore>,
+}
+
+/// A player's result in a game, from the last score frame they sent.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameScore {
+    pub slot: u8,
+    pub team: Team,
+    pub user_id: i32,
+    pub mods: u32,
+    pub score: i64,
+    pub acc: f32,
+    pub max_combo: i32,
+    pub n300: i32,
+    pub n100: i32,
+    pub n50: i32,
+    pub nmiss: i32,
+    pub ngeki: i32,
+    pub nkatu: i32,
+    pub perfect: bool,
+    pub passed: bool,
+}
+
+impl GameScore {
+    pub fn new(
+        slot: u8,
+        team: Team,
+        user_id: i32,
+        mods: Mods,
+        mode: u8,
+        frame: &ScoreFrame,
+        passed: bool,
+    ) -> Self {
+        let state = ScoreState {
+            n300: frame.n300 as u32,
+            n100: frame.n100 as u32,
+            n50: frame.n50 as u32,
+            ngeki: frame.ngeki as u32,
+            nkatu: frame.nkatu as u32,
+            nmiss: frame.nmiss as u32,
+            ..Default::default()
+        };
+
+        Self {
+            slot,
+            team,
+            user_id,
+            mods: mods.bits(),
+            score: frame.total_score as i64,
+            acc: state.accuracy(GameMode::from_params(mode, mods)),
+            max_combo: frame.max_combo as i32,
+            n300: frame.n300 as i32,
+            n100: frame.n100 as i32,
+            n50: frame.n50 as i32,
+            nmiss: frame.nmiss as i32,
+            ngeki: frame.ngeki as i32,
+            nkatu: frame.nkatu as i32,
+            perfect: frame.perfect,
+            passed,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TeamTotals {
+    pub blue: f64,
+    pub red: f64,
+}
+
+impl TeamTotals {
+    /// `None` on a draw.
+    pub fn winner(&self) -> Option<Team> {
+        if self.blue > self.red {
+            Some(Team::Blue)
+        } else if self.red > self.blue {
+            Some(Team::Red)
+        } else {
+            None
+        }
+    }
+}
+
+impl Game {
+    /// Each team's total by the game's win condition: the sum of their
+    /// scores or max combos, or their average accuracy. `None` without teams.
+    pub fn team_totals(&self) -> Option<TeamTotals> {
+        if !self.team_type.has_teams() {
+            return None;
+        }
+
+        let total = |team: Team| {
+            let scores: Vec<&GameScore> = self.scores.iter().filter(|s| s.team == team).collect();
+            match self.win_condition {
+                WinCondition::Score | WinCondition::ScoreV2 => {
+                    scores.iter().map(|s| s.score as f64).sum()
+                }
+                WinCondition::Combo => scores.iter().map(|s| s.max_combo as f64).sum(),
+                WinCondition::Accuracy if scores.is_empty() => 0.0,
+                WinCondition::Accuracy => {
+                    scores.iter().map(|s| s.acc as f64).sum::<f64>() / scores.len() as f64
+                }
+            }
+        };
+
+        Some(TeamTotals {
+            blue: total(Team::Blue),
+            red: total(Team::Red),
+        })
+    }
+}
+
+/// Writes a live match's events to its archive.
+pub struct MatchHistory {
+    id: i64,
+}
+
+impl MatchHistory {
+    /// Starts archiving a new match. If that fails the match goes on without
+    /// a history.
+    pub fn open(name: &str) -> Self {
+        let record = MatchRecord {
+            id: 0,
+            name: name.to_string(),
+            start_time: chrono::Utc::now().timestamp(),
+            end_time: None,
+        };
+
+        match MATCH_HISTORY.insert(record) {
+            Ok(record) => Self { id: record.id },
+            Err(e) => {
+                println!("[multiplayer] failed to archive match {}: {}", name, e);
+                Self { id: 0 }
+            }
+        }
+    }
+
+    /// 0 if the match isn't archived.
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+
+    /// Adds an event to the timeline, returning its id.
+    pub fn record(&self, user_id: Option<i32>, detail: EventDetail) -> Option<i64> {
+        if self.id == 0 {
+            return None;
+        }
+
+        let event = MatchEvent {
+            id: 0,
+            match_id: self.id,
+            time: chrono::Utc::now().timestamp(),
+            user_id,
+            detail,
+        };
+        match MATCH_EVENTS.insert(event) {
+            Ok(event) => Some(event.id),
+            Err(e) => {
+                println!(
+                    "[multiplayer] failed to archive an event of {}: {}",
+                    self.id, e
+                );
+                None
+            }
+        }
+    }
+
+    /// Fills in the results of the game started by the given event.
+    pub fn end_game(&self, event_id: i64, scores: Vec<GameScore>, aborted: bool) {
+        let result = MATCH_EVENTS.update(event_id, |event| {
+            if let EventDetail::Game(game) = &mut event.detail {
+                game.end_time = Some(chrono::Utc::now().timestamp());
+                game.aborted = aborted;
+                game.scores = scores;
+            }
+        });
+        if let Err(e) = result {
+            println!(
+                "[multiplayer] failed to archive a game of {}: {}",
+                self.id, e
+            );
+        }
+    }
+
+    pub fn close(&self) {
+        if self.id == 0 {
+            return;
+        }
+
+        self.record(None, EventDetail::MatchDisbanded);
+        let result = MATCH_HISTORY.update(self.id, |record| {
+            record.end_time = Some(chrono::Utc::now().timestamp());
+        });
+        if let Err(e) = result {
+            println!(
+                "[multiplayer] failed to close the archive of {}: {}",
+                self.id, e
+            );
+        }
+    }
+}
+
+#[test]
+fn test_team_totals() {
+    let score = |team, score, acc, max_combo| GameScore {
+        slot: 0,
+        team,
+        user_id: 0,
+        mods: 0,
+        score,
+        acc,
+        max_combo,
+        n300: 0,
+        n100: 0,
+        n50: 0,
+        nmiss: 0,
+        ngeki: 0,
+        nkatu: 0,
+        perfect: false,
+        passed: true,
+    };
+    let mut game = Game {
+        map_id: 1,
+        map_md5: String::new(),
+        map_name: String::new(),
+        mode: 0,
+        mods: 0,
+        freemods: false,
+        win_condition: WinCondition::Score,
+        team_type: TeamType::TeamVs,
+        start_time: 0,
+        end_time: Some(0),
+        aborted: false,
+        scores: vec![
+            score(Team::Blue, 500_000, 90.0, 300),
+            score(Team::Blue, 400_000, 100.0, 100),
+            score(Team::Red, 800_000, 97.0, 350),
+        ],
+    };
+
+    let totals = game.team_totals().unwrap();
+    assert_eq!((totals.blue, totals.red), (900_000.0, 800_000.0));
+    assert_eq!(totals.winner(), Some(Team::Blue));
+
+    game.win_condition = WinCondition::Accuracy;
+    let totals = game.team_totals().unwrap();
+    assert_eq!((totals.blue, totals.red), (95.0, 97.0));
+    assert_eq!(totals.winner(), Some(Team::Red));
+
+    game.win_condition = WinCondition::Combo;
+    assert_eq!(game.team_totals().unwrap().winner(), Some(Team::Blue));
+
+    game.team_type = TeamType::HeadToHead;
+    assert_eq!(game.team_totals(), None);
+}
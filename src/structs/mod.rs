@@ -0,0 +1,50 @@
+#[path = "avatar.rs"]
+pub mod avatar;
+
+#[path = "beatmap.rs"]
+pub mod beatmap;
+
+#[path = "beatmap_source.rs"]
+pub mod beatmap_source;
+
+#[path = "bot.rs"]
+pub mod bot;
+
+#[path = "channel.rs"]
+pub mod channel;
+
+#[path = "chat_filter.rs"]
+pub mod chat_filter;
+
+#[path = "leaderboard.rs"]
+pub mod leaderboard;
+
+#[path = "match_history.rs"]
+pub mod match_history;
+
+#[path = "moderation.rs"]
+pub mod moderation;
+
+#[path = "multiplayer.rs"]
+pub mod multiplayer;
+
+#[path = "player.rs"]
+pub mod player;
+
+#[path = "ranking.rs"]
+pub mod ranking;
+
+#[path = "replay.rs"]
+pub mod replay;
+
+#[path = "score.rs"]
+pub mod score;
+
+#[path = "seasonal.rs"]
+pub mod seasonal;
+
+#[path = "stats.rs"]
+pub mod stats;
+
+#[path = "user.rs"]
+pub mod user;
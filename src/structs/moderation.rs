@@ -0,0 +1,246 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::time::Duration;
+
+use crate::constants::privileges::{Privileged, Privileges};
+use crate::storage::{Row, MODERATION_LOG, STATS, USERS};
+use crate::structs::channel::CHANNELS;
+use crate::structs::multiplayer::MATCHES;
+use crate::structs::player::{Player, PLAYERS};
+use crate::structs::ranking::RANKINGS;
+use crate::structs::user::User;
+use crate::utils::osu::packets::{
+    ChoAccountRestricted, ChoNotification, ChoPrivileges, ChoRestart, ChoSilenceEnd, ChoUserLogout,
+    ChoUserSilenced,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Silence,
+    Unsilence,
+    Restrict,
+    Unrestrict,
+    Ban,
+    Unban,
+    Grant,
+    Revoke,
+}
+
+/// A staff action against an account, as kept in the audit log.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub id: i64,
+    pub time: i64,
+    /// Who took the action.
+    pub actor_id: i32,
+    pub user_id: i32,
+    pub action: Action,
+    pub reason: String,
+    /// In seconds, for silences.
+    #[serde(default)]
+    pub duration: Option<u64>,
+    /// What was granted or revoked.
+    #[serde(default)]
+    pub privileges: Option<Privileges>,
+}
+
+impl Row for LogEntry {
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = id;
+    }
+}
+
+impl LogEntry {
+    fn new(actor_id: i32, user: &User, action: Action, reason: &str) -> Self {
+        Self {
+            id: 0,
+            time: chrono::Utc::now().timestamp(),
+            actor_id,
+            user_id: user.id,
+            action,
+            reason: reason.to_string(),
+            duration: None,
+            privileges: None,
+        }
+    }
+}
+
+fn log(user: &User, entry: LogEntry) {
+    println!(
+        "[moderation] {:?} of {} by {}: {}",
+        entry.action, user.name, entry.actor_id, entry.reason
+    );
+    let action = entry.action;
+    if let Err(e) = MODERATION_LOG.insert(entry) {
+        println!(
+            "[moderation] failed to log {:?} of {}: {}",
+            action, user.name, e
+        );
+    }
+}
+
+/// Keeps the user from chatting for a while. A new silence replaces whatever
+/// was left of the last one.
+pub fn silence(actor_id: i32, user: &User, duration: Duration, reason: &str) -> io::Result<()> {
+    let silence_end = chrono::Utc::now()
+        .timestamp()
+        .saturating_add(duration.as_secs().min(i64::MAX as u64) as i64);
+    USERS.update(user.id as i64, |u| {
+        u.silence_end = silence_end;
+        u.silence_reason = reason.to_string();
+    })?;
+    log(
+        user,
+        LogEntry {
+            duration: Some(duration.as_secs()),
+            ..LogEntry::new(actor_id, user, Action::Silence, reason)
+        },
+    );
+
+    for player in PLAYERS.sessions(user.id) {
+        player.set_silence_end(silence_end);
+        player.enqueue(&silence_end_packet(player.silence_remaining()));
+    }
+    // everyone drops what they've already said from their chat
+    PLAYERS.enqueue_all(&ChoUserSilenced::new(user.id).write());
+    Ok(())
+}
+
+pub fn unsilence(actor_id: i32, user: &User, reason: &str) -> io::Result<()> {
+    USERS.update(user.id as i64, |u| {
+        u.silence_end = 0;
+        u.silence_reason = String::new();
+    })?;
+    log(
+        user,
+        LogEntry::new(actor_id, user, Action::Unsilence, reason),
+    );
+
+    for player in PLAYERS.sessions(user.id) {
+        player.set_silence_end(0);
+        player.enqueue(&silence_end_packet(0));
+    }
+    Ok(())
+}
+
+/// The client counts down the silence itself, so it's over for it and for
+/// us at the same time.
+pub fn silence_end_packet(remaining: i64) -> Vec<u8> {
+    ChoSilenceEnd::new(remaining.min(i32::MAX as i64) as i32).write()
+}
+
+/// Hides the user from everyone else and takes them off the rankings. They
+/// stay online, limited to what restricted players can do.
+pub fn restrict(actor_id: i32, user: &User, reason: &str) -> io::Result<()> {
+    USERS.update(user.id as i64, |u| {
+        u.privileges.remove(Privileges::UNRESTRICTED)
+    })?;
+    log(
+        user,
+        LogEntry::new(actor_id, user, Action::Restrict, reason),
+    );
+
+    RANKINGS.remove(user.id);
+    for player in PLAYERS.sessions(user.id) {
+        player.set_privileges(player.privileges() - Privileges::UNRESTRICTED);
+        hide(&player);
+        player.enqueue(&ChoAccountRestricted::write());
+    }
+    PLAYERS.enqueue_others(user.id, &ChoUserLogout::new(user.id).write());
+    Ok(())
+}
+
+pub fn unrestrict(actor_id: i32, user: &User, reason: &str) -> io::Result<()> {
+    USERS.update(user.id as i64, |u| {
+        u.privileges.insert(Privileges::UNRESTRICTED)
+    })?;
+    log(
+        user,
+        LogEntry::new(actor_id, user, Action::Unrestrict, reason),
+    );
+
+    for stats in STATS.find(|s| s.user_id == user.id) {
+        RANKINGS.update(&stats, &user.country);
+    }
+    // the client stays in restricted mode until it logs in again
+    for player in PLAYERS.sessions(user.id) {
+        player.enqueue(&ChoNotification::new("Your account has been unrestricted.").write());
+        player.enqueue(&ChoRestart::new(0).write());
+    }
+    Ok(())
+}
+
+/// Restricts the user for good and logs them out; they can't log in again
+/// until they're unbanned.
+pub fn ban(actor_id: i32, user: &User, reason: &str) -> io::Result<()> {
+    USERS.update(user.id as i64, |u| {
+        u.privileges.remove(Privileges::UNRESTRICTED);
+        u.banned = true;
+    })?;
+    log(user, LogEntry::new(actor_id, user, Action::Ban, reason));
+
+    RANKINGS.remove(user.id);
+    for player in PLAYERS.sessions(user.id) {
+        hide(&player);
+        PLAYERS.remove(&player);
+    }
+    PLAYERS.enqueue_others(user.id, &ChoUserLogout::new(user.id).write());
+    Ok(())
+}
+
+/// Lets the user log in again. They stay restricted until they're
+/// unrestricted as well.
+pub fn unban(actor_id: i32, user: &User, reason: &str) -> io::Result<()> {
+    USERS.update(user.id as i64, |u| u.banned = false)?;
+    log(user, LogEntry::new(actor_id, user, Action::Unban, reason));
+    Ok(())
+}
+
+/// Gives the user privileges on top of what they have, or takes them away.
+pub fn set_privileges(
+    actor_id: i32,
+    user: &User,
+    privileges: Privileges,
+    granted: bool,
+    reason: &str,
+) -> io::Result<()> {
+    let user = USERS
+        .update(user.id as i64, |u| u.privileges.set(privileges, granted))?
+        .unwrap_or_else(|| user.clone());
+    let action = match granted {
+        true => Action::Grant,
+        false => Action::Revoke,
+    };
+    log(
+        &user,
+        LogEntry {
+            privileges: Some(privileges),
+            ..LogEntry::new(actor_id, &user, action, reason)
+        },
+    );
+
+    for player in PLAYERS.sessions(user.id) {
+        let mut session_privileges = player.privileges();
+        session_privileges.set(privileges, granted);
+        player.set_privileges(session_privileges);
+        player.enqueue(&ChoPrivileges::new(player.client_privileges().bits()).write());
+        if player.is_visible() {
+            PLAYERS.enqueue_all(&player.presence_packet());
+        }
+    }
+    Ok(())
+}
+
+/// Takes a session out of everything others could see it in.
+fn hide(player: &Player) {
+    MATCHES.leave(player);
+    MATCHES.part_lobby(player);
+    for channel in CHANNELS.all().into_iter().filter(|c| c.has(player)) {
+        channel.kick(player);
+    }
+}
@@ -0,0 +1,1166 @@
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::time::Duration;
+
+use crate::constants::mods::Mods;
+use crate::constants::multiplayer::{SlotStatus, Team, TeamType, WinCondition};
+use crate::structs::beatmap::Beatmap;
+use crate::structs::bot;
+use crate::structs::channel::{Channel, CHANNELS};
+use crate::structs::match_history::{EventDetail, Game, GameScore, MatchHistory};
+use crate::structs::player::Player;
+use crate::utils::osu::packet_reader::{MatchInfo, PacketReader, ScoreFrame};
+use crate::utils::osu::packets::{
+    ChoDisposeMatch, ChoMatchAbort, ChoMatchAllPlayersLoaded, ChoMatchChangePassword,
+    ChoMatchComplete, ChoMatchInvite, ChoMatchJoinSuccess, ChoMatchPlayerFailed,
+    ChoMatchPlayerSkipped, ChoMatchScoreUpdate, ChoMatchSkip, ChoMatchStart, ChoMatchTransferHost,
+    ChoNewMatch, ChoUpdateMatch,
+};
+
+lazy_static::lazy_static! {
+    pub static ref MATCHES: MatchList = MatchList::default();
+}
+
+/// What every match's chat is called in the client.
+pub const MULTIPLAYER_CHANNEL: &str = "#multiplayer";
+
+pub const SLOTS: usize = 16;
+
+/// When a `!mp start` countdown says how long is left.
+const COUNTDOWN_ANNOUNCE: [u64; 7] = [30, 10, 5, 4, 3, 2, 1];
+
+#[derive(Clone)]
+pub struct Slot {
+    pub status: SlotStatus,
+    pub team: Team,
+    /// The slot's own mods, with freemods on.
+    pub mods: Mods,
+    pub player: Option<Player>,
+    pub loaded: bool,
+    pub skipped: bool,
+    pub failed: bool,
+    /// The last score frame the player sent this game.
+    pub frame: Option<ScoreFrame>,
+}
+
+impl Slot {
+    fn empty() -> Self {
+        Self {
+            status: SlotStatus::OPEN,
+            team: Team::Neutral,
+            mods: Mods::empty(),
+            player: None,
+            loaded: false,
+            skipped: false,
+            failed: false,
+            frame: None,
+        }
+    }
+
+    /// Empties the slot, leaving it open or locked.
+    fn reset(&mut self, status: SlotStatus) {
+        *self = Self {
+            status,
+            ..Self::empty()
+        };
+    }
+
+    pub fn has_player(&self) -> bool {
+        self.status.intersects(SlotStatus::HAS_PLAYER)
+    }
+
+    fn player_id(&self) -> Option<i32> {
+        self.player.as_ref().map(|p| p.id())
+    }
+}
+
+pub struct MatchState {
+    pub name: String,
+    pub password: String,
+    pub map_name: String,
+    pub map_id: i32,
+    pub map_md5: String,
+    pub mode: u8,
+    /// The mods everyone plays with; only speed changing ones with freemods.
+    pub mods: Mods,
+    pub freemods: bool,
+    /// 0 while nobody is host.
+    pub host_id: i32,
+    pub win_condition: WinCondition,
+    pub team_type: TeamType,
+    pub seed: i32,
+    pub in_progress: bool,
+    pub slots: [Slot; SLOTS],
+    /// Made with `!mp make`; it has no host until a referee picks one and
+    /// stays open when everyone leaves.
+    pub tournament: bool,
+    /// Can use `!mp` on the match without playing in it.
+    pub referees: Vec<i32>,
+    pub banned: Vec<i32>,
+    /// Keeps players from changing slots and teams themselves.
+    pub locked: bool,
+    /// Bumped to cancel a running `!mp start` countdown.
+    countdown: u64,
+    /// The history event of the game in progress.
+    game_event: Option<i64>,
+}
+
+impl MatchState {
+    fn slot_of(&self, player_id: i32) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|s| s.player_id() == Some(player_id))
+    }
+
+    fn players(&self) -> Vec<Player> {
+        self.slots.iter().filter_map(|s| s.player.clone()).collect()
+    }
+
+    fn playing(&self) -> impl Iterator<Item = &Slot> {
+        self.slots
+            .iter()
+            .filter(|s| s.status == SlotStatus::PLAYING)
+    }
+
+    /// Whoever has been in the match the longest, going by slot order.
+    fn first_player(&self) -> Option<i32> {
+        self.slots.iter().find_map(|s| s.player_id())
+    }
+
+    /// Puts players on the blue and red teams in turns, or on none.
+    fn assign_teams(&mut self) {
+        let has_teams = self.team_type.has_teams();
+        for (i, slot) in self.slots.iter_mut().enumerate() {
+            slot.team = match (has_teams, i % 2) {
+                (false, _) => Team::Neutral,
+                (true, 0) => Team::Blue,
+                (true, _) => Team::Red,
+            };
+        }
+    }
+
+    fn unready_all(&mut self) {
+        for slot in self.slots.iter_mut() {
+            if slot.status == SlotStatus::READY {
+                slot.status = SlotStatus::NOT_READY;
+            }
+        }
+    }
+
+    fn set_freemods(&mut self, freemods: bool) {
+        if freemods == self.freemods {
+            return;
+        }
+        self.freemods = freemods;
+
+        let speed = self.mods & Mods::SPEED_CHANGING;
+        if freemods {
+            // everyone keeps what they were playing with, bar the speed
+            for slot in self.slots.iter_mut().filter(|s| s.has_player()) {
+                slot.mods = self.mods - Mods::SPEED_CHANGING;
+            }
+            self.mods = speed;
+        } else {
+            let host_mods = self
+                .slot_of(self.host_id)
+                .map(|i| self.slots[i].mods)
+                .unwrap_or_else(Mods::empty);
+            self.mods = host_mods | speed;
+            for slot in self.slots.iter_mut() {
+                slot.mods = Mods::empty();
+            }
+        }
+    }
+
+    /// Everyone's results so far in the game in progress.
+    fn scores(&self) -> Vec<GameScore> {
+        let mode = self.mode;
+        let mods = self.mods;
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| {
+                s.status
+                    .intersects(SlotStatus::PLAYING | SlotStatus::COMPLETE)
+            })
+            .filter_map(|(i, s)| {
+                let user_id = s.player_id()?;
+                let frame = s.frame.as_ref()?;
+                Some(GameScore::new(
+                    i as u8,
+                    s.team,
+                    user_id,
+                    mods | s.mods,
+                    mode,
+                    frame,
+                    !s.failed,
+                ))
+            })
+            .collect()
+    }
+
+    /// Ends the game in progress, putting everyone who played back to not
+    /// ready. Returns who was playing.
+    fn end_game(&mut self) -> Vec<Player> {
+        let mut played = Vec::new();
+        for slot in self.slots.iter_mut() {
+            if slot
+                .status
+                .intersects(SlotStatus::PLAYING | SlotStatus::COMPLETE)
+            {
+                slot.status = SlotStatus::NOT_READY;
+                played.extend(slot.player.clone());
+            }
+            slot.loaded = false;
+            slot.skipped = false;
+            slot.failed = false;
+            slot.frame = None;
+        }
+        self.in_progress = false;
+        played
+    }
+}
+
+pub struct Match {
+    id: i32,
+    channel: Arc<Channel>,
+    history: MatchHistory,
+    state: Mutex<MatchState>,
+}
+
+impl Match {
+    fn new(id: i32, name: &str, password: &str) -> Self {
+        let channel = Channel::instance(
+            &format!("#multi_{}", id),
+            MULTIPLAYER_CHANNEL,
+            &format!("multiplayer game #{}", id),
+        );
+
+        Self {
+            id,
+            channel: CHANNELS.add(channel),
+            history: MatchHistory::open(name),
+            state: Mutex::new(MatchState {
+                name: name.to_string(),
+                password: password.to_string(),
+                map_name: String::new(),
+                map_id: 0,
+                map_md5: String::new(),
+                mode: 0,
+                mods: Mods::empty(),
+                freemods: false,
+                host_id: 0,
+                win_condition: WinCondition::Score,
+                team_type: TeamType::HeadToHead,
+                seed: 0,
+                in_progress: false,
+                slots: std::array::from_fn(|_| Slot::empty()),
+                tournament: false,
+                referees: Vec::new(),
+                banned: Vec::new(),
+                locked: false,
+                countdown: 0,
+                game_event: None,
+            }),
+        }
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn channel(&self) -> &Arc<Channel> {
+        &self.channel
+    }
+
+    /// The match's id in the history, which unlike its own isn't reused.
+    pub fn history_id(&self) -> i64 {
+        self.history.id()
+    }
+
+    /// The match's state, for reading; changes should go through the methods
+    /// here so everyone hears about them.
+    pub fn state(&self) -> MutexGuard<'_, MatchState> {
+        self.state.lock().unwrap()
+    }
+
+    pub fn name(&self) -> String {
+        self.state().name.clone()
+    }
+
+    pub fn info(&self) -> MatchInfo {
+        let state = self.state();
+
+        MatchInfo {
+            id: self.id as i16,
+            in_progress: state.in_progress,
+            powerplay: 0,
+            mods: state.mods.bits() as i32,
+            name: state.name.clone(),
+            password: state.password.clone(),
+            map_name: state.map_name.clone(),
+            map_id: state.map_id,
+            map_md5: state.map_md5.clone(),
+            slot_statuses: std::array::from_fn(|i| state.slots[i].status.bits()),
+            slot_teams: std::array::from_fn(|i| state.slots[i].team as u8),
+            slot_ids: state.slots.iter().filter_map(|s| s.player_id()).collect(),
+            host_id: state.host_id,
+            mode: state.mode,
+            win_condition: state.win_condition as u8,
+            team_type: state.team_type as u8,
+            freemods: state.freemods,
+            slot_mods: std::array::from_fn(|i| state.slots[i].mods.bits() as i32),
+            seed: state.seed,
+        }
+    }
+
+    pub fn players(&self) -> Vec<Player> {
+        self.state().players()
+    }
+
+    pub fn enqueue(&self, data: &[u8]) {
+        for player in self.players() {
+            player.enqueue(data);
+        }
+    }
+
+    /// Sends the match as it is now to its players, tournament clients
+    /// watching it and the lobby.
+    pub fn update(&self) {
+        let info = self.info();
+        let packet = ChoUpdateMatch::new(info.clone(), true).write();
+        self.enqueue(&packet);
+        for watcher in self.watchers() {
+            watcher.enqueue(&packet);
+        }
+        MATCHES.enqueue_lobby(&ChoUpdateMatch::new(info, false).write());
+    }
+
+    /// Tournament clients in the match's chat.
+    fn watchers(&self) -> Vec<Player> {
+        self.channel
+            .members()
+            .into_iter()
+            .filter(|p| p.is_tourney_client())
+            .collect()
+    }
+
+    /// Has a tournament client follow the match's chat and state without
+    /// playing in it.
+    pub fn watch(&self, player: &Player) {
+        self.channel.join(player);
+        player.enqueue(&ChoUpdateMatch::new(self.info(), true).write());
+    }
+
+    pub fn unwatch(&self, player: &Player) {
+        if player.match_id() != Some(self.id) {
+            self.channel.part(player);
+        }
+    }
+
+    /// A message from the bot in the match's chat.
+    pub fn announce(&self, text: &str) {
+        self.channel.send(bot::name(), bot::id(), text);
+    }
+
+    pub fn is_host(&self, player: &Player) -> bool {
+        self.state().host_id == player.id()
+    }
+
+    /// Whether the player may run `!mp` on this match.
+    pub fn is_referee(&self, player: &Player) -> bool {
+        let state = self.state();
+        state.host_id == player.id() || state.referees.contains(&player.id())
+    }
+
+    pub fn join(&self, player: &Player, password: &str) -> Result<(), String> {
+        let (joined, became_host) = {
+            let mut state = self.state();
+            if state.slot_of(player.id()).is_some() {
+                return Ok(());
+            }
+            if state.banned.contains(&player.id()) {
+                return Err("you're banned from this match".to_string());
+            }
+            let is_referee = state.referees.contains(&player.id());
+            if !state.password.is_empty() && state.password != password && !is_referee {
+                return Err("wrong password".to_string());
+            }
+
+            let index = state
+                .slots
+                .iter()
+                .position(|s| s.status == SlotStatus::OPEN)
+                .ok_or_else(|| "the match is full".to_string())?;
+
+            let team = if state.team_type.has_teams() {
+                let blue = state
+                    .slots
+                    .iter()
+                    .filter(|s| s.has_player() && s.team == Team::Blue)
+                    .count();
+                let red = state
+                    .slots
+                    .iter()
+                    .filter(|s| s.has_player() && s.team == Team::Red)
+                    .count();
+                if blue <= red {
+                    Team::Blue
+                } else {
+                    Team::Red
+                }
+            } else {
+                Team::Neutral
+            };
+
+            state.slots[index] = Slot {
+                status: SlotStatus::NOT_READY,
+                team,
+                player: Some(player.clone()),
+                ..Slot::empty()
+            };
+            let became_host = state.host_id == 0 && !state.tournament;
+            if became_host {
+                state.host_id = player.id();
+            }
+            (index, became_host)
+        };
+
+        self.history
+            .record(Some(player.id()), EventDetail::PlayerJoined);
+        if became_host {
+            self.history
+                .record(Some(player.id()), EventDetail::HostChanged);
+        }
+        player.set_match_id(Some(self.id));
+        self.channel.join(player);
+        player.enqueue(&ChoMatchJoinSuccess::new(self.info()).write());
+        self.update();
+        println!(
+            "[multiplayer] {} joined match {} in slot {}",
+            player.name(),
+            self.id,
+            joined
+        );
+        Ok(())
+    }
+
+    /// Takes the player out of the match, closing it if nobody's left.
+    pub fn part(&self, player: &Player) {
+        self.remove(player, EventDetail::PlayerLeft);
+    }
+
+    /// Removes a player, sending them back to the lobby.
+    pub fn kick(&self, player: &Player) {
+        self.remove(player, EventDetail::PlayerKicked);
+        player.enqueue(&ChoDisposeMatch::new(self.id).write());
+    }
+
+    fn remove(&self, player: &Player, detail: EventDetail) {
+        let (empty, game_over, new_host) = {
+            let mut state = self.state();
+            let index = match state.slot_of(player.id()) {
+                Some(index) => index,
+                None => return,
+            };
+            state.slots[index].reset(SlotStatus::OPEN);
+
+            let mut new_host = None;
+            if state.host_id == player.id() {
+                state.host_id = match state.tournament {
+                    true => 0,
+                    false => state.first_player().unwrap_or(0),
+                };
+                new_host = state
+                    .slot_of(state.host_id)
+                    .and_then(|i| state.slots[i].player.clone());
+            }
+
+            let empty = !state.slots.iter().any(|s| s.has_player());
+            let game_over = state.in_progress && state.playing().next().is_none();
+            (empty && !state.tournament, game_over, new_host)
+        };
+
+        self.history.record(Some(player.id()), detail);
+        if let Some(host) = &new_host {
+            self.history
+                .record(Some(host.id()), EventDetail::HostChanged);
+        }
+        if player.match_id() == Some(self.id) {
+            player.set_match_id(None);
+        }
+        self.channel.kick(player);
+
+        if empty {
+            MATCHES.dispose(self.id);
+            return;
+        }
+        if game_over {
+            self.finish();
+        }
+        if let Some(host) = new_host {
+            host.enqueue(&ChoMatchTransferHost::write());
+        }
+        self.update();
+    }
+
+    pub fn change_slot(&self, player: &Player, slot: usize) {
+        {
+            let mut state = self.state();
+            if state.locked || state.in_progress || slot >= SLOTS {
+                return;
+            }
+            let from = match state.slot_of(player.id()) {
+                Some(from) => from,
+                None => return,
+            };
+            if state.slots[slot].status != SlotStatus::OPEN {
+                return;
+            }
+
+            state.slots[slot] = state.slots[from].clone();
+            state.slots[from].reset(SlotStatus::OPEN);
+        }
+        self.update();
+    }
+
+    pub fn set_ready(&self, player: &Player, ready: bool) {
+        self.set_status(player, SlotStatus::READY, SlotStatus::NOT_READY, ready);
+    }
+
+    pub fn set_has_map(&self, player: &Player, has_map: bool) {
+        self.set_status(player, SlotStatus::NOT_READY, SlotStatus::NO_MAP, has_map);
+    }
+
+    /// Moves the player's slot between two statuses.
+    fn set_status(&self, player: &Player, on: SlotStatus, off: SlotStatus, value: bool) {
+        {
+            let mut state = self.state();
+            let index = match state.slot_of(player.id()) {
+                Some(index) => index,
+                None => return,
+            };
+            let (from, to) = if value { (off, on) } else { (on, off) };
+            if state.slots[index].status != from {
+                return;
+            }
+            state.slots[index].status = to;
+        }
+        self.update();
+    }
+
+    /// Locks an open slot or opens a locked one; locking a slot with someone
+    /// in it kicks them.
+    pub fn toggle_lock(&self, slot: usize) {
+        if slot >= SLOTS {
+            return;
+        }
+
+        let kicked = {
+            let mut state = self.state();
+            let target = &mut state.slots[slot];
+            if target.status == SlotStatus::LOCKED {
+                target.status = SlotStatus::OPEN;
+                None
+            } else if target.status == SlotStatus::OPEN {
+                target.status = SlotStatus::LOCKED;
+                None
+            } else {
+                target.player.clone()
+            }
+        };
+
+        if let Some(player) = kicked {
+            if self.is_host(&player) {
+                return;
+            }
+            self.kick(&player);
+            self.state().slots[slot].status = SlotStatus::LOCKED;
+        }
+        self.update();
+    }
+
+    /// Applies the settings the host's client sent.
+    pub fn change_settings(&self, info: &MatchInfo) {
+        let map_changed = {
+            let mut state = self.state();
+            state.name = info.name.clone();
+
+            let map_changed = info.map_md5 != state.map_md5;
+            if map_changed {
+                state.unready_all();
+            }
+            state.map_name = info.map_name.clone();
+            state.map_id = info.map_id;
+            state.map_md5 = info.map_md5.clone();
+            state.mode = info.mode;
+            state.seed = info.seed;
+
+            if let Ok(win_condition) = WinCondition::try_from(info.win_condition) {
+                state.win_condition = win_condition;
+            }
+            if let Ok(team_type) = TeamType::try_from(info.team_type) {
+                if team_type != state.team_type {
+                    state.team_type = team_type;
+                    state.assign_teams();
+                }
+            }
+            state.set_freemods(info.freemods);
+            map_changed
+        };
+
+        // the client clears the map while the host is picking one
+        if map_changed && !info.map_md5.is_empty() {
+            self.record_map(info.map_id, &info.map_md5, &info.map_name);
+        }
+        self.update();
+    }
+
+    fn record_map(&self, map_id: i32, map_md5: &str, map_name: &str) {
+        let host_id = self.state().host_id;
+        self.history.record(
+            Some(host_id).filter(|id| *id != 0),
+            EventDetail::BeatmapChanged {
+                map_id,
+                map_md5: map_md5.to_string(),
+                map_name: map_name.to_string(),
+            },
+        );
+    }
+
+    pub fn set_password(&self, password: &str) {
+        self.state().password = password.to_string();
+        self.enqueue(&ChoMatchChangePassword::new(password).write());
+        self.update();
+    }
+
+    /// Mods the player picked; with freemods on everyone picks their own,
+    /// otherwise only the host's count.
+    pub fn change_mods(&self, player: &Player, mods: Mods) {
+        {
+            let mut state = self.state();
+            let index = match state.slot_of(player.id()) {
+                Some(index) => index,
+                None => return,
+            };
+            let is_host = state.host_id == player.id();
+
+            if state.freemods {
+                if is_host {
+                    state.mods = mods & Mods::SPEED_CHANGING;
+                }
+                state.slots[index].mods = mods - Mods::SPEED_CHANGING;
+            } else if is_host {
+                state.mods = mods;
+            } else {
+                return;
+            }
+        }
+        self.update();
+    }
+
+    /// Sets the mods for everyone, or just the speed with freemods.
+    pub fn set_mods(&self, mods: Mods, freemods: bool) {
+        {
+            let mut state = self.state();
+            state.set_freemods(freemods);
+            if freemods {
+                state.mods = mods & Mods::SPEED_CHANGING;
+                let free = mods - Mods::SPEED_CHANGING;
+                for slot in state.slots.iter_mut().filter(|s| s.has_player()) {
+                    slot.mods = free;
+                }
+            } else {
+                state.mods = mods;
+            }
+        }
+        self.update();
+    }
+
+    pub fn change_team(&self, player: &Player) {
+        {
+            let mut state = self.state();
+            if state.locked || !state.team_type.has_teams() {
+                return;
+            }
+            let index = match state.slot_of(player.id()) {
+                Some(index) => index,
+                None => return,
+            };
+            let slot = &mut state.slots[index];
+            slot.team = match slot.team {
+                Team::Blue => Team::Red,
+                _ => Team::Blue,
+            };
+        }
+        self.update();
+    }
+
+    /// Makes whoever's in the slot host.
+    pub fn transfer_host(&self, slot: usize) {
+        let player = match self.state().slots.get(slot).and_then(|s| s.player.clone()) {
+            Some(player) => player,
+            None => return,
+        };
+        self.set_host(Some(&player));
+    }
+
+    pub fn set_host(&self, player: Option<&Player>) {
+        {
+            let mut state = self.state();
+            let id = player.map(|p| p.id()).unwrap_or(0);
+            if id != 0 && state.slot_of(id).is_none() {
+                return;
+            }
+            state.host_id = id;
+        }
+        if let Some(player) = player {
+            self.history
+                .record(Some(player.id()), EventDetail::HostChanged);
+            player.enqueue(&ChoMatchTransferHost::write());
+        }
+        self.update();
+    }
+
+    pub fn set_map(&self, map: &Beatmap) {
+        {
+            let mut state = self.state();
+            state.map_name = map.full_name();
+            state.map_id = map.id;
+            state.map_md5 = map.md5.clone();
+            state.mode = map.mode;
+            state.unready_all();
+        }
+        self.record_map(map.id, &map.md5, &map.full_name());
+        self.update();
+    }
+
+    pub fn set_team_type(&self, team_type: TeamType) {
+        {
+            let mut state = self.state();
+            state.team_type = team_type;
+            state.assign_teams();
+        }
+        self.update();
+    }
+
+    pub fn set_win_condition(&self, win_condition: WinCondition) {
+        self.state().win_condition = win_condition;
+        self.update();
+    }
+
+    /// Opens the first `size` slots and locks the rest, kicking anyone in
+    /// them.
+    pub fn set_size(&self, size: usize) {
+        let kicked: Vec<Player> = {
+            let state = self.state();
+            state.slots[size.min(SLOTS)..]
+                .iter()
+                .filter_map(|s| s.player.clone())
+                .collect()
+        };
+        for player in &kicked {
+            self.kick(player);
+        }
+
+        {
+            let mut state = self.state();
+            for (i, slot) in state.slots.iter_mut().enumerate() {
+                if i < size && slot.status == SlotStatus::LOCKED {
+                    slot.status = SlotStatus::OPEN;
+                } else if i >= size && slot.status == SlotStatus::OPEN {
+                    slot.status = SlotStatus::LOCKED;
+                }
+            }
+        }
+        self.update();
+    }
+
+    pub fn set_locked(&self, locked: bool) {
+        self.state().locked = locked;
+    }
+
+    /// Hands the match over to referees, starting with this one.
+    pub fn make_tournament(&self, referee: &Player) {
+        {
+            let mut state = self.state();
+            state.tournament = true;
+            state.referees.push(referee.id());
+        }
+        self.channel.join(referee);
+    }
+
+    pub fn add_referee(&self, user_id: i32) {
+        let mut state = self.state();
+        if !state.referees.contains(&user_id) {
+            state.referees.push(user_id);
+        }
+    }
+
+    pub fn remove_referee(&self, user_id: i32) {
+        self.state().referees.retain(|id| *id != user_id);
+    }
+
+    /// Keeps a user out of the match, kicking them if they're in it.
+    pub fn ban(&self, user_id: i32) {
+        let player = {
+            let mut state = self.state();
+            if !state.banned.contains(&user_id) {
+                state.banned.push(user_id);
+            }
+            state
+                .slot_of(user_id)
+                .and_then(|i| state.slots[i].player.clone())
+        };
+        if let Some(player) = player {
+            self.kick(&player);
+        }
+    }
+
+    /// Starts the game for everyone who has the map, ready or not. Returns
+    /// how many are playing.
+    pub fn start(&self) -> Result<usize, String> {
+        let started = {
+            let mut state = self.state();
+            if state.in_progress {
+                return Err("the match is already in progress".to_string());
+            }
+            if state.map_md5.is_empty() {
+                return Err("no map is picked".to_string());
+            }
+            state.countdown += 1;
+
+            let mut started = Vec::new();
+            for slot in state.slots.iter_mut() {
+                if slot
+                    .status
+                    .intersects(SlotStatus::READY | SlotStatus::NOT_READY)
+                {
+                    slot.status = SlotStatus::PLAYING;
+                    slot.loaded = false;
+                    slot.skipped = false;
+                    started.extend(slot.player.clone());
+                }
+            }
+            if started.is_empty() {
+                return Err("nobody has the map".to_string());
+            }
+            state.in_progress = true;
+
+            let game = Game {
+                map_id: state.map_id,
+                map_md5: state.map_md5.clone(),
+                map_name: state.map_name.clone(),
+                mode: state.mode,
+                mods: state.mods.bits(),
+                freemods: state.freemods,
+                win_condition: state.win_condition,
+                team_type: state.team_type,
+                start_time: chrono::Utc::now().timestamp(),
+                end_time: None,
+                aborted: false,
+                scores: Vec::new(),
+            };
+            state.game_event = self.history.record(None, EventDetail::Game(game));
+            started
+        };
+
+        let packet = ChoMatchStart::new(self.info()).write();
+        for player in &started {
+            player.enqueue(&packet);
+        }
+        self.update();
+        Ok(started.len())
+    }
+
+    /// Starts the game after a countdown announced in the match's chat.
+    pub fn start_countdown(self: &Arc<Self>, seconds: u64) {
+        let countdown = {
+            let mut state = self.state();
+            state.countdown += 1;
+            state.countdown
+        };
+        self.announce(&format!("The match starts in {} seconds.", seconds));
+
+        let m = self.clone();
+        actix_web::rt::spawn(async move {
+            let mut left = seconds;
+            for announce in COUNTDOWN_ANNOUNCE.iter().filter(|s| **s < seconds) {
+                actix_web::rt::time::sleep(Duration::from_secs(left - announce)).await;
+                left = *announce;
+                if m.state().countdown != countdown {
+                    return;
+                }
+                m.announce(&format!("The match starts in {} seconds.", left));
+            }
+            actix_web::rt::time::sleep(Duration::from_secs(left)).await;
+            if m.state().countdown != countdown {
+                return;
+            }
+
+            match m.start() {
+                Ok(_) => m.announce("Good luck, have fun!"),
+                Err(e) => m.announce(&format!("Couldn't start: {}.", e)),
+            }
+        });
+    }
+
+    /// Stops a running countdown, if there is one.
+    pub fn cancel_countdown(&self) {
+        self.state().countdown += 1;
+    }
+
+    pub fn load_complete(&self, player: &Player) {
+        let (all_loaded, playing) = {
+            let mut state = self.state();
+            let index = match state.slot_of(player.id()) {
+                Some(index) => index,
+                None => return,
+            };
+            state.slots[index].loaded = true;
+            let playing: Vec<Player> = state.playing().filter_map(|s| s.player.clone()).collect();
+            let all_loaded = state.playing().all(|s| s.loaded);
+            (all_loaded, playing)
+        };
+
+        if all_loaded {
+            for player in playing {
+                player.enqueue(&ChoMatchAllPlayersLoaded::write());
+            }
+        }
+    }
+
+    /// Passes a player's score frame on to everyone in the match, with their
+    /// slot as its id.
+    pub fn score_update(&self, player: &Player, mut data: Vec<u8>) {
+        if data.len() < ScoreFrame::SIZE {
+            return;
+        }
+        let index = {
+            let mut state = self.state();
+            let index = match state.slot_of(player.id()) {
+                Some(index) => index,
+                None => return,
+            };
+            let frame = PacketReader::new(data.clone()).read_score_frame();
+            state.slots[index].frame = Some(frame);
+            index
+        };
+        data[4] = index as u8;
+        self.enqueue(&ChoMatchScoreUpdate::new(data).write());
+    }
+
+    pub fn player_failed(&self, player: &Player) {
+        let index = {
+            let mut state = self.state();
+            let index = match state.slot_of(player.id()) {
+                Some(index) => index,
+                None => return,
+            };
+            state.slots[index].failed = true;
+            index
+        };
+        self.enqueue(&ChoMatchPlayerFailed::new(index as i32).write());
+    }
+
+    pub fn skip(&self, player: &Player) {
+        let everyone_skipped = {
+            let mut state = self.state();
+            let index = match state.slot_of(player.id()) {
+                Some(index) => index,
+                None => return,
+            };
+            state.slots[index].skipped = true;
+            let everyone_skipped = state.playing().all(|s| s.skipped);
+            everyone_skipped
+        };
+
+        self.enqueue(&ChoMatchPlayerSkipped::new(player.id()).write());
+        if everyone_skipped {
+            self.enqueue(&ChoMatchSkip::write());
+        }
+    }
+
+    /// The player finished the map; the game ends once everyone has.
+    pub fn complete(&self, player: &Player) {
+        let game_over = {
+            let mut state = self.state();
+            let index = match state.slot_of(player.id()) {
+                Some(index) if state.slots[index].status == SlotStatus::PLAYING => index,
+                _ => return,
+            };
+            state.slots[index].status = SlotStatus::COMPLETE;
+            let game_over = state.playing().next().is_none();
+            game_over
+        };
+
+        if game_over {
+            self.finish();
+            self.update();
+        }
+    }
+
+    fn finish(&self) {
+        let played = self.end_game(false);
+        for player in played {
+            player.enqueue(&ChoMatchComplete::write());
+        }
+    }
+
+    /// Ends the game in progress without results.
+    pub fn abort(&self) -> Result<(), String> {
+        {
+            let mut state = self.state();
+            state.countdown += 1;
+            if !state.in_progress {
+                return Err("the match isn't in progress".to_string());
+            }
+        }
+        let played = self.end_game(true);
+
+        for player in played {
+            player.enqueue(&ChoMatchAbort::write());
+        }
+        self.update();
+        Ok(())
+    }
+
+    /// Ends the game in progress and archives its results.
+    fn end_game(&self, aborted: bool) -> Vec<Player> {
+        let (played, event, scores) = {
+            let mut state = self.state();
+            let scores = state.scores();
+            let event = state.game_event.take();
+            (state.end_game(), event, scores)
+        };
+
+        if let Some(event) = event {
+            self.history.end_game(event, scores, aborted);
+        }
+        played
+    }
+
+    /// A link to the match, sent as a private message to `to`.
+    pub fn invite_packet(&self, from: &str, from_id: i32, to: &str) -> Vec<u8> {
+        let state = self.state();
+        let text = format!(
+            "Come join my multiplayer match: [osump://{}/{} {}]",
+            self.id,
+            state.password.replace(' ', "_"),
+            state.name
+        );
+        ChoMatchInvite::new(from, text.as_str(), to, from_id).write()
+    }
+}
+
+#[derive(Default)]
+pub struct MatchList {
+    matches: RwLock<Vec<Arc<Match>>>,
+    /// Players looking at the list of matches.
+    lobby: RwLock<Vec<Player>>,
+}
+
+impl MatchList {
+    pub fn create(&self, name: &str, password: &str, creator: &Player) -> Arc<Match> {
+        let m = {
+            let mut matches = self.matches.write().unwrap();
+            // the client only has room for an i16
+            let id = (1..=i16::MAX as i32)
+                .find(|id| !matches.iter().any(|m| m.id() == *id))
+                .expect("ran out of match ids");
+
+            let m = Arc::new(Match::new(id, name, password));
+            matches.push(m.clone());
+            m
+        };
+
+        m.history
+            .record(Some(creator.id()), EventDetail::MatchCreated);
+        self.enqueue_lobby(&ChoNewMatch::new(m.info()).write());
+        println!("[multiplayer] created match {}: {}", m.id(), name);
+        m
+    }
+
+    pub fn get(&self, id: i32) -> Option<Arc<Match>> {
+        self.matches
+            .read()
+            .unwrap()
+            .iter()
+            .find(|m| m.id() == id)
+            .cloned()
+    }
+
+    /// The match the player is in.
+    pub fn of(&self, player: &Player) -> Option<Arc<Match>> {
+        player.match_id().and_then(|id| self.get(id))
+    }
+
+    /// A match by the chat it owns.
+    pub fn by_channel(&self, channel_name: &str) -> Option<Arc<Match>> {
+        self.matches
+            .read()
+            .unwrap()
+            .iter()
+            .find(|m| m.channel().name() == channel_name)
+            .cloned()
+    }
+
+    pub fn all(&self) -> Vec<Arc<Match>> {
+        self.matches.read().unwrap().clone()
+    }
+
+    /// Closes a match, sending everyone in it back to the lobby.
+    pub fn dispose(&self, id: i32) {
+        let m = match self.get(id) {
+            Some(m) => m,
+            None => return,
+        };
+        self.matches.write().unwrap().retain(|m| m.id() != id);
+
+        m.cancel_countdown();
+        if m.state().in_progress {
+            m.end_game(true);
+        }
+        m.history.close();
+        let packet = ChoDisposeMatch::new(id).write();
+        for player in m.players() {
+            player.set_match_id(None);
+            player.enqueue(&packet);
+        }
+        CHANNELS.remove(m.channel().name());
+        self.enqueue_lobby(&packet);
+        println!("[multiplayer] closed match {}", id);
+    }
+
+    /// Takes the player out of whatever match they're in.
+    pub fn leave(&self, player: &Player) {
+        if let Some(m) = self.of(player) {
+            m.part(player);
+        }
+    }
+
+    pub fn join_lobby(&self, player: &Player) {
+        {
+            let mut lobby = self.lobby.write().unwrap();
+            if !lobby.iter().any(|p| p.token() == player.token()) {
+                lobby.push(player.clone());
+            }
+        }
+        for m in self.all() {
+            player.enqueue(&ChoNewMatch::new(m.info()).write());
+        }
+    }
+
+    pub fn part_lobby(&self, player: &Player) {
+        self.lobby
+            .write()
+            .unwrap()
+            .retain(|p| p.token() != player.token());
+    }
+
+    pub fn enqueue_lobby(&self, data: &[u8]) {
+        for player in self.lobby.read().unwrap().iter() {
+            player.enqueue(data);
+        }
+    }
+}
@@ -0,0 +1,383 @@
+use std::sync::atomic::{AtomicI64, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+
+use crate::constants::countries::country_id;
+use crate::constants::gamemodes::GameMode;
+use crate::constants::mods::Mods;
+use crate::constants::privileges::{ClientPrivileges, Privileged, Privileges};
+use crate::structs::ranking::RANKINGS;
+use crate::structs::stats::Stats;
+use crate::structs::user::{make_safe_name, User};
+use crate::utils::osu::packets::{ChoUserPresence, ChoUserStats};
+
+lazy_static::lazy_static! {
+    pub static ref PLAYERS: PlayerList = PlayerList::default();
+}
+
+static TOKEN_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// What the player is doing, as last reported by `OsuChangeAction`.
+#[derive(Clone, Debug, Default)]
+pub struct Status {
+    pub action: u8,
+    pub info_text: String,
+    pub map_md5: String,
+    pub mods: u32,
+    /// The client's mode (0-3).
+    pub mode: u8,
+    pub map_id: i32,
+}
+
+impl Status {
+    pub fn game_mode(&self) -> GameMode {
+        GameMode::from_params(self.mode, Mods::from_bits_truncate(self.mods))
+    }
+}
+
+/// The first request a client sends: `name\npw_md5\nversion|utc|city|hashes|pm\n`.
+#[derive(Clone, Debug)]
+pub struct LoginData {
+    pub name: String,
+    pub pw_md5: String,
+    pub osu_version: String,
+    pub utc_offset: i8,
+    pub display_city: bool,
+    pub client_hashes: String,
+    pub pm_private: bool,
+}
+
+impl LoginData {
+    pub fn parse(body: &[u8]) -> Option<Self> {
+        let body = std::str::from_utf8(body).ok()?;
+        let mut lines = body.split('\n');
+
+        let name = lines.next()?.to_string();
+        let pw_md5 = lines.next()?.to_string();
+        let info: Vec<&str> = lines.next()?.split('|').collect();
+        if info.len() < 5 {
+            return None;
+        }
+
+        Some(Self {
+            name,
+            pw_md5,
+            osu_version: info[0].to_string(),
+            utc_offset: info[1].parse().ok()?,
+            display_city: info[2] == "1",
+            client_hashes: info[3].to_string(),
+            pm_private: info[4] == "1",
+        })
+    }
+
+    /// The tournament client logs in with a version like `b20230326tourney`.
+    pub fn is_tourney_client(&self) -> bool {
+        self.osu_version.ends_with("tourney")
+    }
+}
+
+struct Session {
+    id: i32,
+    name: String,
+    token: String,
+    country: String,
+    utc_offset: i8,
+    /// The account's `Privileges`, changed live by staff.
+    privileges: AtomicU32,
+    /// The server's bot, which nobody sends requests for.
+    bot: bool,
+    /// One of possibly many tournament client sessions alongside the
+    /// player's own, which others don't see.
+    tourney: bool,
+    login_time: Instant,
+    /// Unix time their silence ends at, kept in sync with their account.
+    silence_end: AtomicI64,
+    status: Mutex<Status>,
+    /// The multiplayer match they're in.
+    match_id: Mutex<Option<i32>>,
+    /// Packets waiting for the client's next request.
+    queue: Mutex<Vec<u8>>,
+}
+
+/// An online player; clones share the same session.
+#[derive(Clone)]
+pub struct Player(Arc<Session>);
+
+impl Player {
+    pub fn new(user: &User, login: &LoginData, privileges: Privileges) -> Self {
+        let counter = TOKEN_COUNTER.fetch_add(1, Ordering::SeqCst);
+        let token = md5::compute(format!(
+            "{}:{}:{}",
+            user.id,
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0),
+            counter
+        ));
+
+        Self(Arc::new(Session {
+            id: user.id,
+            name: user.name.clone(),
+            token: format!("{:x}", token),
+            country: user.country.clone(),
+            utc_offset: login.utc_offset,
+            privileges: AtomicU32::new(privileges.bits()),
+            bot: false,
+            tourney: login.is_tourney_client(),
+            login_time: Instant::now(),
+            silence_end: AtomicI64::new(user.silence_end),
+            status: Mutex::new(Status::default()),
+            match_id: Mutex::new(None),
+            queue: Mutex::new(Vec::new()),
+        }))
+    }
+
+    /// The session the bot is kept online with.
+    pub fn bot(user: &User, privileges: Privileges) -> Self {
+        Self(Arc::new(Session {
+            id: user.id,
+            name: user.name.clone(),
+            token: String::new(),
+            country: user.country.clone(),
+            utc_offset: 0,
+            privileges: AtomicU32::new(privileges.bits()),
+            bot: true,
+            tourney: false,
+            login_time: Instant::now(),
+            silence_end: AtomicI64::new(user.silence_end),
+            status: Mutex::new(Status::default()),
+            match_id: Mutex::new(None),
+            queue: Mutex::new(Vec::new()),
+        }))
+    }
+
+    pub fn id(&self) -> i32 {
+        self.0.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    pub fn token(&self) -> &str {
+        &self.0.token
+    }
+
+    pub fn country(&self) -> &str {
+        &self.0.country
+    }
+
+    pub fn set_privileges(&self, privileges: Privileges) {
+        self.0.privileges.store(privileges.bits(), Ordering::SeqCst);
+    }
+
+    /// What the client is told the player may do.
+    pub fn client_privileges(&self) -> ClientPrivileges {
+        self.privileges().client()
+    }
+
+    pub fn is_bot(&self) -> bool {
+        self.0.bot
+    }
+
+    pub fn is_tourney_client(&self) -> bool {
+        self.0.tourney
+    }
+
+    pub fn login_time(&self) -> Instant {
+        self.0.login_time
+    }
+
+    /// Seconds left of their silence, 0 once it's over.
+    pub fn silence_remaining(&self) -> i64 {
+        (self.0.silence_end.load(Ordering::SeqCst) - chrono::Utc::now().timestamp()).max(0)
+    }
+
+    pub fn is_silenced(&self) -> bool {
+        self.silence_remaining() > 0
+    }
+
+    pub fn set_silence_end(&self, silence_end: i64) {
+        self.0.silence_end.store(silence_end, Ordering::SeqCst);
+    }
+
+    /// Whether other players see this session at all.
+    pub fn is_visible(&self) -> bool {
+        !self.is_tourney_client() && !self.is_restricted()
+    }
+
+    pub fn status(&self) -> Status {
+        self.0.status.lock().unwrap().clone()
+    }
+
+    pub fn set_status(&self, status: Status) {
+        *self.0.status.lock().unwrap() = status;
+    }
+
+    pub fn match_id(&self) -> Option<i32> {
+        *self.0.match_id.lock().unwrap()
+    }
+
+    pub fn set_match_id(&self, match_id: Option<i32>) {
+        *self.0.match_id.lock().unwrap() = match_id;
+    }
+
+    /// Queues packets to be sent with the response to the client's next request.
+    pub fn enqueue(&self, data: &[u8]) {
+        // nothing would ever collect the bot's packets
+        if self.is_bot() {
+            return;
+        }
+        self.0.queue.lock().unwrap().extend_from_slice(data);
+    }
+
+    pub fn dequeue(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.0.queue.lock().unwrap())
+    }
+
+    pub fn stats_packet(&self) -> Vec<u8> {
+        let status = self.status();
+        let mode = status.game_mode();
+        let stats = Stats::get(self.id(), mode);
+
+        ChoUserStats {
+            user_id: self.id(),
+            action: status.action,
+            info_text: status.info_text,
+            map_md5: status.map_md5,
+            mods: status.mods as i32,
+            mode: status.mode,
+            map_id: status.map_id,
+            ranked_score: stats.ranked_score,
+            accuracy: stats.acc / 100.0,
+            play_count: stats.plays,
+            total_score: stats.total_score,
+            global_rank: RANKINGS.global_rank(self.id(), mode) as i32,
+            // the client only has room for an i16
+            pp: stats.pp.min(i16::MAX as i32) as i16,
+        }
+        .write()
+    }
+
+    pub fn presence_packet(&self) -> Vec<u8> {
+        let status = self.status();
+
+        ChoUserPresence {
+            user_id: self.id(),
+            name: self.name().to_string(),
+            utc_offset: self.0.utc_offset,
+            country_code: country_id(self.country()),
+            bancho_privileges: (self.client_privileges().bits() as u8) | (status.mode << 5),
+            longitude: 0.0,
+            latitude: 0.0,
+            global_rank: RANKINGS.global_rank(self.id(), status.game_mode()) as i32,
+        }
+        .write()
+    }
+}
+
+impl Privileged for Player {
+    fn privileges(&self) -> Privileges {
+        Privileges::from_bits_truncate(self.0.privileges.load(Ordering::SeqCst))
+    }
+}
+
+#[derive(Default)]
+pub struct PlayerList {
+    players: RwLock<Vec<Player>>,
+}
+
+impl PlayerList {
+    pub fn add(&self, player: Player) {
+        self.players.write().unwrap().push(player);
+    }
+
+    pub fn remove(&self, player: &Player) {
+        self.players
+            .write()
+            .unwrap()
+            .retain(|p| p.token() != player.token());
+    }
+
+    pub fn by_token(&self, token: &str) -> Option<Player> {
+        self.players
+            .read()
+            .unwrap()
+            .iter()
+            // the bot's token is empty, so it can't be used to act as the bot
+            .find(|p| !p.is_bot() && p.token() == token)
+            .cloned()
+    }
+
+    /// The player's own session as others see it, never a tournament client
+    /// and never someone restricted.
+    pub fn by_id(&self, id: i32) -> Option<Player> {
+        self.players
+            .read()
+            .unwrap()
+            .iter()
+            .find(|p| p.id() == id && p.is_visible())
+            .cloned()
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<Player> {
+        let safe_name = make_safe_name(name);
+        self.players
+            .read()
+            .unwrap()
+            .iter()
+            .find(|p| make_safe_name(p.name()) == safe_name && p.is_visible())
+            .cloned()
+    }
+
+    /// Everyone online as others see them, without tournament clients or
+    /// anyone restricted.
+    pub fn all(&self) -> Vec<Player> {
+        self.players
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|p| p.is_visible())
+            .cloned()
+            .collect()
+    }
+
+    /// Every session of the user, tournament clients included.
+    pub fn sessions(&self, id: i32) -> Vec<Player> {
+        self.players
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|p| p.id() == id)
+            .cloned()
+            .collect()
+    }
+
+    pub fn enqueue_all(&self, data: &[u8]) {
+        for player in self.players.read().unwrap().iter() {
+            player.enqueue(data);
+        }
+    }
+
+    /// Queues packets for everyone but the user's own sessions.
+    pub fn enqueue_others(&self, id: i32, data: &[u8]) {
+        for player in self.players.read().unwrap().iter() {
+            if player.id() != id {
+                player.enqueue(data);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_parse_login_data() {
+    let body = b"cmyui\n0123456789abcdef0123456789abcdef\nb20230326|-5|1|hash:adapters:md5:uninstall:disk:|0\n";
+    let login = LoginData::parse(body).unwrap();
+
+    assert_eq!(login.name, "cmyui");
+    assert_eq!(login.osu_version, "b20230326");
+    assert_eq!(login.utc_offset, -5);
+    assert!(login.display_city);
+    assert!(!login.pm_private);
+
+    assert!(!login.is_tourney_client());
+    assert!(LoginData::parse(b"cmyui\n").is_none());
+}
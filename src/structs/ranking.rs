@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::constants::gamemodes::GameMode;
+use crate::constants::privileges::Privileged;
+use crate::storage::{STATS, USERS};
+use crate::structs::stats::Stats;
+
+lazy_static::lazy_static! {
+    /// Global and country rankings of every mode, built from storage on first
+    /// use and kept up to date as stats change.
+    pub static ref RANKINGS: Rankings = Rankings::load();
+}
+
+/// Users sorted by pp, best first, ties going to whoever registered first.
+#[derive(Default)]
+pub struct Ranking {
+    entries: Vec<(i32, i32)>,
+}
+
+impl Ranking {
+    fn position(&self, pp: i32, user_id: i32) -> Result<usize, usize> {
+        self.entries
+            .binary_search_by(|(e_pp, e_id)| pp.cmp(e_pp).then(e_id.cmp(&user_id)))
+    }
+
+    fn insert(&mut self, pp: i32, user_id: i32) {
+        if let Err(i) = self.position(pp, user_id) {
+            self.entries.insert(i, (pp, user_id));
+        }
+    }
+
+    fn remove(&mut self, pp: i32, user_id: i32) {
+        if let Ok(i) = self.position(pp, user_id) {
+            self.entries.remove(i);
+        }
+    }
+
+    /// 1-based rank of a user with the given pp, if they're ranked.
+    pub fn rank(&self, pp: i32, user_id: i32) -> Option<usize> {
+        self.position(pp, user_id).ok().map(|i| i + 1)
+    }
+
+    /// `(user id, pp)` of the users ranked in a page of `limit` users.
+    pub fn page(&self, offset: usize, limit: usize) -> Vec<(i32, i32)> {
+        self.entries
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .map(|(pp, id)| (*id, *pp))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[derive(Default)]
+struct ModeRankings {
+    global: Ranking,
+    countries: HashMap<String, Ranking>,
+    /// Where each ranked user currently sits, to find them again on update.
+    users: HashMap<i32, (i32, String)>,
+}
+
+impl ModeRankings {
+    fn remove(&mut self, user_id: i32) {
+        if let Some((pp, country)) = self.users.remove(&user_id) {
+            self.global.remove(pp, user_id);
+            if let Some(ranking) = self.countries.get_mut(&country) {
+                ranking.remove(pp, user_id);
+            }
+        }
+    }
+
+    fn update(&mut self, user_id: i32, pp: i32, country: &str) {
+        self.remove(user_id);
+
+        // users without any pp aren't ranked
+        if pp <= 0 {
+            return;
+        }
+
+        let country = country.to_lowercase();
+        self.global.insert(pp, user_id);
+        self.countries
+            .entry(country.clone())
+            .or_default()
+            .insert(pp, user_id);
+        self.users.insert(user_id, (pp, country));
+    }
+}
+
+#[derive(Default)]
+pub struct Rankings {
+    modes: RwLock<HashMap<GameMode, ModeRankings>>,
+}
+
+impl Rankings {
+    pub fn load() -> Self {
+        let rankings = Rankings::default();
+        let countries: HashMap<i32, String> = USERS
+            .find(|u| !u.is_restricted())
+            .into_iter()
+            .map(|u| (u.id, u.country))
+            .collect();
+
+        for stats in STATS.all() {
+            if let Some(country) = countries.get(&stats.user_id) {
+                rankings.update(&stats, country);
+            }
+        }
+        rankings
+    }
+
+    /// Moves a user to where their new stats place them.
+    pub fn update(&self, stats: &Stats, country: &str) {
+        self.modes
+            .write()
+            .unwrap()
+            .entry(stats.mode)
+            .or_default()
+            .update(stats.user_id, stats.pp, country);
+    }
+
+    /// Takes a user off every ranking, e.g. when they're restricted.
+    pub fn remove(&self, user_id: i32) {
+        for rankings in self.modes.write().unwrap().values_mut() {
+            rankings.remove(user_id);
+        }
+    }
+
+    /// The user's global rank in a mode, 0 when unranked as the client expects.
+    pub fn global_rank(&self, user_id: i32, mode: GameMode) -> usize {
+        let modes = self.modes.read().unwrap();
+        modes
+            .get(&mode)
+            .and_then(|r| {
+                let (pp, _) = r.users.get(&user_id)?;
+                r.global.rank(*pp, user_id)
+            })
+            .unwrap_or(0)
+    }
+
+    /// The user's rank among players from their country, 0 when unranked.
+    pub fn country_rank(&self, user_id: i32, mode: GameMode) -> usize {
+        let modes = self.modes.read().unwrap();
+        modes
+            .get(&mode)
+            .and_then(|r| {
+                let (pp, country) = r.users.get(&user_id)?;
+                r.countries.get(country)?.rank(*pp, user_id)
+            })
+            .unwrap_or(0)
+    }
+
+    /// A page of `(user id, pp)`, of a country's ranking if a lowercase
+    /// country code is given.
+    pub fn page(
+        &self,
+        mode: GameMode,
+        country: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> Vec<(i32, i32)> {
+        let modes = self.modes.read().unwrap();
+        let rankings = match modes.get(&mode) {
+            Some(rankings) => rankings,
+            None => return Vec::new(),
+        };
+
+        match country {
+            Some(country) => rankings
+                .countries
+                .get(country)
+                .map(|r| r.page(offset, limit))
+                .unwrap_or_default(),
+            None => rankings.global.page(offset, limit),
+        }
+    }
+}
+
+#[test]
+fn test_rankings() {
+    let rankings = Rankings::default();
+    let stats = |user_id, pp| {
+        let mut stats = Stats::new(user_id, GameMode::VanillaOsu);
+        stats.pp = pp;
+        stats
+    };
+
+    rankings.update(&stats(1, 100), "us");
+    rankings.update(&stats(2, 300), "jp");
+    rankings.update(&stats(3, 200), "us");
+    rankings.update(&stats(4, 0), "us");
+
+    assert_eq!(rankings.global_rank(2, GameMode::VanillaOsu), 1);
+    assert_eq!(rankings.global_rank(1, GameMode::VanillaOsu), 3);
+    assert_eq!(rankings.country_rank(1, GameMode::VanillaOsu), 2);
+    assert_eq!(rankings.global_rank(4, GameMode::VanillaOsu), 0);
+    assert_eq!(rankings.global_rank(1, GameMode::VanillaTaiko), 0);
+
+    rankings.update(&stats(1, 400), "us");
+    assert_eq!(rankings.global_rank(1, GameMode::VanillaOsu), 1);
+    assert_eq!(
+        rankings.page(GameMode::VanillaOsu, None, 1, 2),
+        vec![(2, 300), (3, 200)]
+    );
+    assert_eq!(
+        rankings.page(GameMode::VanillaOsu, Some("us"), 0, 10),
+        vec![(1, 400), (3, 200)]
+    );
+
+    rankings.remove(2);
+    assert_eq!(rankings.global_rank(3, GameMode::VanillaOsu), 2);
+}
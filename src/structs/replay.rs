@@ -0,0 +1,93 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::storage::data_path;
+use crate::structs::score::Score;
+use crate::structs::user::User;
+use crate::utils::osu::replay::{Replay, ReplayFrame};
+
+/// Ticks (100ns) between 0001-01-01 and the unix epoch, .osr timestamps are
+/// .NET DateTime ticks.
+const UNIX_EPOCH_TICKS: i64 = 621_355_968_000_000_000;
+
+/// Version written into exported replays when the client's wasn't recorded.
+const FALLBACK_CLIENT_VERSION: i32 = 20200207;
+
+fn replay_path(score_id: i64) -> PathBuf {
+    data_path()
+        .join("replays")
+        .join(format!("{}.osr", score_id))
+}
+
+/// Stores the replay the client uploaded with a score. This is only the
+/// compressed frame data, the header is rebuilt from the score on export.
+pub fn save(score_id: i64, data: &[u8]) -> io::Result<()> {
+    let path = replay_path(score_id);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, data)
+}
+
+/// Loads the raw frame data stored for a score, if any.
+pub fn load(score_id: i64) -> Option<Vec<u8>> {
+    fs::read(replay_path(score_id)).ok()
+}
+
+/// Builds a complete .osr file for a score so it can be watched outside the
+/// client it was submitted from.
+pub fn export_osr(score: &Score, user: &User) -> Option<Vec<u8>> {
+    let frames = ReplayFrame::parse_all(&load(score.id)?).ok()?;
+
+    let replay_md5 = format!(
+        "{:x}",
+        md5::compute(format!(
+            "{}p{}o{}o{}t{}a{}r{}e{}y{}o{}u{}{}{}",
+            score.n100 + score.n300,
+            score.n50,
+            score.ngeki,
+            score.nkatu,
+            score.nmiss,
+            score.map_md5,
+            score.max_combo,
+            if score.perfect { "True" } else { "False" },
+            user.name,
+            score.score,
+            0,
+            score.mods,
+            "True",
+        ))
+    );
+
+    let version = match score.client_version {
+        0 => FALLBACK_CLIENT_VERSION,
+        version => version,
+    };
+
+    let replay = Replay {
+        mode: score.mode.as_vanilla(),
+        version,
+        beatmap_md5: score.map_md5.clone(),
+        player_name: user.name.clone(),
+        replay_md5,
+        n300: score.n300 as i16,
+        n100: score.n100 as i16,
+        n50: score.n50 as i16,
+        ngeki: score.ngeki as i16,
+        nkatu: score.nkatu as i16,
+        nmiss: score.nmiss as i16,
+        score: score.score as i32,
+        max_combo: score.max_combo as i16,
+        perfect: score.perfect,
+        mods: score.mods,
+        // the life bar graph isn't part of what the client uploads
+        life_bar: Vec::new(),
+        timestamp: score.play_time * 10_000_000 + UNIX_EPOCH_TICKS,
+        frames,
+        score_id: score.id,
+        target_practice_accuracy: None,
+    };
+
+    Some(replay.write())
+}
@@ -0,0 +1,254 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::constants::gamemodes::GameMode;
+use crate::constants::grades::Grade;
+use crate::constants::mods::Mods;
+use crate::storage::{Row, BEATMAPS, SCORES};
+use crate::utils::osu::beatmap::BeatmapFile;
+use crate::utils::osu::performance::{self, ScoreState};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum SubmissionStatus {
+    Failed,
+    Submitted,
+    /// The user's best score on the map in its mode.
+    Best,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Score {
+    pub id: i64,
+    pub map_md5: String,
+    pub user_id: i32,
+
+    pub score: i64,
+    pub pp: f32,
+    pub acc: f32,
+    pub max_combo: i32,
+    pub mods: u32,
+
+    pub n300: i32,
+    pub n100: i32,
+    pub n50: i32,
+    pub nmiss: i32,
+    pub ngeki: i32,
+    pub nkatu: i32,
+
+    pub grade: Grade,
+    pub status: SubmissionStatus,
+    pub mode: GameMode,
+    pub passed: bool,
+    pub perfect: bool,
+
+    /// Unix timestamp of the submission.
+    pub play_time: i64,
+    /// Milliseconds into the map the play ended (or failed) at.
+    pub time_elapsed: i32,
+    pub client_flags: i32,
+    pub online_checksum: String,
+    /// The client's version, e.g. 20230326.
+    #[serde(default)]
+    pub client_version: i32,
+}
+
+impl Row for Score {
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = id;
+    }
+}
+
+/// A score as parsed from the decrypted submission, along with the fields
+/// that only matter while validating it.
+pub struct SubmittedScore {
+    pub score: Score,
+    pub username: String,
+    /// The `yyMMddHHmmss` client time, kept verbatim for the checksum.
+    pub client_time: String,
+    pub osu_version: String,
+}
+
+impl Score {
+    /// Parses the colon separated score data the client submits:
+    /// `md5:name:checksum:300:100:50:geki:katu:miss:score:combo:perfect:grade:mods:passed:mode:time:version`.
+    pub fn from_submission(data: &[String]) -> Result<SubmittedScore, String> {
+        if data.len() < 18 {
+            return Err(format!("expected 18 fields, got {}", data.len()));
+        }
+
+        let int = |i: usize| -> Result<i64, String> {
+            data[i]
+                .parse::<i64>()
+                .map_err(|_| format!("field {} ({:?}) is not an integer", i, data[i]))
+        };
+
+        let mods = Mods::from_i32(int(13)? as i32);
+        let mode_vn = int(15)?;
+        if !(0..=3).contains(&mode_vn) {
+            return Err(format!("invalid mode {}", mode_vn));
+        }
+
+        let score = Score {
+            id: 0,
+            map_md5: data[0].clone(),
+            user_id: 0,
+            score: int(9)?,
+            pp: 0.0,
+            acc: 0.0,
+            max_combo: int(10)? as i32,
+            mods: mods.bits(),
+            n300: int(3)? as i32,
+            n100: int(4)? as i32,
+            n50: int(5)? as i32,
+            ngeki: int(6)? as i32,
+            nkatu: int(7)? as i32,
+            nmiss: int(8)? as i32,
+            grade: data[12].parse()?,
+            status: SubmissionStatus::Submitted,
+            mode: GameMode::from_params(mode_vn as u8, mods),
+            passed: data[14] == "True",
+            perfect: data[11] == "True",
+            play_time: chrono::Utc::now().timestamp(),
+            time_elapsed: 0,
+            // the client pads the version with a space per anticheat flag
+            client_flags: (data[17].len() - data[17].trim_end().len()) as i32 & !4,
+            online_checksum: data[2].clone(),
+            client_version: 0,
+        };
+
+        let mut submitted = SubmittedScore {
+            score,
+            username: data[1].trim_end().to_string(),
+            client_time: data[16].clone(),
+            osu_version: data[17].trim_end().to_string(),
+        };
+        submitted.score.acc = submitted.score.calculate_accuracy();
+        submitted.score.client_version = submitted
+            .osu_version
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect::<String>()
+            .parse()
+            .unwrap_or(0);
+
+        Ok(submitted)
+    }
+
+    pub fn mods(&self) -> Mods {
+        Mods::from_bits_truncate(self.mods)
+    }
+
+    pub fn calculate_accuracy(&self) -> f32 {
+        ScoreState::from_score(self).accuracy(self.mode)
+    }
+
+    /// Whether this score ranks above `other` on a leaderboard.
+    pub fn beats(&self, other: &Score) -> bool {
+        if self.mode.sorts_by_pp() {
+            self.pp > other.pp
+        } else {
+            self.score > other.score
+        }
+    }
+
+    /// Calculates the pp of this score on the given map.
+    pub fn calculate_pp(&self, map: &BeatmapFile) -> f32 {
+        let state = ScoreState::from_score(self);
+        performance::calculate(map, self.mode, self.mods(), &state).pp as f32
+    }
+
+    /// The user's best score on a map in a mode, if they have one.
+    pub fn best_for(user_id: i32, map_md5: &str, mode: GameMode) -> Option<Score> {
+        SCORES.find_one(|s| {
+            s.user_id == user_id
+                && s.mode == mode
+                && s.status == SubmissionStatus::Best
+                && s.map_md5 == map_md5
+        })
+    }
+
+    /// Position of this score on its map's leaderboard.
+    pub fn leaderboard_rank(&self) -> usize {
+        SCORES
+            .find(|s| {
+                s.map_md5 == self.map_md5
+                    && s.mode == self.mode
+                    && s.status == SubmissionStatus::Best
+                    && s.beats(self)
+            })
+            .len()
+            + 1
+    }
+}
+
+/// Recalculates the pp of every score on a map we have the .osu file of and
+/// re-picks each user's best score per map and mode, for after the pp
+/// formulas change. Returns how many scores were recalculated.
+pub fn recalculate_all() -> std::io::Result<usize> {
+    let mut recalculated = 0;
+
+    for map in BEATMAPS.all() {
+        let file = match map.osu_file() {
+            Some(file) => file,
+            None => continue,
+        };
+
+        let mut bests: HashMap<(i32, GameMode), Score> = HashMap::new();
+        for mut score in SCORES.find(|s| s.map_md5 == map.md5) {
+            score.pp = score.calculate_pp(&file);
+            recalculated += 1;
+
+            if score.passed {
+                let key = (score.user_id, score.mode);
+                if bests.get(&key).is_none_or(|b| score.beats(b)) {
+                    bests.insert(key, score.clone());
+                }
+                score.status = SubmissionStatus::Submitted;
+            }
+            SCORES.insert(score)?;
+        }
+
+        for best in bests.into_values() {
+            SCORES.update(best.id, |s| s.status = SubmissionStatus::Best)?;
+        }
+    }
+
+    Ok(recalculated)
+}
+
+impl SubmittedScore {
+    /// The checksum the client computes over its own score, which also covers
+    /// the client hash and storyboard md5 so those can't be tampered with.
+    pub fn online_checksum(&self, client_hash: &str, storyboard_md5: &str) -> String {
+        let s = &self.score;
+        let py_bool = |b: bool| if b { "True" } else { "False" };
+
+        let data = format!(
+            "chickenmcnuggets{}o15{}{}smustard{}{}uu{}{}{}{}{}{}{}Q{}{}{}{}{}{}",
+            s.n100 + s.n300,
+            s.n50,
+            s.ngeki,
+            s.nkatu,
+            s.nmiss,
+            s.map_md5,
+            s.max_combo,
+            py_bool(s.perfect),
+            self.username,
+            s.score,
+            s.grade.as_str(),
+            s.mods,
+            py_bool(s.passed),
+            s.mode.as_vanilla(),
+            self.osu_version,
+            self.client_time,
+            client_hash,
+            storyboard_md5,
+        );
+
+        format!("{:x}", md5::compute(data))
+    }
+}
@@ -0,0 +1,284 @@
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::config::{self, MenuIcon, SeasonalBackground};
+use crate::storage::{Row, SEASONAL};
+use crate::structs::player::PLAYERS;
+use crate::utils::osu::packets::ChoMainMenuIcon;
+
+/// How often we check whether the main menu icon has changed, either through
+/// a config reload or because its dates came around.
+const ICON_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+lazy_static::lazy_static! {
+    /// The icon everyone online was last sent.
+    static ref CURRENT_ICON: Mutex<Option<MenuIcon>> = Mutex::new(menu_icon());
+}
+
+/// A background or menu icon staff scheduled in game, shown alongside the
+/// config's; staff icons win over the config's while they're up.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledArt {
+    pub id: i64,
+    pub image_url: String,
+    /// Where clicking a menu icon goes; backgrounds have none.
+    pub click_url: Option<String>,
+    pub from: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+    pub added_by: i32,
+    /// Taken down by staff, tables don't forget rows.
+    #[serde(default)]
+    pub removed: bool,
+}
+
+impl Row for ScheduledArt {
+    fn id(&self) -> i64 {
+        self.id
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = id;
+    }
+}
+
+impl ScheduledArt {
+    pub fn background(url: &str, from: Option<NaiveDate>, until: Option<NaiveDate>) -> Self {
+        Self {
+            id: 0,
+            image_url: url.to_string(),
+            click_url: None,
+            from,
+            until,
+            added_by: 0,
+            removed: false,
+        }
+    }
+
+    pub fn menu_icon(
+        image_url: &str,
+        click_url: &str,
+        from: Option<NaiveDate>,
+        until: Option<NaiveDate>,
+    ) -> Self {
+        Self {
+            click_url: Some(click_url.to_string()),
+            ..Self::background(image_url, from, until)
+        }
+    }
+
+    pub fn as_menu_icon(&self) -> Option<MenuIcon> {
+        Some(MenuIcon {
+            image_url: self.image_url.clone(),
+            click_url: self.click_url.clone()?,
+            from: self.from,
+            until: self.until,
+        })
+    }
+
+    pub fn as_background(&self) -> Option<SeasonalBackground> {
+        match self.click_url {
+            Some(_) => None,
+            None => Some(SeasonalBackground {
+                url: self.image_url.clone(),
+                from: self.from,
+                until: self.until,
+            }),
+        }
+    }
+
+    /// What's wrong with it, if anything.
+    pub fn problem(&self) -> Option<String> {
+        let mut urls = std::iter::once(&self.image_url).chain(&self.click_url);
+        if let Some(url) = urls.find(|url| !config::is_url(url)) {
+            return Some(format!("{:?} isn't an http(s) url", url));
+        }
+        if config::ends_before_start(self.from, self.until) {
+            return Some("it ends before it starts".to_string());
+        }
+        None
+    }
+}
+
+/// Everything staff have scheduled and not taken down, oldest first.
+pub fn scheduled() -> Vec<ScheduledArt> {
+    SEASONAL.find(|a| !a.removed)
+}
+
+/// Schedules a background or menu icon, pushing the icon out if it's up now.
+pub fn schedule(art: ScheduledArt) -> io::Result<ScheduledArt> {
+    let art = SEASONAL.insert(art)?;
+    refresh_menu_icon();
+    Ok(art)
+}
+
+/// Takes down something staff scheduled, `None` if there's no such thing.
+pub fn unschedule(id: i64) -> io::Result<Option<ScheduledArt>> {
+    if SEASONAL.get(id).is_none_or(|a| a.removed) {
+        return Ok(None);
+    }
+    let art = SEASONAL.update(id, |a| a.removed = true)?;
+    refresh_menu_icon();
+    Ok(art)
+}
+
+fn today() -> NaiveDate {
+    Utc::now().date_naive()
+}
+
+fn active_backgrounds(
+    scheduled: &[ScheduledArt],
+    config: Vec<String>,
+    today: NaiveDate,
+) -> Vec<String> {
+    scheduled
+        .iter()
+        .filter_map(ScheduledArt::as_background)
+        .filter(|b| b.is_active(today))
+        .map(|b| b.url)
+        .chain(config)
+        .collect()
+}
+
+/// The newest icon staff scheduled that's up today, or else the config's.
+fn active_menu_icon(
+    scheduled: &[ScheduledArt],
+    config: Option<MenuIcon>,
+    today: NaiveDate,
+) -> Option<MenuIcon> {
+    scheduled
+        .iter()
+        .rev()
+        .filter_map(ScheduledArt::as_menu_icon)
+        .find(|i| i.is_active(today))
+        .or(config)
+}
+
+/// The backgrounds the main menu should cycle through today.
+pub fn backgrounds() -> Vec<String> {
+    let today = today();
+    active_backgrounds(
+        &scheduled(),
+        config::get().seasonal_backgrounds(today),
+        today,
+    )
+}
+
+/// Today's main menu icon.
+pub fn menu_icon() -> Option<MenuIcon> {
+    let today = today();
+    active_menu_icon(&scheduled(), config::get().menu_icon(today).cloned(), today)
+}
+
+/// Tells the client which icon to show, an empty one hiding it.
+pub fn menu_icon_packet(icon: Option<&MenuIcon>) -> Vec<u8> {
+    match icon {
+        Some(icon) => ChoMainMenuIcon::new(&icon.image_url, &icon.click_url).write(),
+        None => ChoMainMenuIcon::new("", "").write(),
+    }
+}
+
+/// Pushes the main menu icon to everyone online if it's changed since they
+/// were last sent it.
+pub fn refresh_menu_icon() {
+    let icon = menu_icon();
+    let mut current = CURRENT_ICON.lock().unwrap();
+    if *current == icon {
+        return;
+    }
+
+    match &icon {
+        Some(icon) => println!("[seasonal] menu icon is now {}", icon.image_url),
+        None => println!("[seasonal] menu icon removed"),
+    }
+    PLAYERS.enqueue_all(&menu_icon_packet(icon.as_ref()));
+    *current = icon;
+}
+
+/// Keeps the main menu icon up to date as the config and dates change.
+pub fn watch() {
+    lazy_static::initialize(&CURRENT_ICON);
+
+    actix_web::rt::spawn(async {
+        let mut interval = actix_web::rt::time::interval(ICON_CHECK_INTERVAL);
+
+        loop {
+            interval.tick().await;
+            refresh_menu_icon();
+        }
+    });
+}
+
+#[test]
+fn test_scheduled_art() {
+    let date = |month, day| NaiveDate::from_ymd_opt(2026, month, day).unwrap();
+    let config_icon = MenuIcon {
+        image_url: "https://example.com/config.png".to_string(),
+        click_url: "https://example.com".to_string(),
+        from: None,
+        until: None,
+    };
+    let scheduled = vec![
+        ScheduledArt::menu_icon(
+            "https://example.com/old.png",
+            "https://example.com",
+            None,
+            None,
+        ),
+        ScheduledArt::menu_icon(
+            "https://example.com/winter.png",
+            "https://example.com",
+            Some(date(12, 1)),
+            Some(date(12, 31)),
+        ),
+        ScheduledArt::background(
+            "https://example.com/winter.jpg",
+            Some(date(12, 1)),
+            Some(date(12, 31)),
+        ),
+    ];
+
+    // the newest staff icon that's up wins over older ones and the config's
+    let icon = |today| active_menu_icon(&scheduled, Some(config_icon.clone()), today);
+    assert_eq!(
+        icon(date(12, 24)).unwrap().image_url,
+        "https://example.com/winter.png"
+    );
+    assert_eq!(
+        icon(date(11, 30)).unwrap().image_url,
+        "https://example.com/old.png"
+    );
+    assert_eq!(
+        active_menu_icon(&[], Some(config_icon.clone()), date(12, 24)),
+        Some(config_icon)
+    );
+
+    let config = vec!["https://example.com/config.jpg".to_string()];
+    assert_eq!(
+        active_backgrounds(&scheduled, config.clone(), date(12, 1)),
+        vec![
+            "https://example.com/winter.jpg",
+            "https://example.com/config.jpg"
+        ]
+    );
+    assert_eq!(
+        active_backgrounds(&scheduled, config.clone(), date(11, 30)),
+        config
+    );
+
+    assert_eq!(scheduled[2].problem(), None);
+    assert!(
+        ScheduledArt::background("ftp://example.com/a.jpg", None, None)
+            .problem()
+            .is_some()
+    );
+    assert!(ScheduledArt::background(
+        "https://example.com/a.jpg",
+        Some(date(12, 2)),
+        Some(date(12, 1))
+    )
+    .problem()
+    .is_some());
+}
@@ -0,0 +1,230 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::constants::gamemodes::GameMode;
+use crate::constants::grades::Grade;
+use crate::storage::{Row, BEATMAPS, SCORES, STATS};
+use crate::structs::score::{Score, SubmissionStatus};
+
+/// Only this many of a user's best scores count towards their pp and accuracy.
+const TOP_SCORES: usize = 100;
+
+/// A user's totals in a single mode.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Stats {
+    pub user_id: i32,
+    pub mode: GameMode,
+
+    pub total_score: i64,
+    pub ranked_score: i64,
+    pub pp: i32,
+    pub acc: f32,
+    pub plays: i32,
+    pub playtime: i32,
+    pub max_combo: i32,
+    pub total_hits: i64,
+    pub replay_views: i32,
+
+    #[serde(default)]
+    pub xh_count: i32,
+    #[serde(default)]
+    pub x_count: i32,
+    #[serde(default)]
+    pub sh_count: i32,
+    #[serde(default)]
+    pub s_count: i32,
+    #[serde(default)]
+    pub a_count: i32,
+}
+
+impl Row for Stats {
+    fn id(&self) -> i64 {
+        Stats::key(self.user_id, self.mode)
+    }
+
+    fn set_id(&mut self, _id: i64) {}
+}
+
+impl Stats {
+    pub fn new(user_id: i32, mode: GameMode) -> Self {
+        Self {
+            user_id,
+            mode,
+            total_score: 0,
+            ranked_score: 0,
+            pp: 0,
+            acc: 0.0,
+            plays: 0,
+            playtime: 0,
+            max_combo: 0,
+            total_hits: 0,
+            replay_views: 0,
+            xh_count: 0,
+            x_count: 0,
+            sh_count: 0,
+            s_count: 0,
+            a_count: 0,
+        }
+    }
+
+    /// Stats rows are keyed by user and mode together.
+    pub fn key(user_id: i32, mode: GameMode) -> i64 {
+        ((user_id as i64) << 4) | mode as i64
+    }
+
+    /// Fetches a user's stats for a mode, empty if they've never played it.
+    pub fn get(user_id: i32, mode: GameMode) -> Self {
+        STATS
+            .get(Stats::key(user_id, mode))
+            .unwrap_or_else(|| Stats::new(user_id, mode))
+    }
+
+    /// Rebuilds a user's stats in a mode from their scores: totals come from
+    /// every play, while pp, accuracy and ranked score only count the best
+    /// score on each ranked or approved map.
+    pub fn recalculate(user_id: i32, mode: GameMode) -> Self {
+        let scores = SCORES.find(|s| s.user_id == user_id && s.mode == mode);
+        let prev = Stats::get(user_id, mode);
+
+        let mut stats = Stats::new(user_id, mode);
+        stats.replay_views = prev.replay_views;
+
+        for score in &scores {
+            stats.plays += 1;
+            stats.total_score += score.score;
+            stats.playtime += score.time_elapsed / 1000;
+            stats.total_hits += (score.n300 + score.n100 + score.n50) as i64;
+            if mode.as_vanilla() == 3 {
+                stats.total_hits += (score.ngeki + score.nkatu) as i64;
+            }
+        }
+
+        // only the maps of their best scores are looked at, not every map
+        let best_md5s: HashSet<&str> = scores
+            .iter()
+            .filter(|s| s.status == SubmissionStatus::Best)
+            .map(|s| s.map_md5.as_str())
+            .collect();
+        let ranked_maps: HashSet<String> = BEATMAPS
+            .find(|m| best_md5s.contains(m.md5.as_str()) && m.awards_ranked_pp())
+            .into_iter()
+            .map(|m| m.md5)
+            .collect();
+
+        let mut best: Vec<&Score> = scores
+            .iter()
+            .filter(|s| s.status == SubmissionStatus::Best && ranked_maps.contains(&s.map_md5))
+            .collect();
+        best.sort_by(|a, b| b.pp.total_cmp(&a.pp));
+
+        for score in &best {
+            stats.ranked_score += score.score;
+            stats.max_combo = stats.max_combo.max(score.max_combo);
+
+            match score.grade {
+                Grade::XH => stats.xh_count += 1,
+                Grade::X => stats.x_count += 1,
+                Grade::SH => stats.sh_count += 1,
+                Grade::S => stats.s_count += 1,
+                Grade::A => stats.a_count += 1,
+                _ => {}
+            }
+        }
+
+        let top: Vec<(f32, f32)> = best
+            .iter()
+            .take(TOP_SCORES)
+            .map(|s| (s.pp, s.acc))
+            .collect();
+        stats.pp = weighted_pp(
+            &top.iter().map(|(pp, _)| *pp).collect::<Vec<_>>(),
+            best.len(),
+        )
+        .round() as i32;
+        stats.acc = weighted_acc(&top.iter().map(|(_, acc)| *acc).collect::<Vec<_>>());
+
+        stats
+    }
+
+    /// Recalculates every user's stats in every mode they've played, e.g.
+    /// after scores' pp have been recalculated.
+    pub fn recalculate_all() -> std::io::Result<()> {
+        let mut keys: Vec<(i32, GameMode)> = SCORES
+            .all()
+            .into_iter()
+            .map(|s| (s.user_id, s.mode))
+            .collect();
+        keys.sort();
+        keys.dedup();
+
+        for (user_id, mode) in keys {
+            STATS.insert(Stats::recalculate(user_id, mode))?;
+        }
+        Ok(())
+    }
+
+    /// The level shown in game, with progress to the next level as the fraction.
+    pub fn level(&self) -> f32 {
+        let mut level = 1;
+        while level < 120 && required_score(level + 1) <= self.total_score as f64 {
+            level += 1;
+        }
+
+        let (current, next) = (required_score(level), required_score(level + 1));
+        let progress = (self.total_score as f64 - current) / (next - current);
+        level as f32 + progress.clamp(0.0, 1.0) as f32
+    }
+}
+
+/// Total score needed to reach a level.
+fn required_score(level: i32) -> f64 {
+    let n = level as f64;
+    if level <= 100 {
+        5000.0 / 3.0 * (4.0 * n.powi(3) - 3.0 * n.powi(2) - n) + 1.25 * 1.8_f64.powf(n - 60.0)
+    } else {
+        26_931_190_827.0 + 99_999_999_999.0 * (n - 100.0)
+    }
+}
+
+/// Sums pp sorted best first, each worth 95% of the one before, plus the
+/// bonus for the total number of ranked scores set.
+fn weighted_pp(pps: &[f32], score_count: usize) -> f64 {
+    let weighted: f64 = pps
+        .iter()
+        .enumerate()
+        .map(|(i, pp)| *pp as f64 * 0.95_f64.powi(i as i32))
+        .sum();
+    let bonus = 416.6667 * (1.0 - 0.9994_f64.powi(score_count as i32));
+
+    weighted + bonus
+}
+
+/// Accuracy weighted the same way as pp, so better scores count for more.
+fn weighted_acc(accs: &[f32]) -> f32 {
+    if accs.is_empty() {
+        return 0.0;
+    }
+
+    let (sum, weights) = accs
+        .iter()
+        .enumerate()
+        .fold((0.0, 0.0), |(sum, weights), (i, acc)| {
+            let weight = 0.95_f64.powi(i as i32);
+            (sum + *acc as f64 * weight, weights + weight)
+        });
+    (sum / weights) as f32
+}
+
+#[test]
+fn test_weighted_stats() {
+    let pp = weighted_pp(&[100.0, 100.0], 2);
+    assert!((pp - (195.0 + 416.6667 * (1.0 - 0.9994_f64.powi(2)))).abs() < 1e-6);
+    assert_eq!(weighted_pp(&[], 0), 0.0);
+
+    assert!((weighted_acc(&[100.0, 90.0]) - 95.128_2).abs() < 1e-3);
+
+    let mut stats = Stats::new(1, GameMode::VanillaOsu);
+    assert_eq!(stats.level(), 1.0);
+    stats.total_score = required_score(50) as i64 + 1;
+    assert_eq!(stats.level().floor(), 50.0);
+}
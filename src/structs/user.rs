@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::constants::privileges::{Privileged, Privileges};
+use crate::storage::{Row, USERS};
+
+lazy_static::lazy_static! {
+    /// bcrypt hash -> password md5 for logins we've already verified, since
+    /// bcrypt is far too slow to run on every request the client makes.
+    static ref BCRYPT_CACHE: RwLock<HashMap<String, String>> = RwLock::new(HashMap::new());
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct User {
+    pub id: i32,
+    pub name: String,
+    pub safe_name: String,
+    /// bcrypt of the md5 of the password, the client never sends it in plain.
+    pub pw_bcrypt: String,
+    pub country: String,
+    pub creation_time: i64,
+    #[serde(default)]
+    pub friends: Vec<i32>,
+    /// When their silence ends, 0 if they've never been silenced.
+    #[serde(default)]
+    pub silence_end: i64,
+    #[serde(default)]
+    pub silence_reason: String,
+    #[serde(default = "Privileges::default_for_account")]
+    pub privileges: Privileges,
+    /// Can't log in at all.
+    #[serde(default)]
+    pub banned: bool,
+}
+
+impl Row for User {
+    fn id(&self) -> i64 {
+        self.id as i64
+    }
+
+    fn set_id(&mut self, id: i64) {
+        self.id = id as i32;
+    }
+}
+
+impl Privileged for User {
+    fn privileges(&self) -> Privileges {
+        self.privileges
+    }
+}
+
+pub fn make_safe_name(name: &str) -> String {
+    name.trim().to_lowercase().replace(' ', "_")
+}
+
+impl User {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let safe_name = make_safe_name(name);
+        USERS.find_one(|u| u.safe_name == safe_name)
+    }
+
+    /// Seconds left of their silence, 0 once it's over.
+    pub fn silence_remaining(&self) -> i64 {
+        (self.silence_end - chrono::Utc::now().timestamp()).max(0)
+    }
+
+    pub fn check_password(&self, pw_md5: &str) -> bool {
+        if let Some(cached) = BCRYPT_CACHE.read().unwrap().get(&self.pw_bcrypt) {
+            return cached == pw_md5;
+        }
+
+        if !bcrypt::verify(pw_md5, &self.pw_bcrypt).unwrap_or(false) {
+            return false;
+        }
+
+        BCRYPT_CACHE
+            .write()
+            .unwrap()
+            .insert(self.pw_bcrypt.clone(), pw_md5.to_string());
+        true
+    }
+}
+
+/// Looks up a user by name and checks the password md5 the client sent.
+/// Banned accounts don't authenticate at all.
+pub fn authenticate(name: &str, pw_md5: &str) -> Option<User> {
+    User::from_name(name).filter(|u| !u.banned && u.check_password(pw_md5))
+}
@@ -1,4 +1,10 @@
 pub mod osu {
+    #[path = "beatmap.rs"]
+    pub mod beatmap;
+
+    #[path = "crypto.rs"]
+    pub mod crypto;
+
     #[path = "packets.rs"]
     pub mod packets;
 
@@ -7,4 +13,10 @@ pub mod osu {
 
     #[path = "packet_writer.rs"]
     pub mod packet_writer;
+
+    #[path = "performance/mod.rs"]
+    pub mod performance;
+
+    #[path = "replay.rs"]
+    pub mod replay;
 }
\ No newline at end of file
@@ -0,0 +1,681 @@
+use std::str::FromStr;
+
+/// Slider velocity multipliers of inherited timing points are clamped to this
+/// range by the client.
+const SV_RANGE: (f64, f64) = (0.1, 10.0);
+
+#[derive(Debug)]
+pub struct BeatmapError {
+    /// 1-based line of the .osu file the error was found on.
+    pub line: usize,
+    pub reason: String,
+}
+
+impl std::fmt::Display for BeatmapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for BeatmapError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct General {
+    pub audio_filename: String,
+    pub audio_lead_in: i32,
+    pub preview_time: i32,
+    pub stack_leniency: f64,
+    pub mode: u8,
+}
+
+impl Default for General {
+    fn default() -> Self {
+        Self {
+            audio_filename: String::new(),
+            audio_lead_in: 0,
+            preview_time: -1,
+            stack_leniency: 0.7,
+            mode: 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metadata {
+    pub title: String,
+    pub title_unicode: String,
+    pub artist: String,
+    pub artist_unicode: String,
+    pub creator: String,
+    pub version: String,
+    pub source: String,
+    pub tags: String,
+    pub beatmap_id: i32,
+    pub beatmap_set_id: i32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difficulty {
+    pub hp: f64,
+    pub cs: f64,
+    pub od: f64,
+    pub ar: f64,
+    pub slider_multiplier: f64,
+    pub slider_tick_rate: f64,
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Self {
+            hp: 5.0,
+            cs: 5.0,
+            od: 5.0,
+            ar: 5.0,
+            slider_multiplier: 1.4,
+            slider_tick_rate: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimingPoint {
+    pub time: f64,
+    /// Milliseconds per beat for uninherited points, a negative inverse slider
+    /// velocity percentage for inherited ones.
+    pub beat_length: f64,
+    pub meter: i32,
+    pub uninherited: bool,
+    pub kiai: bool,
+}
+
+impl TimingPoint {
+    /// Slider velocity multiplier of an inherited point.
+    pub fn sv_multiplier(&self) -> f64 {
+        if self.uninherited || self.beat_length >= 0.0 {
+            1.0
+        } else {
+            (-100.0 / self.beat_length).clamp(SV_RANGE.0, SV_RANGE.1)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CurveType {
+    Bezier,
+    Catmull,
+    Linear,
+    PerfectCircle,
+}
+
+impl FromStr for CurveType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "B" => Ok(CurveType::Bezier),
+            "C" => Ok(CurveType::Catmull),
+            "L" => Ok(CurveType::Linear),
+            "P" => Ok(CurveType::PerfectCircle),
+            _ => Err(format!("unknown curve type {:?}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Slider {
+    pub curve_type: CurveType,
+    /// Control points after the slider head.
+    pub control_points: Vec<(f32, f32)>,
+    /// Number of times the slider is traversed, 1 for no repeats.
+    pub slides: u32,
+    /// Visual length in osu!pixels.
+    pub length: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum HitObjectKind {
+    Circle,
+    Slider(Slider),
+    Spinner {
+        end_time: f64,
+    },
+    /// Mania long note.
+    Hold {
+        end_time: f64,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HitObject {
+    pub x: f32,
+    pub y: f32,
+    pub time: f64,
+    pub new_combo: bool,
+    pub hit_sound: u8,
+    pub kind: HitObjectKind,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ObjectCounts {
+    pub circles: u32,
+    pub sliders: u32,
+    pub spinners: u32,
+    pub holds: u32,
+}
+
+impl ObjectCounts {
+    pub fn total(&self) -> u32 {
+        self.circles + self.sliders + self.spinners + self.holds
+    }
+}
+
+/// A parsed .osu file. Only the sections needed server side are kept, the
+/// editor, colour and storyboard data is skipped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BeatmapFile {
+    pub format_version: u32,
+    pub md5: String,
+    pub general: General,
+    pub metadata: Metadata,
+    pub difficulty: Difficulty,
+    /// Background image from the events section, if there is one.
+    pub background: Option<String>,
+    pub timing_points: Vec<TimingPoint>,
+    pub hit_objects: Vec<HitObject>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    None,
+    General,
+    Metadata,
+    Difficulty,
+    Events,
+    TimingPoints,
+    HitObjects,
+    Other,
+}
+
+fn parse_field<T: FromStr>(value: &str, name: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid {} {:?}", name, value))
+}
+
+fn parse_timing_point(line: &str) -> Result<TimingPoint, String> {
+    let parts: Vec<&str> = line.split(',').collect();
+    if parts.len() < 2 {
+        return Err(format!(
+            "timing point needs at least 2 fields, got {}",
+            parts.len()
+        ));
+    }
+
+    let beat_length: f64 = parse_field(parts[1], "beat length")?;
+
+    // v3 and older maps only have the time and beat length, with inherited
+    // points marked by a negative beat length
+    let uninherited = match parts.get(6) {
+        Some(value) => parse_field::<i32>(value, "uninherited flag")? == 1,
+        None => beat_length >= 0.0,
+    };
+    let effects = match parts.get(7) {
+        Some(value) => parse_field::<i32>(value, "effects")?,
+        None => 0,
+    };
+
+    Ok(TimingPoint {
+        time: parse_field(parts[0], "time")?,
+        beat_length,
+        meter: match parts.get(2) {
+            Some(value) => parse_field(value, "meter")?,
+            None => 4,
+        },
+        uninherited,
+        kiai: effects & 1 != 0,
+    })
+}
+
+fn parse_point(point: &str) -> Result<(f32, f32), String> {
+    let (x, y) = point
+        .split_once(':')
+        .ok_or_else(|| format!("invalid curve point {:?}", point))?;
+    Ok((parse_field(x, "curve x")?, parse_field(y, "curve y")?))
+}
+
+fn parse_hit_object(line: &str) -> Result<HitObject, String> {
+    let parts: Vec<&str> = line.split(',').collect();
+    if parts.len() < 5 {
+        return Err(format!(
+            "hit object needs at least 5 fields, got {}",
+            parts.len()
+        ));
+    }
+
+    let time: f64 = parse_field(parts[2], "time")?;
+    let object_type: u32 = parse_field(parts[3], "object type")?;
+
+    let field = |i: usize, name: &str| -> Result<&str, String> {
+        parts
+            .get(i)
+            .copied()
+            .ok_or_else(|| format!("hit object is missing its {}", name))
+    };
+
+    let kind = if object_type & 1 != 0 {
+        HitObjectKind::Circle
+    } else if object_type & 2 != 0 {
+        let mut curve = field(5, "curve")?.split('|');
+        let curve_type = curve.next().unwrap_or_default().parse()?;
+        let control_points = curve.map(parse_point).collect::<Result<_, _>>()?;
+
+        let slides: u32 = parse_field(field(6, "slide count")?, "slide count")?;
+        if slides == 0 {
+            return Err("slider has no slides".to_string());
+        }
+
+        HitObjectKind::Slider(Slider {
+            curve_type,
+            control_points,
+            slides,
+            length: match parts.get(7) {
+                Some(value) => parse_field(value, "slider length")?,
+                None => 0.0,
+            },
+        })
+    } else if object_type & 8 != 0 {
+        HitObjectKind::Spinner {
+            end_time: parse_field(field(5, "end time")?, "end time")?,
+        }
+    } else if object_type & 128 != 0 {
+        let params = field(5, "end time")?;
+        let end_time = params.split(':').next().unwrap_or_default();
+        HitObjectKind::Hold {
+            end_time: parse_field(end_time, "end time")?,
+        }
+    } else {
+        return Err(format!("unknown object type {}", object_type));
+    };
+
+    Ok(HitObject {
+        x: parse_field(parts[0], "x")?,
+        y: parse_field(parts[1], "y")?,
+        time,
+        new_combo: object_type & 4 != 0,
+        hit_sound: parse_field(parts[4], "hit sound")?,
+        kind,
+    })
+}
+
+impl BeatmapFile {
+    pub fn parse(data: &[u8]) -> Result<Self, BeatmapError> {
+        let md5 = format!("{:x}", md5::compute(data));
+        let text = String::from_utf8_lossy(data);
+
+        let mut map = Self {
+            format_version: 0,
+            md5,
+            general: General::default(),
+            metadata: Metadata::default(),
+            difficulty: Difficulty::default(),
+            background: None,
+            timing_points: Vec::new(),
+            hit_objects: Vec::new(),
+        };
+
+        let mut section = Section::None;
+        let mut ar = None;
+
+        for (i, line) in text.lines().enumerate() {
+            let line_no = i + 1;
+            let error = |reason: String| BeatmapError {
+                line: line_no,
+                reason,
+            };
+
+            let line = line.trim_start_matches('\u{feff}').trim_end();
+            if line.trim().is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            if map.format_version == 0 {
+                map.format_version = line
+                    .strip_prefix("osu file format v")
+                    .and_then(|v| v.trim().parse().ok())
+                    .ok_or_else(|| {
+                        error(format!("expected a file format header, got {:?}", line))
+                    })?;
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                section = match &line[1..line.len() - 1] {
+                    "General" => Section::General,
+                    "Metadata" => Section::Metadata,
+                    "Difficulty" => Section::Difficulty,
+                    "Events" => Section::Events,
+                    "TimingPoints" => Section::TimingPoints,
+                    "HitObjects" => Section::HitObjects,
+                    _ => Section::Other,
+                };
+                continue;
+            }
+
+            match section {
+                Section::General | Section::Metadata | Section::Difficulty => {
+                    let (key, value) = line.split_once(':').ok_or_else(|| {
+                        error(format!("expected a key: value pair, got {:?}", line))
+                    })?;
+                    let (key, value) = (key.trim(), value.trim());
+
+                    match (section, key) {
+                        (Section::General, "AudioFilename") => {
+                            map.general.audio_filename = value.to_string()
+                        }
+                        (Section::General, "AudioLeadIn") => {
+                            map.general.audio_lead_in = parse_field(value, key).map_err(error)?
+                        }
+                        (Section::General, "PreviewTime") => {
+                            map.general.preview_time = parse_field(value, key).map_err(error)?
+                        }
+                        (Section::General, "StackLeniency") => {
+                            map.general.stack_leniency = parse_field(value, key).map_err(error)?
+                        }
+                        (Section::General, "Mode") => {
+                            let mode = parse_field(value, key).map_err(error)?;
+                            if mode > 3 {
+                                return Err(error(format!("unknown mode {}", mode)));
+                            }
+                            map.general.mode = mode;
+                        }
+
+                        (Section::Metadata, "Title") => map.metadata.title = value.to_string(),
+                        (Section::Metadata, "TitleUnicode") => {
+                            map.metadata.title_unicode = value.to_string()
+                        }
+                        (Section::Metadata, "Artist") => map.metadata.artist = value.to_string(),
+                        (Section::Metadata, "ArtistUnicode") => {
+                            map.metadata.artist_unicode = value.to_string()
+                        }
+                        (Section::Metadata, "Creator") => map.metadata.creator = value.to_string(),
+                        (Section::Metadata, "Version") => map.metadata.version = value.to_string(),
+                        (Section::Metadata, "Source") => map.metadata.source = value.to_string(),
+                        (Section::Metadata, "Tags") => map.metadata.tags = value.to_string(),
+                        (Section::Metadata, "BeatmapID") => {
+                            map.metadata.beatmap_id = parse_field(value, key).map_err(error)?
+                        }
+                        (Section::Metadata, "BeatmapSetID") => {
+                            map.metadata.beatmap_set_id = parse_field(value, key).map_err(error)?
+                        }
+
+                        (Section::Difficulty, "HPDrainRate") => {
+                            map.difficulty.hp = parse_field(value, key).map_err(error)?
+                        }
+                        (Section::Difficulty, "CircleSize") => {
+                            map.difficulty.cs = parse_field(value, key).map_err(error)?
+                        }
+                        (Section::Difficulty, "OverallDifficulty") => {
+                            map.difficulty.od = parse_field(value, key).map_err(error)?
+                        }
+                        (Section::Difficulty, "ApproachRate") => {
+                            ar = Some(parse_field(value, key).map_err(error)?)
+                        }
+                        (Section::Difficulty, "SliderMultiplier") => {
+                            map.difficulty.slider_multiplier =
+                                parse_field(value, key).map_err(error)?
+                        }
+                        (Section::Difficulty, "SliderTickRate") => {
+                            map.difficulty.slider_tick_rate =
+                                parse_field(value, key).map_err(error)?
+                        }
+
+                        _ => {}
+                    }
+                }
+                Section::Events => {
+                    // 0,0,"bg.jpg",0,0
+                    let parts: Vec<&str> = line.split(',').collect();
+                    if map.background.is_none() && parts.len() >= 3 && parts[0].trim() == "0" {
+                        map.background = Some(parts[2].trim().trim_matches('"').to_string());
+                    }
+                }
+                Section::TimingPoints => map
+                    .timing_points
+                    .push(parse_timing_point(line).map_err(error)?),
+                Section::HitObjects => map.hit_objects.push(parse_hit_object(line).map_err(error)?),
+                Section::None | Section::Other => {}
+            }
+        }
+
+        if map.format_version == 0 {
+            return Err(BeatmapError {
+                line: 1,
+                reason: "file is empty".to_string(),
+            });
+        }
+
+        // maps from before approach rate existed use the overall difficulty
+        map.difficulty.ar = ar.unwrap_or(map.difficulty.od);
+
+        map.timing_points.sort_by(|a, b| a.time.total_cmp(&b.time));
+        map.hit_objects.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        Ok(map)
+    }
+
+    /// Beat length of the uninherited and velocity multiplier of the
+    /// inherited timing point active at `time`.
+    pub fn timing_at(&self, time: f64) -> (f64, f64) {
+        let mut beat_length = self
+            .timing_points
+            .iter()
+            .find(|tp| tp.uninherited)
+            .map_or(1000.0, |tp| tp.beat_length);
+        let mut sv = 1.0;
+
+        for tp in self.timing_points.iter().take_while(|tp| tp.time <= time) {
+            if tp.uninherited {
+                beat_length = tp.beat_length;
+                sv = 1.0;
+            } else {
+                sv = tp.sv_multiplier();
+            }
+        }
+
+        (beat_length, sv)
+    }
+
+    /// Milliseconds a single slide of the slider at `time` takes.
+    pub fn slide_duration(&self, time: f64, slider: &Slider) -> f64 {
+        let (beat_length, sv) = self.timing_at(time);
+        let px_per_beat = 100.0 * self.difficulty.slider_multiplier * sv;
+        slider.length / px_per_beat * beat_length
+    }
+
+    /// Distances from the start of a slide at which the slider at `time` has
+    /// ticks.
+    pub fn slide_tick_distances(&self, time: f64, slider: &Slider) -> Vec<f64> {
+        let (beat_length, sv) = self.timing_at(time);
+        let px_per_beat = 100.0 * self.difficulty.slider_multiplier * sv;
+
+        let mut tick_distance = px_per_beat / self.difficulty.slider_tick_rate;
+        if self.format_version < 8 {
+            // older maps don't scale tick spacing with slider velocity
+            tick_distance /= sv;
+        }
+        if !tick_distance.is_finite() || tick_distance <= 0.0 {
+            return Vec::new();
+        }
+
+        // ticks too close to the slider end are skipped
+        let min_distance_from_end = px_per_beat / beat_length * 10.0;
+
+        let mut distances = Vec::new();
+        let mut distance = tick_distance;
+        while distance < slider.length - min_distance_from_end {
+            distances.push(distance);
+            distance += tick_distance;
+        }
+        distances
+    }
+
+    /// Number of ticks on a single slide of the slider at `time`.
+    pub fn slide_ticks(&self, time: f64, slider: &Slider) -> u32 {
+        self.slide_tick_distances(time, slider).len() as u32
+    }
+
+    /// When the given object ends, in milliseconds.
+    pub fn end_time(&self, object: &HitObject) -> f64 {
+        match &object.kind {
+            HitObjectKind::Circle => object.time,
+            HitObjectKind::Slider(slider) => {
+                object.time + self.slide_duration(object.time, slider) * slider.slides as f64
+            }
+            HitObjectKind::Spinner { end_time } | HitObjectKind::Hold { end_time } => *end_time,
+        }
+    }
+
+    pub fn object_counts(&self) -> ObjectCounts {
+        let mut counts = ObjectCounts::default();
+        for object in &self.hit_objects {
+            match object.kind {
+                HitObjectKind::Circle => counts.circles += 1,
+                HitObjectKind::Slider(_) => counts.sliders += 1,
+                HitObjectKind::Spinner { .. } => counts.spinners += 1,
+                HitObjectKind::Hold { .. } => counts.holds += 1,
+            }
+        }
+        counts
+    }
+
+    /// Highest combo reachable on the map in its own mode.
+    pub fn max_combo(&self) -> u32 {
+        let slider_combo = |object: &HitObject, slider: &Slider| {
+            // head, then the ticks and repeat or tail of every slide
+            1 + slider.slides * (self.slide_ticks(object.time, slider) + 1)
+        };
+
+        self.hit_objects
+            .iter()
+            .map(|object| match (self.general.mode, &object.kind) {
+                // drumrolls and dendens don't give combo
+                (1, HitObjectKind::Circle) => 1,
+                (1, _) => 0,
+                // neither do bananas
+                (2, HitObjectKind::Spinner { .. }) => 0,
+                (_, HitObjectKind::Slider(slider)) => slider_combo(object, slider),
+                _ => 1,
+            })
+            .sum()
+    }
+
+    /// Drain length in milliseconds, from the first object to the end of the
+    /// last one.
+    pub fn length(&self) -> f64 {
+        let first = match self.hit_objects.first() {
+            Some(object) => object.time,
+            None => return 0.0,
+        };
+        let last = self
+            .hit_objects
+            .iter()
+            .map(|object| self.end_time(object))
+            .fold(first, f64::max);
+        last - first
+    }
+
+    /// The bpm the map spends the most time at.
+    pub fn bpm(&self) -> f64 {
+        let end = self
+            .hit_objects
+            .iter()
+            .map(|object| self.end_time(object))
+            .fold(0.0, f64::max);
+
+        let uninherited: Vec<&TimingPoint> = self
+            .timing_points
+            .iter()
+            .filter(|tp| tp.uninherited)
+            .collect();
+
+        let mut durations: Vec<(f64, f64)> = Vec::new();
+        for (i, tp) in uninherited.iter().enumerate() {
+            let until = uninherited.get(i + 1).map_or(end, |next| next.time);
+            let duration = (until - tp.time).max(0.0);
+
+            match durations
+                .iter_mut()
+                .find(|(bl, _)| (bl - tp.beat_length).abs() < 1e-3)
+            {
+                Some((_, total)) => *total += duration,
+                None => durations.push((tp.beat_length, duration)),
+            }
+        }
+
+        durations
+            .into_iter()
+            .filter(|(beat_length, _)| *beat_length > 0.0)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(0.0, |(beat_length, _)| 60_000.0 / beat_length)
+    }
+}
+
+#[test]
+fn test_parse_beatmap() {
+    let data = b"osu file format v14
+
+[General]
+AudioFilename: audio.mp3
+Mode: 0
+
+[Metadata]
+Title:Blue Zenith
+Artist:xi
+Creator:Asphyxia
+Version:FOUR DIMENSIONS
+BeatmapID:658127
+BeatmapSetID:292301
+
+[Difficulty]
+HPDrainRate:6
+CircleSize:4
+OverallDifficulty:8
+ApproachRate:9.5
+SliderMultiplier:1
+SliderTickRate:1
+
+[Events]
+0,0,\"bg.jpg\",0,0
+
+[TimingPoints]
+0,500,4,2,0,100,1,0
+2000,-50,4,2,0,100,0,1
+
+[HitObjects]
+256,192,0,5,0,0:0:0:0:
+256,192,500,2,0,L|356:192,2,200
+256,192,2500,2,0,B|300:100|356:192,1,100
+256,192,3000,12,0,4000,0:0:0:0:
+";
+
+    let map = BeatmapFile::parse(data).unwrap();
+    assert_eq!(map.format_version, 14);
+    assert_eq!(map.md5, format!("{:x}", md5::compute(&data[..])));
+    assert_eq!(map.metadata.beatmap_id, 658127);
+    assert_eq!(map.difficulty.ar, 9.5);
+    assert_eq!(map.background.as_deref(), Some("bg.jpg"));
+    assert_eq!(map.timing_at(2500.0), (500.0, 2.0));
+
+    let counts = map.object_counts();
+    assert_eq!((counts.circles, counts.sliders, counts.spinners), (1, 2, 1));
+
+    // circle + (head + 2 * (1 tick + end)) + (head + end at 2x sv) + spinner
+    assert_eq!(map.max_combo(), 1 + 5 + 2 + 1);
+    assert_eq!(map.length(), 4000.0);
+    assert_eq!(map.bpm(), 120.0);
+
+    let broken = b"osu file format v14\n\n[HitObjects]\n256,192,0,1,0\n256,192,abc,1,0\n";
+    let err = BeatmapFile::parse(broken).unwrap_err();
+    assert_eq!(err.line, 5);
+}
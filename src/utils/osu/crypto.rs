@@ -0,0 +1,350 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+// osu! encrypts score submissions with .NET's RijndaelManaged using a 256-bit
+// block, which isn't AES (AES is Rijndael fixed at 128-bit blocks), so none of
+// the usual crates can decrypt it. This is a small implementation of Rijndael
+// for any block size the spec allows, with the S-boxes generated on first use.
+
+lazy_static::lazy_static! {
+    static ref SBOXES: ([u8; 256], [u8; 256]) = {
+        let mut sbox = [0u8; 256];
+        let mut inv_sbox = [0u8; 256];
+
+        for x in 0..=255u8 {
+            // multiplicative inverse in GF(2^8), with 0 mapping to 0
+            let inv = (1..=255u8).find(|&y| gmul(x, y) == 1).unwrap_or(0);
+
+            let s = inv
+                ^ inv.rotate_left(1)
+                ^ inv.rotate_left(2)
+                ^ inv.rotate_left(3)
+                ^ inv.rotate_left(4)
+                ^ 0x63;
+
+            sbox[x as usize] = s;
+            inv_sbox[s as usize] = x;
+        }
+
+        (sbox, inv_sbox)
+    };
+}
+
+#[derive(Debug)]
+pub enum CryptoError {
+    InvalidKeyLength(usize),
+    InvalidBlockLength(usize),
+    InvalidCiphertextLength(usize),
+    InvalidBase64(base64::DecodeError),
+    InvalidUtf8,
+}
+
+impl std::fmt::Display for CryptoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CryptoError::InvalidKeyLength(len) => write!(f, "invalid key length {}", len),
+            CryptoError::InvalidBlockLength(len) => write!(f, "invalid block length {}", len),
+            CryptoError::InvalidCiphertextLength(len) => {
+                write!(
+                    f,
+                    "ciphertext length {} is not a multiple of the block size",
+                    len
+                )
+            }
+            CryptoError::InvalidBase64(e) => write!(f, "invalid base64: {}", e),
+            CryptoError::InvalidUtf8 => write!(f, "decrypted data is not valid utf-8"),
+        }
+    }
+}
+
+impl std::error::Error for CryptoError {}
+
+fn xtime(b: u8) -> u8 {
+    (b << 1) ^ if b & 0x80 != 0 { 0x1b } else { 0 }
+}
+
+fn gmul(mut a: u8, mut b: u8) -> u8 {
+    let mut r = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            r ^= a;
+        }
+        a = xtime(a);
+        b >>= 1;
+    }
+    r
+}
+
+pub struct Rijndael {
+    /// Block size in 32-bit words (4, 6 or 8).
+    nb: usize,
+    rounds: usize,
+    round_keys: Vec<[u8; 4]>,
+}
+
+impl Rijndael {
+    pub fn new(key: &[u8], block_size: usize) -> Result<Self, CryptoError> {
+        if !matches!(key.len(), 16 | 24 | 32) {
+            return Err(CryptoError::InvalidKeyLength(key.len()));
+        }
+        if !matches!(block_size, 16 | 24 | 32) {
+            return Err(CryptoError::InvalidBlockLength(block_size));
+        }
+
+        let nk = key.len() / 4;
+        let nb = block_size / 4;
+        let rounds = nk.max(nb) + 6;
+        let sbox = &SBOXES.0;
+
+        let mut round_keys: Vec<[u8; 4]> =
+            key.chunks(4).map(|c| [c[0], c[1], c[2], c[3]]).collect();
+
+        let mut rcon = 1u8;
+        for i in nk..nb * (rounds + 1) {
+            let mut temp = round_keys[i - 1];
+
+            if i % nk == 0 {
+                temp.rotate_left(1);
+                for b in temp.iter_mut() {
+                    *b = sbox[*b as usize];
+                }
+                temp[0] ^= rcon;
+                rcon = xtime(rcon);
+            } else if nk > 6 && i % nk == 4 {
+                for b in temp.iter_mut() {
+                    *b = sbox[*b as usize];
+                }
+            }
+
+            let prev = round_keys[i - nk];
+            round_keys.push([
+                prev[0] ^ temp[0],
+                prev[1] ^ temp[1],
+                prev[2] ^ temp[2],
+                prev[3] ^ temp[3],
+            ]);
+        }
+
+        Ok(Self {
+            nb,
+            rounds,
+            round_keys,
+        })
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.nb * 4
+    }
+
+    /// Row offsets used by ShiftRows for the current block size.
+    fn shifts(&self) -> [usize; 4] {
+        match self.nb {
+            8 => [0, 1, 3, 4],
+            _ => [0, 1, 2, 3],
+        }
+    }
+
+    fn add_round_key(&self, state: &mut [u8], round: usize) {
+        for c in 0..self.nb {
+            let key = self.round_keys[round * self.nb + c];
+            for r in 0..4 {
+                state[c * 4 + r] ^= key[r];
+            }
+        }
+    }
+
+    fn shift_rows(&self, state: &mut [u8], inverse: bool) {
+        let shifts = self.shifts();
+        let original = state.to_vec();
+
+        for (r, shift) in shifts.iter().enumerate().skip(1) {
+            for c in 0..self.nb {
+                let (dst, src) = if inverse {
+                    ((c + shift) % self.nb, c)
+                } else {
+                    (c, (c + shift) % self.nb)
+                };
+                state[dst * 4 + r] = original[src * 4 + r];
+            }
+        }
+    }
+
+    fn mix_columns(&self, state: &mut [u8], inverse: bool) {
+        let coefficients: [u8; 4] = if inverse {
+            [0x0e, 0x0b, 0x0d, 0x09]
+        } else {
+            [0x02, 0x03, 0x01, 0x01]
+        };
+
+        for column in state.chunks_mut(4) {
+            let a = [column[0], column[1], column[2], column[3]];
+            for (r, out) in column.iter_mut().enumerate() {
+                *out = (0..4).fold(0, |acc, i| acc ^ gmul(coefficients[(4 + i - r) % 4], a[i]));
+            }
+        }
+    }
+
+    pub fn encrypt_block(&self, block: &mut [u8]) {
+        let sbox = &SBOXES.0;
+
+        self.add_round_key(block, 0);
+        for round in 1..=self.rounds {
+            for b in block.iter_mut() {
+                *b = sbox[*b as usize];
+            }
+            self.shift_rows(block, false);
+            if round != self.rounds {
+                self.mix_columns(block, false);
+            }
+            self.add_round_key(block, round);
+        }
+    }
+
+    pub fn decrypt_block(&self, block: &mut [u8]) {
+        let inv_sbox = &SBOXES.1;
+
+        self.add_round_key(block, self.rounds);
+        for round in (0..self.rounds).rev() {
+            self.shift_rows(block, true);
+            for b in block.iter_mut() {
+                *b = inv_sbox[*b as usize];
+            }
+            self.add_round_key(block, round);
+            if round != 0 {
+                self.mix_columns(block, true);
+            }
+        }
+    }
+
+    /// CBC encryption, zero padding the data to a whole number of blocks the
+    /// way the client does.
+    pub fn encrypt_cbc(&self, iv: &[u8], data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let bs = self.block_size();
+        if iv.len() != bs {
+            return Err(CryptoError::InvalidBlockLength(iv.len()));
+        }
+
+        let mut out = data.to_vec();
+        out.resize(data.len().div_ceil(bs).max(1) * bs, 0);
+
+        let mut prev = iv.to_vec();
+        for block in out.chunks_mut(bs) {
+            for (b, p) in block.iter_mut().zip(prev.iter()) {
+                *b ^= p;
+            }
+            self.encrypt_block(block);
+            prev.copy_from_slice(block);
+        }
+
+        Ok(out)
+    }
+
+    /// CBC decryption, stripping the client's trailing zero padding.
+    pub fn decrypt_cbc(&self, iv: &[u8], data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let bs = self.block_size();
+        if iv.len() != bs {
+            return Err(CryptoError::InvalidBlockLength(iv.len()));
+        }
+        if data.is_empty() || !data.len().is_multiple_of(bs) {
+            return Err(CryptoError::InvalidCiphertextLength(data.len()));
+        }
+
+        let mut out = data.to_vec();
+        let mut prev = iv.to_vec();
+        for block in out.chunks_mut(bs) {
+            let ciphertext = block.to_vec();
+            self.decrypt_block(block);
+            for (b, p) in block.iter_mut().zip(prev.iter()) {
+                *b ^= p;
+            }
+            prev = ciphertext;
+        }
+
+        let len = out.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+        out.truncate(len);
+
+        Ok(out)
+    }
+}
+
+/// Decrypts the base64 `score` and `s` fields of a score submission with the
+/// `osu!-scoreburgr---------{osuver}` key, returning the colon separated score
+/// data and the client hash.
+pub fn decrypt_score_data(
+    score_data_b64: &str,
+    client_hash_b64: &str,
+    iv_b64: &str,
+    osu_version: &str,
+) -> Result<(Vec<String>, String), CryptoError> {
+    let key = format!("osu!-scoreburgr---------{}", osu_version);
+    let cipher = Rijndael::new(key.as_bytes(), 32)?;
+
+    let iv = STANDARD
+        .decode(iv_b64)
+        .map_err(CryptoError::InvalidBase64)?;
+
+    let decrypt = |data_b64: &str| -> Result<String, CryptoError> {
+        let data = STANDARD
+            .decode(data_b64)
+            .map_err(CryptoError::InvalidBase64)?;
+        let plain = cipher.decrypt_cbc(&iv, &data)?;
+        String::from_utf8(plain).map_err(|_| CryptoError::InvalidUtf8)
+    };
+
+    let score_data = decrypt(score_data_b64)?
+        .split(':')
+        .map(|s| s.to_string())
+        .collect();
+    let client_hash = decrypt(client_hash_b64)?;
+
+    Ok((score_data, client_hash))
+}
+
+#[test]
+fn test_aes256_vector() {
+    // FIPS-197 appendix C.3, AES is Rijndael with a 128-bit block
+    let key: Vec<u8> = (0..32).collect();
+    let cipher = Rijndael::new(&key, 16).unwrap();
+
+    let mut block = [
+        0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee,
+        0xff,
+    ];
+    cipher.encrypt_block(&mut block);
+    assert_eq!(
+        block,
+        [
+            0x8e, 0xa2, 0xb7, 0xca, 0x51, 0x67, 0x45, 0xbf, 0xea, 0xfc, 0x49, 0x90, 0x4b, 0x49,
+            0x60, 0x89
+        ]
+    );
+
+    cipher.decrypt_block(&mut block);
+    assert_eq!(block[0], 0x00);
+    assert_eq!(block[15], 0xff);
+}
+
+#[test]
+fn test_score_data_roundtrip() {
+    let key = "osu!-scoreburgr---------20230326";
+    let cipher = Rijndael::new(key.as_bytes(), 32).unwrap();
+    let iv = [7u8; 32];
+
+    let score = "a5b99395a42bd55bc5eb1d2411cbdf8b:cmyui:abc:1:2:3:4:5:6:727:100:False:A:0:True:0:230326120000:20230326";
+    // exactly one block, which the client sends without any padding
+    let hash = "86d9b2c3e7c6b8b0c6f0d3c3d8b3c3e:";
+
+    // like the client, zero padded rather than pkcs7
+    let score_encrypted = cipher.encrypt_cbc(&iv, score.as_bytes()).unwrap();
+    let hash_encrypted = cipher.encrypt_cbc(&iv, hash.as_bytes()).unwrap();
+    assert_eq!(score_encrypted.len(), score.len().div_ceil(32) * 32);
+    assert_eq!(hash_encrypted.len(), 32);
+
+    let score_b64 = STANDARD.encode(score_encrypted);
+    let hash_b64 = STANDARD.encode(hash_encrypted);
+
+    let (data, client_hash) =
+        decrypt_score_data(&score_b64, &hash_b64, &STANDARD.encode(iv), "20230326").unwrap();
+    assert_eq!(data.join(":"), score);
+    assert_eq!(data[1], "cmyui");
+    assert_eq!(client_hash, hash);
+}
@@ -1,4 +1,6 @@
-use std::{convert::TryInto, io::BufRead};
+use std::convert::TryInto;
+
+use crate::constants::multiplayer::SlotStatus;
 
 pub struct PacketReader {
     buffer: Vec<u8>,
@@ -197,10 +199,10 @@ pub struct PacketReader {
 */
 
 pub struct Message {
-    sender: String,
-    text: String,
-    recipient: String,
-    sender_id: i32,
+    pub sender: String,
+    pub text: String,
+    pub recipient: String,
+    pub sender_id: i32,
 }
 
 pub struct Channel {
@@ -209,6 +211,60 @@ pub struct Channel {
     players: i32,
 }
 
+/// A multiplayer match as the client sends and receives it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MatchInfo {
+    pub id: i16,
+    pub in_progress: bool,
+    pub powerplay: i8,
+    pub mods: i32,
+    pub name: String,
+    pub password: String,
+    pub map_name: String,
+    pub map_id: i32,
+    pub map_md5: String,
+    pub slot_statuses: [u8; 16],
+    pub slot_teams: [u8; 16],
+    /// The ids of the players in the slots that have one, in slot order.
+    pub slot_ids: Vec<i32>,
+    pub host_id: i32,
+    pub mode: u8,
+    pub win_condition: u8,
+    pub team_type: u8,
+    pub freemods: bool,
+    /// Only sent with freemods on.
+    pub slot_mods: [i32; 16],
+    /// Used for mania's random mod.
+    pub seed: i32,
+}
+
+/// A player's running totals during a multiplayer game, sent after every
+/// judgement.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScoreFrame {
+    pub time: i32,
+    /// The player's slot once the server passes it on.
+    pub id: u8,
+    pub n300: u16,
+    pub n100: u16,
+    pub n50: u16,
+    pub ngeki: u16,
+    pub nkatu: u16,
+    pub nmiss: u16,
+    pub total_score: i32,
+    pub max_combo: u16,
+    pub current_combo: u16,
+    pub perfect: bool,
+    pub current_hp: u8,
+    pub tag_byte: u8,
+    pub score_v2: bool,
+}
+
+impl ScoreFrame {
+    /// Bytes in a frame without the score v2 portions.
+    pub const SIZE: usize = 29;
+}
+
 impl PacketReader {
     pub fn new(buffer: Vec<u8>) -> Self {
         PacketReader { buffer }
@@ -301,27 +357,55 @@ impl PacketReader {
         list
     }
 
-    // strings
-    pub fn read_string(&mut self) -> String {
-        if !self.buffer[0] == 0x0B {
-            self.buffer = self.buffer[1..].to_vec();
-            return "".to_string();
-        }
-        self.buffer = self.buffer[1..].to_vec();
+    pub fn read_bytes(&mut self, len: usize) -> Vec<u8> {
+        let val = self.buffer[0..len].to_vec();
+        self.buffer = self.buffer[len..].to_vec();
+        val
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn read_uleb128(&mut self) -> usize {
         let mut shift = 0;
-        let mut len = 0;
+        let mut val = 0;
         loop {
-            let b = self.buffer[0];
-            self.buffer = self.buffer[1..].to_vec();
-            len |= (b & 0x7F) << shift;
+            let b = self.read_u8();
+            val |= ((b & 0x7F) as usize) << shift;
             if (b & 0x80) == 0 {
                 break;
             }
             shift += 7;
         }
-        let val = self.buffer[0..len as usize].to_vec();
-        self.buffer = self.buffer[len as usize..].to_vec();
-        String::from_utf8(val).unwrap()
+        val
+    }
+
+    // strings
+    pub fn read_string(&mut self) -> String {
+        if self.read_u8() != 0x0B {
+            return "".to_string();
+        }
+        let len = self.read_uleb128();
+        String::from_utf8_lossy(&self.read_bytes(len)).into_owned()
+    }
+
+    /// Whether a complete string can be read from what's left of the buffer.
+    pub fn has_string(&self) -> bool {
+        match self.buffer.first() {
+            None => false,
+            Some(&0x0B) => {
+                let mut len = 0;
+                for (i, b) in self.buffer[1..].iter().enumerate().take(10) {
+                    len |= ((b & 0x7F) as usize) << (7 * i);
+                    if b & 0x80 == 0 {
+                        return (self.buffer.len() - i - 2) >= len;
+                    }
+                }
+                false
+            }
+            Some(_) => true,
+        }
     }
 
     // custom
@@ -338,6 +422,67 @@ impl PacketReader {
         }
     }
 
+    pub fn read_match(&mut self) -> MatchInfo {
+        let mut m = MatchInfo {
+            id: self.read_i16(),
+            in_progress: self.read_i8() == 1,
+            powerplay: self.read_i8(),
+            mods: self.read_i32(),
+            name: self.read_string(),
+            password: self.read_string(),
+            map_name: self.read_string(),
+            map_id: self.read_i32(),
+            map_md5: self.read_string(),
+            ..Default::default()
+        };
+        for status in m.slot_statuses.iter_mut() {
+            *status = self.read_u8();
+        }
+        for team in m.slot_teams.iter_mut() {
+            *team = self.read_u8();
+        }
+
+        for status in m.slot_statuses {
+            if SlotStatus::from_bits_truncate(status).intersects(SlotStatus::HAS_PLAYER) {
+                m.slot_ids.push(self.read_i32());
+            }
+        }
+
+        m.host_id = self.read_i32();
+        m.mode = self.read_u8();
+        m.win_condition = self.read_u8();
+        m.team_type = self.read_u8();
+        m.freemods = self.read_u8() == 1;
+        if m.freemods {
+            for mods in m.slot_mods.iter_mut() {
+                *mods = self.read_i32();
+            }
+        }
+        m.seed = self.read_i32();
+        m
+    }
+
+    /// Reads a score frame; the score v2 portions after it are left unread.
+    pub fn read_score_frame(&mut self) -> ScoreFrame {
+        ScoreFrame {
+            time: self.read_i32(),
+            id: self.read_u8(),
+            n300: self.read_u16(),
+            n100: self.read_u16(),
+            n50: self.read_u16(),
+            ngeki: self.read_u16(),
+            nkatu: self.read_u16(),
+            nmiss: self.read_u16(),
+            total_score: self.read_i32(),
+            max_combo: self.read_u16(),
+            current_combo: self.read_u16(),
+            perfect: self.read_u8() == 1,
+            current_hp: self.read_u8(),
+            tag_byte: self.read_u8(),
+            score_v2: self.read_u8() == 1,
+        }
+    }
+
     pub fn read_channel(&mut self) -> Channel {
         let name = self.read_string();
         let topic = self.read_string();
@@ -1,7 +1,8 @@
-use std::collections::HashMap;
 use std::convert::TryInto;
 extern crate alloc;
 
+use super::packet_reader::MatchInfo;
+
 // _noexpand_types: dict[osuTypes, Callable[..., bytes]] = {
 //     # base
 //     osuTypes.i8: struct.Struct("<b").pack,
@@ -66,14 +67,14 @@ pub fn write_f64(value: f64) -> [u8; 8] {
     value.to_le_bytes()
 }
 
-pub fn write_uleb128(mut value: u8) -> Vec<u8> {
+pub fn write_uleb128(mut value: usize) -> Vec<u8> {
     let mut result = Vec::new();
 
     loop {
-        let mut byte = value & 0x7f;
+        let mut byte = (value & 0x7f) as u8;
         value >>= 7;
 
-        if value == 0 {
+        if value != 0 {
             byte |= 0x80;
         }
 
@@ -88,12 +89,12 @@ pub fn write_uleb128(mut value: u8) -> Vec<u8> {
 
 pub fn write_string(value: &str) -> Vec<u8> {
     //! god forgive us
-    if value == "" {
+    if value.is_empty() {
         return b"\x00".to_vec();
     }
     let mut r = Vec::new();
-    r.extend(b"\x00".to_vec());
-    r.extend(write_uleb128(value.as_bytes().len().try_into().unwrap()));
+    r.extend(b"\x0b".to_vec());
+    r.extend(write_uleb128(value.len()));
     r.extend(value.as_bytes().to_vec());
     r
 }
@@ -107,31 +108,68 @@ pub fn write_i32_list(value: Vec<i32>) -> Vec<u8> {
     r
 }
 
-pub fn write_message<T, U, V, W>(sender: T, msg: U, recipient: V, sender_id: W) -> Vec<u8>
-where
-    T: Into<&'static str>,
-    U: Into<&'static str>,
-    V: Into<&'static str>,
-    W: Into<u32>,
-{
+/// Frames packet data the way bancho sends it: the packet id, a padding byte
+/// and the length of the data.
+pub fn write_packet(packet_id: i16, data: &[u8]) -> Vec<u8> {
+    let mut r = Vec::with_capacity(7 + data.len());
+    r.extend(write_i16(packet_id));
+    r.extend(write_u8(0));
+    r.extend(write_u32(data.len() as u32));
+    r.extend(data);
+    r
+}
+
+pub fn write_message(sender: &str, msg: &str, recipient: &str, sender_id: i32) -> Vec<u8> {
     let mut r = Vec::new();
-    r.extend(write_string(sender.into()));
-    r.extend(write_string(msg.into()));
-    r.extend(write_string(recipient.into()));
-    r.extend(write_u32(sender_id.into()));
+    r.extend(write_string(sender));
+    r.extend(write_string(msg));
+    r.extend(write_string(recipient));
+    r.extend(write_i32(sender_id));
     r
 }
 
-pub fn write_channel<T, U, V>(name: T, topic: U, count: V) -> Vec<u8>
-where
-    T: Into<&'static str>,
-    U: Into<&'static str>,
-    V: Into<u16>,
-{
+pub fn write_channel(name: &str, topic: &str, count: u16) -> Vec<u8> {
     let mut r = Vec::new();
-    r.extend(write_string(name.into()));
-    r.extend(write_string(topic.into()));
-    r.extend(write_u16(count.into()));
+    r.extend(write_string(name));
+    r.extend(write_string(topic));
+    r.extend(write_u16(count));
+    r
+}
+
+/// Writes a match; the password is left out of what's sent to the lobby, which
+/// only needs to know whether there is one.
+pub fn write_match(m: &MatchInfo, send_password: bool) -> Vec<u8> {
+    let mut r = Vec::new();
+    r.extend(write_i16(m.id));
+    r.extend(write_u8(m.in_progress as u8));
+    r.extend(write_i8(m.powerplay));
+    r.extend(write_i32(m.mods));
+    r.extend(write_string(&m.name));
+    if send_password || m.password.is_empty() {
+        r.extend(write_string(&m.password));
+    } else {
+        // an empty string that's still present
+        r.extend(b"\x0b\x00");
+    }
+    r.extend(write_string(&m.map_name));
+    r.extend(write_i32(m.map_id));
+    r.extend(write_string(&m.map_md5));
+    r.extend(m.slot_statuses);
+    r.extend(m.slot_teams);
+    for id in &m.slot_ids {
+        r.extend(write_i32(*id));
+    }
+    r.extend(write_i32(m.host_id));
+    r.extend(write_u8(m.mode));
+    r.extend(write_u8(m.win_condition));
+    r.extend(write_u8(m.team_type));
+    r.extend(write_u8(m.freemods as u8));
+    if m.freemods {
+        for mods in m.slot_mods {
+            r.extend(write_i32(mods));
+        }
+    }
+    r.extend(write_i32(m.seed));
     r
 }
 
@@ -180,19 +218,52 @@ pub fn write<T>(value: T) -> Vec<u8> {
 
 #[test]
 fn test_write() {
-    println!("i8: {:x?}", write(1 as i8));
-    println!("u8: {:x?}", write(1 as u8));
-    println!("i16: {:x?}", write(1 as i16));
-    println!("u16: {:x?}", write(1 as u16));
-    println!("i32: {:x?}", write(1 as i32));
-    println!("u32: {:x?}", write(1 as u32));
+    println!("i8: {:x?}", write(1_i8));
+    println!("u8: {:x?}", write(1_u8));
+    println!("i16: {:x?}", write(1_i16));
+    println!("u16: {:x?}", write(1_u16));
+    println!("i32: {:x?}", write(1_i32));
+    println!("u32: {:x?}", write(1_u32));
     //println!("{}", std::any::type_name::<half::f16>());
-    println!("f16: {:x?}", write(half::f16::from_f32(1 as f32)));
-    println!("f32: {:x?}", write(1 as f32));
-    println!("i64: {:x?}", write(1 as i64));
-    println!("u64: {:x?}", write(1 as u64));
-    println!("f64: {:x?}", write(1 as f64));
+    println!("f16: {:x?}", write(half::f16::from_f32(1_f32)));
+    println!("f32: {:x?}", write(1_f32));
+    println!("i64: {:x?}", write(1_i64));
+    println!("u64: {:x?}", write(1_u64));
+    println!("f64: {:x?}", write(1_f64));
     println!("&str: {:x?}", write("test"));
     //println!("{}", std::any::type_name::<String>());
     //println!("alloc::string::String: {:x?}", write("test".to_string()));
 }
+
+#[test]
+fn test_match_round_trip() {
+    use super::packet_reader::PacketReader;
+
+    let mut m = MatchInfo {
+        id: 3,
+        mods: 64,
+        name: "alice's game".to_string(),
+        password: "secret".to_string(),
+        map_name: "Artist - Title [Insane]".to_string(),
+        map_id: 1002,
+        map_md5: "de33ab02".to_string(),
+        slot_ids: vec![2, 3],
+        host_id: 3,
+        team_type: 2,
+        freemods: true,
+        seed: 42,
+        ..Default::default()
+    };
+    m.slot_statuses = [1; 16];
+    m.slot_statuses[0] = 4;
+    m.slot_statuses[5] = 8;
+    m.slot_teams[5] = 2;
+    m.slot_mods[5] = 8;
+
+    let read = PacketReader::new(write_match(&m, true)).read_match();
+    assert_eq!(read, m);
+
+    let hidden = PacketReader::new(write_match(&m, false)).read_match();
+    assert_eq!(hidden.password, "");
+    assert_eq!(hidden.slot_ids, m.slot_ids);
+}
@@ -1,10 +1,23 @@
-use std::{collections::HashMap, sync::Arc};
-use serde::{Deserialize, Serialize};
-use super::packet_reader::{Message, self, PacketReader};
+use std::collections::HashMap;
+use super::packet_reader::{MatchInfo, Message, PacketReader};
+use super::packet_writer::{
+    write_channel, write_f32, write_i32, write_i32_list, write_i64, write_i16, write_match,
+    write_message, write_packet, write_string, write_u8,
+};
+use crate::constants::gamemodes::GameMode;
+use crate::constants::grades::Grade;
+use crate::constants::mods::Mods;
+use crate::commands::{self, Target};
+use crate::structs::beatmap::{Beatmap, RankedStatus};
+use crate::structs::bot;
+use crate::structs::channel::CHANNELS;
+use crate::structs::chat_filter;
+use crate::structs::multiplayer::MATCHES;
+use crate::structs::player::{Player, Status, PLAYERS};
+use crate::structs::score::Score;
 use futures::future::{BoxFuture, FutureExt};
 
 pub type ClientPacketData = Vec<u8>;
-pub struct Player; // TODO: actually make this
 
 pub struct PacketError {
     pub packet_id: u8,          // Packet ID
@@ -32,7 +45,7 @@ impl BasePacket for OsuChangeAction {
     }
 
     fn to_bytes(&self) -> Vec<u8> {
-        let mut r = Vec::new();
+        let r = Vec::new();
         // r.extend(write_u32(self.id));
         // r.extend(write_u8(self.action));
         // r.extend(write_u32(self.beatmap_id));
@@ -156,6 +169,16 @@ pub enum Packets {
     OsuTournamentLeaveMatchChannel = 109,
 }
 
+impl Packets {
+    pub fn from_i16(id: i16) -> Option<Self> {
+        // 6 and 35 were removed from the protocol
+        match id {
+            0..=5 | 7..=34 | 36..=109 => Some(unsafe { std::mem::transmute::<i16, Packets>(id) }),
+            _ => None,
+        }
+    }
+}
+
 // -===========-
 // -= Client Packets =-
 // -===========-
@@ -169,6 +192,7 @@ pub struct OsuChangeAction {
     map_md5: String,
     mods: u32,
     mode: u8,
+    map_id: i32,
 }
 
 impl ClientPacket for OsuChangeAction {}
@@ -181,13 +205,14 @@ impl OsuChangeAction {
             map_md5: reader.read_string(),
             mods: reader.read_u32(),
             mode: reader.read_u8(),
+            map_id: reader.read_i32(),
         }
     }
 }
 
 pub struct OsuSendPublicMessage {
     id: i16,
-    message: String,
+    message: Message,
 }
 
 impl ClientPacket for OsuSendPublicMessage {}
@@ -195,7 +220,7 @@ impl OsuSendPublicMessage {
     pub fn new(reader: &mut PacketReader) -> Self {
         Self {
             id: Packets::OsuSendPublicMessage as i16,
-            message: reader.read_string(),
+            message: reader.read_message(),
         }
     }
 }
@@ -348,7 +373,8 @@ impl OsuJoinLobby {
 }
 
 pub struct OsuCreateMatch {
-    id: i16
+    id: i16,
+    match_info: MatchInfo,
 }
 
 impl ClientPacket for OsuCreateMatch {}
@@ -356,12 +382,15 @@ impl OsuCreateMatch {
     pub fn new(reader: &mut PacketReader) -> Self {
         Self {
             id: Packets::OsuCreateMatch as i16,
+            match_info: reader.read_match(),
         }
     }
 }
 
 pub struct OsuJoinMatch {
-    id: i16
+    id: i16,
+    match_id: i32,
+    password: String,
 }
 
 impl ClientPacket for OsuJoinMatch {}
@@ -369,6 +398,8 @@ impl OsuJoinMatch {
     pub fn new(reader: &mut PacketReader) -> Self {
         Self {
             id: Packets::OsuJoinMatch as i16,
+            match_id: reader.read_i32(),
+            password: reader.read_string(),
         }
     }
 }
@@ -387,7 +418,8 @@ impl OsuPartMatch {
 }
 
 pub struct OsuMatchChangeSlot {
-    id: i16
+    id: i16,
+    slot: i32,
 }
 
 impl ClientPacket for OsuMatchChangeSlot {}
@@ -395,6 +427,7 @@ impl OsuMatchChangeSlot {
     pub fn new(reader: &mut PacketReader) -> Self {
         Self {
             id: Packets::OsuMatchChangeSlot as i16,
+            slot: reader.read_i32(),
         }
     }
 }
@@ -413,7 +446,8 @@ impl OsuMatchReady {
 }
 
 pub struct OsuMatchLock {
-    id: i16
+    id: i16,
+    slot: i32,
 }
 
 impl ClientPacket for OsuMatchLock {}
@@ -421,12 +455,14 @@ impl OsuMatchLock {
     pub fn new(reader: &mut PacketReader) -> Self {
         Self {
             id: Packets::OsuMatchLock as i16,
+            slot: reader.read_i32(),
         }
     }
 }
 
 pub struct OsuMatchChangeSettings {
-    id: i16
+    id: i16,
+    match_info: MatchInfo,
 }
 
 impl ClientPacket for OsuMatchChangeSettings {}
@@ -434,6 +470,7 @@ impl OsuMatchChangeSettings {
     pub fn new(reader: &mut PacketReader) -> Self {
         Self {
             id: Packets::OsuMatchChangeSettings as i16,
+            match_info: reader.read_match(),
         }
     }
 }
@@ -452,7 +489,9 @@ impl OsuMatchStart {
 }
 
 pub struct OsuMatchScoreUpdate {
-    id: i16
+    id: i16,
+    /// The score frame, passed on to the others in the match as is.
+    data: Vec<u8>,
 }
 
 impl ClientPacket for OsuMatchScoreUpdate {}
@@ -460,6 +499,7 @@ impl OsuMatchScoreUpdate {
     pub fn new(reader: &mut PacketReader) -> Self {
         Self {
             id: Packets::OsuMatchScoreUpdate as i16,
+            data: reader.read_bytes(reader.remaining()),
         }
     }
 }
@@ -478,7 +518,8 @@ impl OsuMatchComplete {
 }
 
 pub struct OsuMatchChangeMods {
-    id: i16
+    id: i16,
+    mods: i32,
 }
 
 impl ClientPacket for OsuMatchChangeMods {}
@@ -486,6 +527,7 @@ impl OsuMatchChangeMods {
     pub fn new(reader: &mut PacketReader) -> Self {
         Self {
             id: Packets::OsuMatchChangeMods as i16,
+            mods: reader.read_i32(),
         }
     }
 }
@@ -584,20 +626,28 @@ impl OsuChannelJoin {
 }
 
 pub struct OsuBeatmapInfoRequest {
-    id: i16
+    id: i16,
+    filenames: Vec<String>,
+    map_ids: Vec<i32>,
 }
 
 impl ClientPacket for OsuBeatmapInfoRequest {}
 impl OsuBeatmapInfoRequest {
     pub fn new(reader: &mut PacketReader) -> Self {
+        let count = reader.read_i32().max(0);
+        let filenames = (0..count).map(|_| reader.read_string()).collect();
+
         Self {
             id: Packets::OsuBeatmapInfoRequest as i16,
+            filenames,
+            map_ids: reader.read_i32_list_i32l(),
         }
     }
 }
 
 pub struct OsuMatchTransferHost {
-    id: i16
+    id: i16,
+    slot: i32,
 }
 
 impl ClientPacket for OsuMatchTransferHost {}
@@ -605,6 +655,7 @@ impl OsuMatchTransferHost {
     pub fn new(reader: &mut PacketReader) -> Self {
         Self {
             id: Packets::OsuMatchTransferHost as i16,
+            slot: reader.read_i32(),
         }
     }
 }
@@ -728,6 +779,7 @@ impl OsuUserStatsRequest {
 
 pub struct OsuMatchInvite {
     id: i16,
+    user_id: i32,
 }
 
 impl ClientPacket for OsuMatchInvite {}
@@ -735,12 +787,14 @@ impl OsuMatchInvite {
     pub fn new(reader: &mut PacketReader) -> Self {
         Self {
             id: Packets::OsuMatchInvite as i16,
+            user_id: reader.read_i32(),
         }
     }
 }
 
 pub struct OsuMatchChangePassword {
     id: i16,
+    match_info: MatchInfo,
 }
 
 impl ClientPacket for OsuMatchChangePassword {}
@@ -748,12 +802,14 @@ impl OsuMatchChangePassword {
     pub fn new(reader: &mut PacketReader) -> Self {
         Self {
             id: Packets::OsuMatchChangePassword as i16,
+            match_info: reader.read_match(),
         }
     }
 }
 
 pub struct OsuTournamentMatchInfoRequest {
     id: i16,
+    match_id: i32,
 }
 
 impl ClientPacket for OsuTournamentMatchInfoRequest {}
@@ -761,6 +817,7 @@ impl OsuTournamentMatchInfoRequest {
     pub fn new(reader: &mut PacketReader) -> Self {
         Self {
             id: Packets::OsuTournamentMatchInfoRequest as i16,
+            match_id: reader.read_i32(),
         }
     }
 }
@@ -813,6 +870,7 @@ impl OsuToggleBlockNonFriendDms {
 
 pub struct  OsuTournamentJoinMatchChannel {
     id: i16,
+    match_id: i32,
 }
 
 impl ClientPacket for OsuTournamentJoinMatchChannel {}
@@ -820,12 +878,14 @@ impl OsuTournamentJoinMatchChannel {
     pub fn new(reader: &mut PacketReader) -> Self {
         Self {
             id: Packets::OsuTournamentJoinMatchChannel as i16,
+            match_id: reader.read_i32(),
         }
     }
 }
 
 pub struct  OsuTournamentLeaveMatchChannel {
     id: i16,
+    match_id: i32,
 }
 
 impl ClientPacket for OsuTournamentLeaveMatchChannel {}
@@ -833,6 +893,7 @@ impl OsuTournamentLeaveMatchChannel {
     pub fn new(reader: &mut PacketReader) -> Self {
         Self {
             id: Packets::OsuTournamentLeaveMatchChannel as i16,
+            match_id: reader.read_i32(),
         }
     }
 }
@@ -895,6 +956,475 @@ register_packets!(
     pub async fn osu_change_action(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
         let packet = OsuChangeAction::new(reader);
 
+        player.set_status(Status {
+            action: packet.action,
+            info_text: packet.info_text,
+            map_md5: packet.map_md5,
+            mods: packet.mods,
+            mode: packet.mode,
+            map_id: packet.map_id,
+        });
+        if !player.is_tourney_client() {
+            PLAYERS.enqueue_all(&player.stats_packet());
+        }
+
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuLogout, true)]
+    pub async fn osu_logout(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        // the client sends a logout right after logging in when it's
+        // reconnecting, which shouldn't end the new session
+        if player.login_time().elapsed().as_secs() < 1 {
+            return Ok(Vec::new());
+        }
+
+        PLAYERS.remove(player);
+        MATCHES.leave(player);
+        MATCHES.part_lobby(player);
+        CHANNELS.part_all(player);
+        // the player's own session is still online, and nobody saw them if
+        // they're restricted
+        if player.is_visible() {
+            PLAYERS.enqueue_all(&ChoUserLogout::new(player.id()).write());
+        }
+        println!("[cho] {} logged out", player.name());
+
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuRequestStatusUpdate, true)]
+    pub async fn osu_request_status_update(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        Ok(player.stats_packet())
+    }
+
+    #[packet(Packets::OsuPing, true)]
+    pub async fn osu_ping(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuUserStatsRequest, false)]
+    pub async fn osu_user_stats_request(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let packet = OsuUserStatsRequest::new(reader);
+
+        let mut res = Vec::new();
+        for id in packet.user_ids.into_iter().filter(|id| *id != player.id()) {
+            if let Some(other) = PLAYERS.by_id(id) {
+                res.extend(other.stats_packet());
+            }
+        }
+        Ok(res)
+    }
+
+    #[packet(Packets::OsuUserPresenceRequest, false)]
+    pub async fn osu_user_presence_request(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let packet = OsuUserPresenceRequest::new(reader);
+
+        let mut res = Vec::new();
+        for id in packet.user_ids {
+            if let Some(other) = PLAYERS.by_id(id) {
+                res.extend(other.presence_packet());
+            }
+        }
+        Ok(res)
+    }
+
+    #[packet(Packets::OsuBeatmapInfoRequest, false)]
+    pub async fn osu_beatmap_info_request(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let packet = OsuBeatmapInfoRequest::new(reader);
+
+        // maps asked for by filename are answered with their index in the
+        // request, ones asked for by id with -1
+        let maps = packet
+            .filenames
+            .iter()
+            .enumerate()
+            .filter_map(|(i, f)| Some((i as i16, Beatmap::from_filename(f)?)))
+            .chain(
+                packet
+                    .map_ids
+                    .iter()
+                    .filter_map(|id| Some((-1, Beatmap::from_id(*id)?))),
+            );
+
+        let mut infos = Vec::new();
+        for (index, map) in maps {
+            let mut grades = [Grade::N; 4];
+            for (mode, grade) in grades.iter_mut().enumerate() {
+                let mode = GameMode::from_params(mode as u8, Mods::empty());
+                if let Some(best) = Score::best_for(player.id(), &map.md5, mode) {
+                    *grade = best.grade;
+                }
+            }
+
+            infos.push(BeatmapInfo {
+                index,
+                map_id: map.id,
+                set_id: map.set_id,
+                thread_id: 0,
+                status: map.status,
+                grades,
+                map_md5: map.md5,
+            });
+        }
+
+        Ok(ChoBeatmapInfoReply::new(infos).write())
+    }
+
+    #[packet(Packets::OsuSendPublicMessage, false)]
+    pub async fn osu_send_public_message(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let packet = OsuSendPublicMessage::new(reader);
+        let text = packet.message.text.trim();
+
+        let channel = match CHANNELS.get_for(player, &packet.message.recipient) {
+            Some(channel) if channel.has(player) => channel,
+            _ => return Ok(Vec::new()),
+        };
+        if text.is_empty() || player.is_silenced() {
+            return Ok(Vec::new());
+        }
+        let text = match chat_filter::filter(player, channel.name(), text) {
+            Some(text) => text,
+            None => return Ok(Vec::new()),
+        };
+        let text = text.as_str();
+
+        let reply = commands::run(player, Target::Channel(channel.name().to_string()), text).await;
+        match reply {
+            // staff commands stay between the bot and whoever used them
+            Some(reply) if reply.command.hidden() => Ok(bot::message(channel.name(), &reply.text)),
+            Some(reply) => {
+                channel.send(player.name(), player.id(), text);
+                channel.send(bot::name(), bot::id(), &reply.text);
+                Ok(Vec::new())
+            }
+            None => {
+                channel.send(player.name(), player.id(), text);
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    #[packet(Packets::OsuSendPrivateMessage, false)]
+    pub async fn osu_send_private_message(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let packet = OsuSendPrivateMessage::new(reader);
+        let text = packet.message.text.trim();
+        if text.is_empty() || player.is_silenced() {
+            return Ok(Vec::new());
+        }
+        let text = match chat_filter::filter(player, &packet.message.recipient, text) {
+            Some(text) => text,
+            None => return Ok(Vec::new()),
+        };
+        let text = text.as_str();
+
+        if bot::is_bot(&packet.message.recipient) {
+            if let Some(reply) = bot::now_playing(text).await {
+                return Ok(bot::message(player.name(), &reply));
+            }
+            return Ok(match commands::run(player, Target::Private, text).await {
+                Some(reply) => bot::message(player.name(), &reply.text),
+                None if text.starts_with(commands::PREFIX) => bot::message(
+                    player.name(),
+                    &format!("I don't know that command, try {}help", commands::PREFIX),
+                ),
+                None => Vec::new(),
+            });
+        }
+
+        if let Some(target) = PLAYERS.by_name(&packet.message.recipient) {
+            // they couldn't answer anyway
+            if target.is_silenced() {
+                return Ok(ChoTargetIsSilenced::new(target.name()).write());
+            }
+            target.enqueue(&ChoSendMessage::new(player.name(), text, target.name(), player.id()).write());
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuChannelJoin, false)]
+    pub async fn osu_channel_join(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let packet = OsuChannelJoin::new(reader);
+
+        match CHANNELS.get_for(player, &packet.channel_name) {
+            Some(channel) if !channel.is_instance() => channel.join(player),
+            // joined along with whatever they belong to
+            Some(_) => {}
+            None => player.enqueue(&ChoChannelKick::new(packet.channel_name).write()),
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuChannelPart, false)]
+    pub async fn osu_channel_part(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let packet = OsuChannelPart::new(reader);
+
+        if let Some(channel) = CHANNELS.get_for(player, &packet.channel_name) {
+            channel.part(player);
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuUserPresenceRequestAll, false)]
+    pub async fn osu_user_presence_request_all(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut res = Vec::new();
+        for other in PLAYERS.all() {
+            if other.id() != player.id() {
+                res.extend(other.presence_packet());
+            }
+        }
+        Ok(res)
+    }
+
+    #[packet(Packets::OsuJoinLobby, false)]
+    pub async fn osu_join_lobby(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        MATCHES.join_lobby(player);
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuPartLobby, false)]
+    pub async fn osu_part_lobby(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        MATCHES.part_lobby(player);
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuCreateMatch, false)]
+    pub async fn osu_create_match(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let packet = OsuCreateMatch::new(reader);
+        let info = packet.match_info;
+
+        MATCHES.leave(player);
+        let m = MATCHES.create(&info.name, &info.password, player);
+        m.change_settings(&info);
+        m.set_mods(Mods::from_i32(info.mods), info.freemods);
+
+        if let Err(e) = m.join(player, &info.password) {
+            println!("[multiplayer] {} couldn't join their own match: {}", player.name(), e);
+            MATCHES.dispose(m.id());
+            return Ok(ChoMatchJoinFail::write());
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuJoinMatch, false)]
+    pub async fn osu_join_match(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let packet = OsuJoinMatch::new(reader);
+
+        let m = match MATCHES.get(packet.match_id) {
+            Some(m) => m,
+            None => return Ok(ChoMatchJoinFail::write()),
+        };
+        if player.match_id() != Some(m.id()) {
+            MATCHES.leave(player);
+        }
+
+        match m.join(player, &packet.password) {
+            Ok(()) => Ok(Vec::new()),
+            Err(e) => {
+                player.enqueue(&bot::message(player.name(), &format!("Couldn't join the match: {}.", e)));
+                Ok(ChoMatchJoinFail::write())
+            }
+        }
+    }
+
+    #[packet(Packets::OsuPartMatch, false)]
+    pub async fn osu_part_match(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        MATCHES.leave(player);
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuMatchChangeSlot, false)]
+    pub async fn osu_match_change_slot(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let packet = OsuMatchChangeSlot::new(reader);
+
+        if let Some(m) = MATCHES.of(player) {
+            m.change_slot(player, packet.slot as usize);
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuMatchReady, false)]
+    pub async fn osu_match_ready(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if let Some(m) = MATCHES.of(player) {
+            m.set_ready(player, true);
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuMatchNotReady, false)]
+    pub async fn osu_match_not_ready(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if let Some(m) = MATCHES.of(player) {
+            m.set_ready(player, false);
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuMatchLock, false)]
+    pub async fn osu_match_lock(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let packet = OsuMatchLock::new(reader);
+
+        if let Some(m) = MATCHES.of(player).filter(|m| m.is_host(player)) {
+            m.toggle_lock(packet.slot as usize);
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuMatchChangeSettings, false)]
+    pub async fn osu_match_change_settings(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let packet = OsuMatchChangeSettings::new(reader);
+
+        if let Some(m) = MATCHES.of(player).filter(|m| m.is_host(player)) {
+            m.change_settings(&packet.match_info);
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuMatchChangePassword, false)]
+    pub async fn osu_match_change_password(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let packet = OsuMatchChangePassword::new(reader);
+
+        if let Some(m) = MATCHES.of(player).filter(|m| m.is_host(player)) {
+            m.set_password(&packet.match_info.password);
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuMatchChangeMods, false)]
+    pub async fn osu_match_change_mods(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let packet = OsuMatchChangeMods::new(reader);
+
+        if let Some(m) = MATCHES.of(player) {
+            m.change_mods(player, Mods::from_i32(packet.mods));
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuMatchChangeTeam, false)]
+    pub async fn osu_match_change_team(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if let Some(m) = MATCHES.of(player) {
+            m.change_team(player);
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuMatchTransferHost, false)]
+    pub async fn osu_match_transfer_host(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let packet = OsuMatchTransferHost::new(reader);
+
+        if let Some(m) = MATCHES.of(player).filter(|m| m.is_host(player)) {
+            m.transfer_host(packet.slot as usize);
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuMatchHasBeatmap, false)]
+    pub async fn osu_match_has_beatmap(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if let Some(m) = MATCHES.of(player) {
+            m.set_has_map(player, true);
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuMatchNoBeatmap, false)]
+    pub async fn osu_match_no_beatmap(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if let Some(m) = MATCHES.of(player) {
+            m.set_has_map(player, false);
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuMatchInvite, false)]
+    pub async fn osu_match_invite(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let packet = OsuMatchInvite::new(reader);
+
+        let (m, target) = match (MATCHES.of(player), PLAYERS.by_id(packet.user_id)) {
+            (Some(m), Some(target)) => (m, target),
+            _ => return Ok(Vec::new()),
+        };
+        target.enqueue(&m.invite_packet(player.name(), player.id(), target.name()));
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuMatchStart, false)]
+    pub async fn osu_match_start(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if let Some(m) = MATCHES.of(player).filter(|m| m.is_host(player)) {
+            if let Err(e) = m.start() {
+                return Ok(bot::message(player.name(), &format!("Couldn't start: {}.", e)));
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuMatchLoadComplete, false)]
+    pub async fn osu_match_load_complete(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if let Some(m) = MATCHES.of(player) {
+            m.load_complete(player);
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuMatchScoreUpdate, false)]
+    pub async fn osu_match_score_update(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let packet = OsuMatchScoreUpdate::new(reader);
+
+        if let Some(m) = MATCHES.of(player) {
+            m.score_update(player, packet.data);
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuMatchSkipRequest, false)]
+    pub async fn osu_match_skip_request(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if let Some(m) = MATCHES.of(player) {
+            m.skip(player);
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuMatchFailed, false)]
+    pub async fn osu_match_failed(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if let Some(m) = MATCHES.of(player) {
+            m.player_failed(player);
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuMatchComplete, false)]
+    pub async fn osu_match_complete(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if let Some(m) = MATCHES.of(player) {
+            m.complete(player);
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuTournamentMatchInfoRequest, false)]
+    pub async fn osu_tournament_match_info_request(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let packet = OsuTournamentMatchInfoRequest::new(reader);
+
+        match MATCHES.get(packet.match_id) {
+            Some(m) if player.is_tourney_client() => Ok(ChoUpdateMatch::new(m.info(), true).write()),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    #[packet(Packets::OsuTournamentJoinMatchChannel, false)]
+    pub async fn osu_tournament_join_match_channel(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let packet = OsuTournamentJoinMatchChannel::new(reader);
+
+        match MATCHES.get(packet.match_id) {
+            Some(m) if player.is_tourney_client() => m.watch(player),
+            _ => {}
+        }
+        Ok(Vec::new())
+    }
+
+    #[packet(Packets::OsuTournamentLeaveMatchChannel, false)]
+    pub async fn osu_tournament_leave_match_channel(player: &mut Player, reader: &mut PacketReader) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let packet = OsuTournamentLeaveMatchChannel::new(reader);
+
+        if let Some(m) = MATCHES.get(packet.match_id) {
+            m.unwatch(player);
+        }
         Ok(Vec::new())
     }
 );
@@ -903,4 +1433,623 @@ register_packets!(
 // -= Server Packets =-
 // -===========-
 
+// id 5
+pub struct ChoUserId {
+    /// The user's id, or a negative login failure reason.
+    pub user_id: i32,
+}
+
+impl ChoUserId {
+    pub fn new(user_id: i32) -> Self {
+        Self { user_id }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(Packets::ChoUserId as i16, &write_i32(self.user_id))
+    }
+}
+
+// id 11
+pub struct ChoUserStats {
+    pub user_id: i32,
+    pub action: u8,
+    pub info_text: String,
+    pub map_md5: String,
+    pub mods: i32,
+    pub mode: u8,
+    pub map_id: i32,
+    pub ranked_score: i64,
+    /// 0 to 1.
+    pub accuracy: f32,
+    pub play_count: i32,
+    pub total_score: i64,
+    pub global_rank: i32,
+    pub pp: i16,
+}
+
+impl ChoUserStats {
+    pub fn write(&self) -> Vec<u8> {
+        let mut r = Vec::new();
+        r.extend(write_i32(self.user_id));
+        r.extend(write_u8(self.action));
+        r.extend(write_string(&self.info_text));
+        r.extend(write_string(&self.map_md5));
+        r.extend(write_i32(self.mods));
+        r.extend(write_u8(self.mode));
+        r.extend(write_i32(self.map_id));
+        r.extend(write_i64(self.ranked_score));
+        r.extend(write_f32(self.accuracy));
+        r.extend(write_i32(self.play_count));
+        r.extend(write_i64(self.total_score));
+        r.extend(write_i32(self.global_rank));
+        r.extend(write_i16(self.pp));
+        write_packet(Packets::ChoUserStats as i16, &r)
+    }
+}
+
+// id 12
+pub struct ChoUserLogout {
+    pub user_id: i32,
+}
+
+impl ChoUserLogout {
+    pub fn new(user_id: i32) -> Self {
+        Self { user_id }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        let mut r = Vec::new();
+        r.extend(write_i32(self.user_id));
+        r.extend(write_u8(0));
+        write_packet(Packets::ChoUserLogout as i16, &r)
+    }
+}
+
+// id 24
+pub struct ChoNotification {
+    pub message: String,
+}
+
+impl ChoNotification {
+    pub fn new<T: Into<String>>(message: T) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(Packets::ChoNotification as i16, &write_string(&self.message))
+    }
+}
+
+pub struct BeatmapInfo {
+    /// Index of the filename the client asked about, -1 when asked by id.
+    pub index: i16,
+    pub map_id: i32,
+    pub set_id: i32,
+    pub thread_id: i32,
+    pub status: RankedStatus,
+    /// The player's best grades in osu!, taiko, catch and mania.
+    pub grades: [Grade; 4],
+    pub map_md5: String,
+}
+
+// id 69
+pub struct ChoBeatmapInfoReply {
+    pub maps: Vec<BeatmapInfo>,
+}
+
+impl ChoBeatmapInfoReply {
+    pub fn new(maps: Vec<BeatmapInfo>) -> Self {
+        Self { maps }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        let mut r = Vec::new();
+        r.extend(write_i32(self.maps.len() as i32));
+        for map in &self.maps {
+            r.extend(write_i16(map.index));
+            r.extend(write_i32(map.map_id));
+            r.extend(write_i32(map.set_id));
+            r.extend(write_i32(map.thread_id));
+            r.extend(write_u8((map.status as i32).max(0) as u8));
+            // the client reads these as osu!, catch, taiko, mania
+            for mode in [0, 2, 1, 3] {
+                r.extend(write_u8(map.grades[mode].client_id()));
+            }
+            r.extend(write_string(&map.map_md5));
+        }
+        write_packet(Packets::ChoBeatmapInfoReply as i16, &r)
+    }
+}
+
+// id 71
+pub struct ChoPrivileges {
+    pub privileges: i32,
+}
+
+impl ChoPrivileges {
+    pub fn new(privileges: i32) -> Self {
+        Self { privileges }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(Packets::ChoPrivileges as i16, &write_i32(self.privileges))
+    }
+}
+
+// id 72
+pub struct ChoFriendsList {
+    pub user_ids: Vec<i32>,
+}
+
+impl ChoFriendsList {
+    pub fn new(user_ids: Vec<i32>) -> Self {
+        Self { user_ids }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(
+            Packets::ChoFriendsList as i16,
+            &write_i32_list(self.user_ids.clone()),
+        )
+    }
+}
+
+// id 75
+pub struct ChoProtocolVersion {
+    pub version: i32,
+}
+
+impl ChoProtocolVersion {
+    pub fn new(version: i32) -> Self {
+        Self { version }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(Packets::ChoProtocolVersion as i16, &write_i32(self.version))
+    }
+}
+
+// id 83
+pub struct ChoUserPresence {
+    pub user_id: i32,
+    pub name: String,
+    /// Hours from UTC.
+    pub utc_offset: i8,
+    pub country_code: u8,
+    /// Client privileges in the low 5 bits, the mode in the top 3.
+    pub bancho_privileges: u8,
+    pub longitude: f32,
+    pub latitude: f32,
+    pub global_rank: i32,
+}
+
+impl ChoUserPresence {
+    pub fn write(&self) -> Vec<u8> {
+        let mut r = Vec::new();
+        r.extend(write_i32(self.user_id));
+        r.extend(write_string(&self.name));
+        r.extend(write_u8((self.utc_offset as i16 + 24) as u8));
+        r.extend(write_u8(self.country_code));
+        r.extend(write_u8(self.bancho_privileges));
+        r.extend(write_f32(self.longitude));
+        r.extend(write_f32(self.latitude));
+        r.extend(write_i32(self.global_rank));
+        write_packet(Packets::ChoUserPresence as i16, &r)
+    }
+}
+
+// id 86
+pub struct ChoRestart {
+    pub ms_delay: i32,
+}
+
+impl ChoRestart {
+    pub fn new(ms_delay: i32) -> Self {
+        Self { ms_delay }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(Packets::ChoRestart as i16, &write_i32(self.ms_delay))
+    }
+}
+
+// id 89
+pub struct ChoChannelInfoEnd;
+
+impl ChoChannelInfoEnd {
+    pub fn write() -> Vec<u8> {
+        write_packet(Packets::ChoChannelInfoEnd as i16, &[])
+    }
+}
+
+// id 92
+pub struct ChoSilenceEnd {
+    /// Seconds until the silence ends, 0 when not silenced.
+    pub seconds: i32,
+}
+
+impl ChoSilenceEnd {
+    pub fn new(seconds: i32) -> Self {
+        Self { seconds }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(Packets::ChoSilenceEnd as i16, &write_i32(self.seconds))
+    }
+}
+
+// id 94
+pub struct ChoUserSilenced {
+    pub user_id: i32,
+}
+
+impl ChoUserSilenced {
+    pub fn new(user_id: i32) -> Self {
+        Self { user_id }
+    }
+
+    /// Tells clients to clear the user's messages from their chat.
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(Packets::ChoUserSilenced as i16, &write_i32(self.user_id))
+    }
+}
+
+// id 101
+pub struct ChoTargetIsSilenced {
+    pub name: String,
+}
+
+impl ChoTargetIsSilenced {
+    pub fn new<T: Into<String>>(name: T) -> Self {
+        Self { name: name.into() }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(
+            Packets::ChoTargetIsSilenced as i16,
+            &write_message("", "", &self.name, 0),
+        )
+    }
+}
+
+// id 104
+pub struct ChoAccountRestricted;
+
+impl ChoAccountRestricted {
+    pub fn write() -> Vec<u8> {
+        write_packet(Packets::ChoAccountRestricted as i16, &[])
+    }
+}
+
+// id 76
+pub struct ChoMainMenuIcon {
+    pub image_url: String,
+    pub click_url: String,
+}
+
+impl ChoMainMenuIcon {
+    pub fn new<T: Into<String>>(image_url: T, click_url: T) -> Self {
+        Self {
+            image_url: image_url.into(),
+            click_url: click_url.into(),
+        }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(
+            Packets::ChoMainMenuIcon as i16,
+            &write_string(&format!("{}|{}", self.image_url, self.click_url)),
+        )
+    }
+}
+
+// id 7
+pub struct ChoSendMessage {
+    pub sender: String,
+    pub text: String,
+    /// The channel, or the name of the player for private messages.
+    pub recipient: String,
+    pub sender_id: i32,
+}
+
+impl ChoSendMessage {
+    pub fn new<T: Into<String>>(sender: T, text: T, recipient: T, sender_id: i32) -> Self {
+        Self {
+            sender: sender.into(),
+            text: text.into(),
+            recipient: recipient.into(),
+            sender_id,
+        }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(
+            Packets::ChoSendMessage as i16,
+            &write_message(&self.sender, &self.text, &self.recipient, self.sender_id),
+        )
+    }
+}
+
+// id 64
+pub struct ChoChannelJoinSuccess {
+    pub name: String,
+}
+
+impl ChoChannelJoinSuccess {
+    pub fn new<T: Into<String>>(name: T) -> Self {
+        Self { name: name.into() }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(Packets::ChoChannelJoinSuccess as i16, &write_string(&self.name))
+    }
+}
+
+// id 65
+pub struct ChoChannelInfo {
+    pub name: String,
+    pub topic: String,
+    pub player_count: u16,
+}
+
+impl ChoChannelInfo {
+    pub fn new<T: Into<String>>(name: T, topic: T, player_count: u16) -> Self {
+        Self {
+            name: name.into(),
+            topic: topic.into(),
+            player_count,
+        }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(
+            Packets::ChoChannelInfo as i16,
+            &write_channel(&self.name, &self.topic, self.player_count),
+        )
+    }
+}
+
+// id 66
+pub struct ChoChannelKick {
+    pub name: String,
+}
+
+impl ChoChannelKick {
+    pub fn new<T: Into<String>>(name: T) -> Self {
+        Self { name: name.into() }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(Packets::ChoChannelKick as i16, &write_string(&self.name))
+    }
+}
+
+// id 26
+pub struct ChoUpdateMatch {
+    pub info: MatchInfo,
+    pub send_password: bool,
+}
+
+impl ChoUpdateMatch {
+    pub fn new(info: MatchInfo, send_password: bool) -> Self {
+        Self {
+            info,
+            send_password,
+        }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(
+            Packets::ChoUpdateMatch as i16,
+            &write_match(&self.info, self.send_password),
+        )
+    }
+}
+
+// id 27
+pub struct ChoNewMatch {
+    pub info: MatchInfo,
+}
+
+impl ChoNewMatch {
+    pub fn new(info: MatchInfo) -> Self {
+        Self { info }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(Packets::ChoNewMatch as i16, &write_match(&self.info, false))
+    }
+}
+
+// id 28
+pub struct ChoDisposeMatch {
+    pub match_id: i32,
+}
+
+impl ChoDisposeMatch {
+    pub fn new(match_id: i32) -> Self {
+        Self { match_id }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(Packets::ChoDisposeMatch as i16, &write_i32(self.match_id))
+    }
+}
+
+// id 36
+pub struct ChoMatchJoinSuccess {
+    pub info: MatchInfo,
+}
+
+impl ChoMatchJoinSuccess {
+    pub fn new(info: MatchInfo) -> Self {
+        Self { info }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(Packets::ChoMatchJoinSuccess as i16, &write_match(&self.info, true))
+    }
+}
+
+// id 37
+pub struct ChoMatchJoinFail;
+
+impl ChoMatchJoinFail {
+    pub fn write() -> Vec<u8> {
+        write_packet(Packets::ChoMatchJoinFail as i16, &[])
+    }
+}
+
+// id 46
+pub struct ChoMatchStart {
+    pub info: MatchInfo,
+}
+
+impl ChoMatchStart {
+    pub fn new(info: MatchInfo) -> Self {
+        Self { info }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(Packets::ChoMatchStart as i16, &write_match(&self.info, true))
+    }
+}
+
+// id 48
+pub struct ChoMatchScoreUpdate {
+    /// A score frame, with the slot of whoever it's from as its id.
+    pub data: Vec<u8>,
+}
+
+impl ChoMatchScoreUpdate {
+    pub fn new(data: Vec<u8>) -> Self {
+        Self { data }
+    }
 
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(Packets::ChoMatchScoreUpdate as i16, &self.data)
+    }
+}
+
+// id 50
+pub struct ChoMatchTransferHost;
+
+impl ChoMatchTransferHost {
+    pub fn write() -> Vec<u8> {
+        write_packet(Packets::ChoMatchTransferHost as i16, &[])
+    }
+}
+
+// id 53
+pub struct ChoMatchAllPlayersLoaded;
+
+impl ChoMatchAllPlayersLoaded {
+    pub fn write() -> Vec<u8> {
+        write_packet(Packets::ChoMatchAllPlayersLoaded as i16, &[])
+    }
+}
+
+// id 57
+pub struct ChoMatchPlayerFailed {
+    pub slot: i32,
+}
+
+impl ChoMatchPlayerFailed {
+    pub fn new(slot: i32) -> Self {
+        Self { slot }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(Packets::ChoMatchPlayerFailed as i16, &write_i32(self.slot))
+    }
+}
+
+// id 58
+pub struct ChoMatchComplete;
+
+impl ChoMatchComplete {
+    pub fn write() -> Vec<u8> {
+        write_packet(Packets::ChoMatchComplete as i16, &[])
+    }
+}
+
+// id 61
+pub struct ChoMatchSkip;
+
+impl ChoMatchSkip {
+    pub fn write() -> Vec<u8> {
+        write_packet(Packets::ChoMatchSkip as i16, &[])
+    }
+}
+
+// id 81
+pub struct ChoMatchPlayerSkipped {
+    pub user_id: i32,
+}
+
+impl ChoMatchPlayerSkipped {
+    pub fn new(user_id: i32) -> Self {
+        Self { user_id }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(Packets::ChoMatchPlayerSkipped as i16, &write_i32(self.user_id))
+    }
+}
+
+// id 88
+pub struct ChoMatchInvite {
+    pub sender: String,
+    pub text: String,
+    pub recipient: String,
+    pub sender_id: i32,
+}
+
+impl ChoMatchInvite {
+    pub fn new<T: Into<String>>(sender: T, text: T, recipient: T, sender_id: i32) -> Self {
+        Self {
+            sender: sender.into(),
+            text: text.into(),
+            recipient: recipient.into(),
+            sender_id,
+        }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(
+            Packets::ChoMatchInvite as i16,
+            &write_message(&self.sender, &self.text, &self.recipient, self.sender_id),
+        )
+    }
+}
+
+// id 91
+pub struct ChoMatchChangePassword {
+    pub password: String,
+}
+
+impl ChoMatchChangePassword {
+    pub fn new<T: Into<String>>(password: T) -> Self {
+        Self {
+            password: password.into(),
+        }
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        write_packet(
+            Packets::ChoMatchChangePassword as i16,
+            &write_string(&self.password),
+        )
+    }
+}
+
+// id 106
+pub struct ChoMatchAbort;
+
+impl ChoMatchAbort {
+    pub fn write() -> Vec<u8> {
+        write_packet(Packets::ChoMatchAbort as i16, &[])
+    }
+}
@@ -0,0 +1,172 @@
+use super::curve::SliderPath;
+use super::{AdjustedDifficulty, ScoreState, StrainSkill};
+use crate::constants::mods::Mods;
+use crate::utils::osu::beatmap::{BeatmapFile, HitObjectKind};
+
+const STAR_SCALING_FACTOR: f64 = 0.153;
+const NORMALISED_HITOBJECT_RADIUS: f64 = 41.0;
+const ABSOLUTE_PLAYER_POSITIONING_ERROR: f64 = 16.0;
+const DIRECTION_CHANGE_BONUS: f64 = 21.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difficulty {
+    pub stars: f64,
+    pub ar: f64,
+    /// Fruits and droplets, everything that counts towards combo.
+    pub max_combo: u32,
+}
+
+/// The fruits and droplets of the map in time order, as (time, x). Tiny
+/// droplets and bananas don't affect difficulty.
+fn palpable_objects(map: &BeatmapFile) -> Vec<(f64, f64)> {
+    let mut objects = Vec::new();
+
+    for h in &map.hit_objects {
+        match &h.kind {
+            HitObjectKind::Slider(slider) => {
+                let path = SliderPath::new((h.x as f64, h.y as f64), slider);
+                let duration = map.slide_duration(h.time, slider);
+                let ticks = map.slide_tick_distances(h.time, slider);
+
+                objects.push((h.time, h.x as f64));
+                for slide in 0..slider.slides {
+                    let start = h.time + duration * slide as f64;
+                    let reverse = slide % 2 == 1;
+
+                    for &d in &ticks {
+                        let progress = d / path.length;
+                        let position = if reverse { path.length - d } else { d };
+                        objects.push((start + duration * progress, path.position_at(position).0));
+                    }
+
+                    let end = if reverse { 0.0 } else { path.length };
+                    objects.push((start + duration, path.position_at(end).0));
+                }
+            }
+            HitObjectKind::Spinner { .. } => {}
+            _ => objects.push((h.time, h.x as f64)),
+        }
+    }
+
+    objects.sort_by(|a, b| a.0.total_cmp(&b.0));
+    objects
+}
+
+pub fn difficulty(map: &BeatmapFile, mods: Mods) -> Difficulty {
+    let adjusted = AdjustedDifficulty::new(map, mods);
+    let objects = palpable_objects(map);
+
+    let catcher_width = 106.75 * (1.0 - 0.7 * (adjusted.cs - 5.0) / 5.0);
+    let half_catcher_width = catcher_width / 2.0 * (1.0 - (adjusted.cs - 5.5).max(0.0) * 0.0625);
+    let scaling = NORMALISED_HITOBJECT_RADIUS / half_catcher_width;
+
+    let mut movement = StrainSkill::new(900.0, 0.2, 0.94);
+    let mut last_player_position: Option<f64> = None;
+    let mut last_distance_moved = 0.0_f64;
+    let mut last_strain_time = 0.0_f64;
+
+    for pair in objects.windows(2) {
+        let (last_time, last_x) = pair[0];
+        let (time, x) = pair[1];
+        let time = time / adjusted.clock_rate;
+        let strain_time = (time - last_time / adjusted.clock_rate).max(40.0);
+
+        let position = x * scaling;
+        let last_position = last_player_position.unwrap_or(last_x * scaling);
+
+        let leeway = NORMALISED_HITOBJECT_RADIUS - ABSOLUTE_PLAYER_POSITIONING_ERROR;
+        let player_position = last_position.clamp(position - leeway, position + leeway);
+        let distance_moved = player_position - last_position;
+
+        let weighted_strain_time = strain_time + 13.0 + 3.0 / adjusted.clock_rate;
+
+        let mut distance_addition = distance_moved.abs().powf(1.3) / 510.0;
+        let sqrt_strain = weighted_strain_time.sqrt();
+
+        if distance_moved.abs() > 0.1 {
+            if last_distance_moved.abs() > 0.1
+                && distance_moved.signum() != last_distance_moved.signum()
+            {
+                let bonus_factor = distance_moved.abs().min(50.0) / 50.0;
+                let anti_flow_factor = (last_distance_moved.abs().min(70.0) / 70.0).max(0.38);
+
+                distance_addition += DIRECTION_CHANGE_BONUS / (last_strain_time + 16.0).sqrt()
+                    * bonus_factor
+                    * anti_flow_factor
+                    * (1.0 - (weighted_strain_time / 1000.0).powi(3)).max(0.0);
+            }
+
+            distance_addition += 12.5 * distance_moved.abs().min(NORMALISED_HITOBJECT_RADIUS * 2.0)
+                / (NORMALISED_HITOBJECT_RADIUS * 6.0)
+                / sqrt_strain;
+        }
+
+        last_player_position = Some(player_position);
+        last_distance_moved = distance_moved;
+        last_strain_time = strain_time;
+
+        movement.process(time, distance_addition / weighted_strain_time);
+    }
+
+    Difficulty {
+        stars: movement.difficulty_value().sqrt() * STAR_SCALING_FACTOR,
+        ar: adjusted.ar,
+        max_combo: objects.len() as u32,
+    }
+}
+
+/// In catch the judgements are sent as 300s for fruits, 100s for droplets,
+/// 50s for tiny droplets and katus for missed tiny droplets.
+pub fn performance(diff: &Difficulty, mods: Mods, state: &ScoreState) -> f64 {
+    let combo_hits = (state.n300 + state.n100 + state.nmiss) as f64;
+    let total = combo_hits + (state.n50 + state.nkatu) as f64;
+    if total == 0.0 {
+        return 0.0;
+    }
+    let accuracy = (state.n300 + state.n100 + state.n50) as f64 / total;
+
+    let mut value = (5.0 * (diff.stars / 0.0049).max(1.0) - 4.0).powi(2) / 100_000.0;
+
+    let length_bonus = 0.95
+        + 0.3 * (combo_hits / 2500.0).min(1.0)
+        + if combo_hits > 2500.0 {
+            (combo_hits / 2500.0).log10() * 0.475
+        } else {
+            0.0
+        };
+    value *= length_bonus;
+    value *= 0.97_f64.powi(state.nmiss as i32);
+
+    if diff.max_combo > 0 {
+        value *= ((state.max_combo as f64).powf(0.8) / (diff.max_combo as f64).powf(0.8)).min(1.0);
+    }
+
+    let mut ar_factor = 1.0;
+    if diff.ar > 9.0 {
+        ar_factor += 0.1 * (diff.ar - 9.0);
+    }
+    if diff.ar > 10.0 {
+        ar_factor += 0.1 * (diff.ar - 10.0);
+    } else if diff.ar < 8.0 {
+        ar_factor += 0.025 * (8.0 - diff.ar);
+    }
+    value *= ar_factor;
+
+    if mods.contains(Mods::HIDDEN) {
+        value *= if diff.ar <= 10.0 {
+            1.05 + 0.075 * (10.0 - diff.ar)
+        } else {
+            1.01 + 0.04 * (11.0 - diff.ar.min(11.0))
+        };
+    }
+    if mods.contains(Mods::FLASHLIGHT) {
+        value *= 1.35 * length_bonus;
+    }
+
+    value *= accuracy.powf(5.5);
+
+    if mods.contains(Mods::NOFAIL) {
+        value *= 0.9;
+    }
+    value
+}
@@ -0,0 +1,205 @@
+use crate::utils::osu::beatmap::{CurveType, Slider};
+
+pub type Point = (f64, f64);
+
+/// Points each bezier, catmull or circular section is approximated with.
+const SECTION_POINTS: usize = 50;
+
+fn lerp(a: Point, b: Point, t: f64) -> Point {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt()
+}
+
+fn bezier(control: &[Point]) -> Vec<Point> {
+    if control.len() < 3 {
+        return control.to_vec();
+    }
+
+    (0..=SECTION_POINTS)
+        .map(|i| {
+            let t = i as f64 / SECTION_POINTS as f64;
+            let mut points = control.to_vec();
+            for n in (1..points.len()).rev() {
+                for j in 0..n {
+                    points[j] = lerp(points[j], points[j + 1], t);
+                }
+            }
+            points[0]
+        })
+        .collect()
+}
+
+fn catmull(control: &[Point]) -> Vec<Point> {
+    let mut path = Vec::new();
+
+    for i in 0..control.len().saturating_sub(1) {
+        let v2 = control[i];
+        let v1 = if i > 0 { control[i - 1] } else { v2 };
+        let v3 = control[i + 1];
+        let v4 = control
+            .get(i + 2)
+            .copied()
+            .unwrap_or((2.0 * v3.0 - v2.0, 2.0 * v3.1 - v2.1));
+
+        for step in 0..SECTION_POINTS {
+            let t = step as f64 / SECTION_POINTS as f64;
+            let (t2, t3) = (t * t, t * t * t);
+            let at = |a: f64, b: f64, c: f64, d: f64| {
+                0.5 * (2.0 * b
+                    + (-a + c) * t
+                    + (2.0 * a - 5.0 * b + 4.0 * c - d) * t2
+                    + (-a + 3.0 * b - 3.0 * c + d) * t3)
+            };
+            path.push((at(v1.0, v2.0, v3.0, v4.0), at(v1.1, v2.1, v3.1, v4.1)));
+        }
+    }
+
+    path.extend(control.last());
+    path
+}
+
+/// Arc through three points, or `None` when they're (nearly) on a line.
+fn circular_arc(a: Point, b: Point, c: Point) -> Option<Vec<Point>> {
+    let d = 2.0 * (a.0 * (b.1 - c.1) + b.0 * (c.1 - a.1) + c.0 * (a.1 - b.1));
+    if d.abs() < 1e-3 {
+        return None;
+    }
+
+    let norm = |p: Point| p.0 * p.0 + p.1 * p.1;
+    let center = (
+        (norm(a) * (b.1 - c.1) + norm(b) * (c.1 - a.1) + norm(c) * (a.1 - b.1)) / d,
+        (norm(a) * (c.0 - b.0) + norm(b) * (a.0 - c.0) + norm(c) * (b.0 - a.0)) / d,
+    );
+    let radius = distance(a, center);
+
+    let angle = |p: Point| (p.1 - center.1).atan2(p.0 - center.0);
+    let start = angle(a);
+    let mut end = angle(c);
+
+    // go the way round that passes through the middle point
+    let clockwise = (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0) < 0.0;
+    let tau = std::f64::consts::TAU;
+    if clockwise {
+        while end > start {
+            end -= tau;
+        }
+    } else {
+        while end < start {
+            end += tau;
+        }
+    }
+
+    Some(
+        (0..=SECTION_POINTS)
+            .map(|i| {
+                let theta = start + (end - start) * i as f64 / SECTION_POINTS as f64;
+                (
+                    center.0 + radius * theta.cos(),
+                    center.1 + radius * theta.sin(),
+                )
+            })
+            .collect(),
+    )
+}
+
+/// A slider's path approximated as a polyline, cut or extended to the length
+/// the map specifies.
+pub struct SliderPath {
+    points: Vec<Point>,
+    /// Distance along the path at each point.
+    cumulative: Vec<f64>,
+    pub length: f64,
+}
+
+impl SliderPath {
+    pub fn new(head: Point, slider: &Slider) -> Self {
+        let mut control = vec![head];
+        control.extend(
+            slider
+                .control_points
+                .iter()
+                .map(|&(x, y)| (x as f64, y as f64)),
+        );
+
+        let mut points = match slider.curve_type {
+            CurveType::Linear => control,
+            CurveType::Catmull => catmull(&control),
+            CurveType::PerfectCircle if control.len() == 3 => {
+                circular_arc(control[0], control[1], control[2]).unwrap_or_else(|| bezier(&control))
+            }
+            CurveType::Bezier | CurveType::PerfectCircle => {
+                // repeated control points split the curve into sections
+                let mut points = Vec::new();
+                let mut section_start = 0;
+                for i in 1..=control.len() {
+                    if i == control.len() || control[i] == control[i - 1] {
+                        points.extend(bezier(&control[section_start..i]));
+                        section_start = i;
+                    }
+                }
+                points
+            }
+        };
+        points.dedup();
+        if points.is_empty() {
+            points.push(head);
+        }
+
+        let mut cumulative = vec![0.0];
+        for pair in points.windows(2) {
+            cumulative.push(cumulative.last().unwrap() + distance(pair[0], pair[1]));
+        }
+
+        let mut path = Self {
+            points,
+            cumulative,
+            length: slider.length,
+        };
+
+        let calculated = *path.cumulative.last().unwrap();
+        if path.length <= 0.0 {
+            path.length = calculated;
+        } else if path.length > calculated && path.points.len() >= 2 {
+            // the path is extended in a straight line past its last point
+            let n = path.points.len();
+            let (a, b) = (path.points[n - 2], path.points[n - 1]);
+            let segment = distance(a, b);
+            if segment > 0.0 {
+                let t = (path.length - calculated) / segment;
+                path.points
+                    .push((b.0 + (b.0 - a.0) * t, b.1 + (b.1 - a.1) * t));
+                path.cumulative.push(path.length);
+            }
+        }
+
+        path
+    }
+
+    /// Position `d` osu!pixels along the path.
+    pub fn position_at(&self, d: f64) -> Point {
+        let d = d.clamp(0.0, self.length);
+
+        let i = self.cumulative.partition_point(|&c| c < d);
+        if i == 0 {
+            return self.points[0];
+        }
+        if i >= self.points.len() {
+            return *self.points.last().unwrap();
+        }
+
+        let (start, end) = (self.cumulative[i - 1], self.cumulative[i]);
+        let t = if end > start {
+            (d - start) / (end - start)
+        } else {
+            0.0
+        };
+        lerp(self.points[i - 1], self.points[i], t)
+    }
+
+    pub fn end(&self) -> Point {
+        self.position_at(self.length)
+    }
+}
@@ -0,0 +1,197 @@
+use super::{ScoreState, StrainSkill};
+use crate::constants::mods::Mods;
+use crate::utils::osu::beatmap::{BeatmapFile, HitObjectKind};
+
+const STAR_SCALING_FACTOR: f64 = 0.018;
+const INDIVIDUAL_DECAY_BASE: f64 = 0.125;
+const OVERALL_DECAY_BASE: f64 = 0.3;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difficulty {
+    pub stars: f64,
+    pub keys: u32,
+    /// Great hit window in ms, with the legacy mod adjustments.
+    pub great_hit_window: f64,
+    pub n_objects: u32,
+}
+
+/// Key count of the map, or the one a standard map converts to. Converts are
+/// laid out by x position rather than the client's pattern generator.
+fn key_count(map: &BeatmapFile, mods: Mods) -> u32 {
+    if map.general.mode == 3 {
+        return (map.difficulty.cs.round() as u32).max(1);
+    }
+
+    let key_mods = [
+        (Mods::KEY1, 1),
+        (Mods::KEY2, 2),
+        (Mods::KEY3, 3),
+        (Mods::KEY4, 4),
+        (Mods::KEY5, 5),
+        (Mods::KEY6, 6),
+        (Mods::KEY7, 7),
+        (Mods::KEY8, 8),
+        (Mods::KEY9, 9),
+    ];
+    if let Some((_, keys)) = key_mods.iter().find(|(m, _)| mods.contains(*m)) {
+        return *keys;
+    }
+
+    let counts = map.object_counts();
+    let rounded_cs = map.difficulty.cs.round();
+    let rounded_od = map.difficulty.od.round();
+    let long_ratio = if counts.total() > 0 {
+        (counts.sliders + counts.spinners) as f64 / counts.total() as f64
+    } else {
+        0.0
+    };
+
+    if long_ratio < 0.2 {
+        7
+    } else if long_ratio < 0.3 || rounded_cs >= 5.0 {
+        if rounded_od > 5.0 {
+            7
+        } else {
+            6
+        }
+    } else if long_ratio > 0.6 {
+        if rounded_od > 4.0 {
+            5
+        } else {
+            4
+        }
+    } else {
+        (rounded_od as u32 + 1).clamp(4, 7)
+    }
+}
+
+fn great_hit_window(map: &BeatmapFile, mods: Mods) -> f64 {
+    let mut window = if map.general.mode == 3 {
+        34.0 + 3.0 * (10.0 - map.difficulty.od).clamp(0.0, 10.0)
+    } else if map.difficulty.od.round() > 4.0 {
+        34.0
+    } else {
+        47.0
+    };
+
+    if mods.contains(Mods::HARDROCK) {
+        window /= 1.4;
+    } else if mods.contains(Mods::EASY) {
+        window *= 1.4;
+    }
+
+    // stable scales the window the wrong way round for rate changes, which
+    // is what ranked pp has always used
+    if mods.contains(Mods::DOUBLETIME) {
+        window *= 1.5;
+    } else if mods.contains(Mods::HALFTIME) {
+        window *= 0.75;
+    }
+
+    window.trunc()
+}
+
+pub fn difficulty(map: &BeatmapFile, mods: Mods) -> Difficulty {
+    let keys = key_count(map, mods);
+    let clock_rate = mods.clock_rate();
+
+    // (start, end, column)
+    let mut notes: Vec<(f64, f64, usize)> = map
+        .hit_objects
+        .iter()
+        .map(|h| {
+            let column =
+                ((h.x as f64 * keys as f64 / 512.0).floor() as usize).min(keys as usize - 1);
+            let end = match h.kind {
+                HitObjectKind::Circle => h.time,
+                _ => map.end_time(h),
+            };
+            (h.time / clock_rate, end / clock_rate, column)
+        })
+        .collect();
+    notes.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut strain = StrainSkill::new(1.0, 1.0, 0.9);
+    let mut hold_end_times = vec![0.0; keys as usize];
+    let mut individual_strains = vec![0.0; keys as usize];
+    let mut overall_strain = 1.0;
+
+    for i in 1..notes.len() {
+        let (start, end, column) = notes[i];
+        let delta_time = start - notes[i - 1].0;
+
+        let mut hold_factor = 1.0;
+        let mut hold_addition = 0.0;
+        for &hold_end in &hold_end_times {
+            // releasing in the middle of another hold is harder
+            if start < hold_end && end > hold_end {
+                hold_addition = 1.0;
+            }
+            if end == hold_end {
+                hold_addition = 0.0;
+            }
+            if hold_end > end {
+                hold_factor = 1.25;
+            }
+        }
+        hold_end_times[column] = end;
+
+        let decay = |base: f64| base.powf(delta_time / 1000.0);
+        individual_strains[column] =
+            individual_strains[column] * decay(INDIVIDUAL_DECAY_BASE) + 2.0 * hold_factor;
+        overall_strain =
+            overall_strain * decay(OVERALL_DECAY_BASE) + (1.0 + hold_addition) * hold_factor;
+
+        let target = individual_strains[column] + overall_strain;
+        strain.process(start, target - strain.current_strain());
+    }
+
+    Difficulty {
+        stars: strain.difficulty_value() * STAR_SCALING_FACTOR,
+        keys,
+        great_hit_window: great_hit_window(map, mods),
+        n_objects: notes.len() as u32,
+    }
+}
+
+/// Unlike the other modes mania pp comes from score rather than accuracy,
+/// `state.score` has to be set.
+pub fn performance(diff: &Difficulty, mods: Mods, state: &ScoreState) -> f64 {
+    let mut score_multiplier = 1.0;
+    for m in [Mods::EASY, Mods::NOFAIL, Mods::HALFTIME] {
+        if mods.contains(m) {
+            score_multiplier *= 0.5;
+        }
+    }
+    let score = state.score as f64 / score_multiplier;
+    let total_hits = diff.n_objects as f64;
+
+    let mut strain_value = (5.0 * (diff.stars / 0.2).max(1.0) - 4.0).powf(2.2) / 135.0;
+    strain_value *= 1.0 + 0.1 * (total_hits / 1500.0).min(1.0);
+    strain_value *= match score {
+        s if s <= 500_000.0 => 0.0,
+        s if s <= 600_000.0 => (s - 500_000.0) / 100_000.0 * 0.3,
+        s if s <= 700_000.0 => 0.3 + (s - 600_000.0) / 100_000.0 * 0.25,
+        s if s <= 800_000.0 => 0.55 + (s - 700_000.0) / 100_000.0 * 0.2,
+        s if s <= 900_000.0 => 0.75 + (s - 800_000.0) / 100_000.0 * 0.15,
+        s => 0.9 + (s - 900_000.0) / 100_000.0 * 0.1,
+    };
+
+    let acc_value = if diff.great_hit_window <= 0.0 {
+        0.0
+    } else {
+        (0.2 - (diff.great_hit_window - 34.0) * 0.006667).max(0.0)
+            * strain_value
+            * ((score - 960_000.0).max(0.0) / 40_000.0).powf(1.1)
+    };
+
+    let mut multiplier = 0.8;
+    if mods.contains(Mods::NOFAIL) {
+        multiplier *= 0.9;
+    }
+    if mods.contains(Mods::EASY) {
+        multiplier *= 0.5;
+    }
+
+    (strain_value.powf(1.1) + acc_value.powf(1.1)).powf(1.0 / 1.1) * multiplier
+}
@@ -0,0 +1,386 @@
+use std::f64::consts::PI;
+
+use super::curve::{Point, SliderPath};
+use super::{AdjustedDifficulty, ScoreState, StrainSkill};
+use crate::constants::mods::Mods;
+use crate::utils::osu::beatmap::{BeatmapFile, HitObjectKind};
+
+const DIFFICULTY_MULTIPLIER: f64 = 0.0675;
+/// Distances are normalised as if every circle had this radius.
+const NORMALISED_RADIUS: f64 = 52.0;
+/// Delta times are capped below this, anything faster is as hard as 50ms.
+const MIN_DELTA_TIME: f64 = 50.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difficulty {
+    pub stars: f64,
+    pub aim: f64,
+    pub speed: f64,
+    pub ar: f64,
+    pub od: f64,
+    pub max_combo: u32,
+    pub n_circles: u32,
+    pub n_sliders: u32,
+    pub n_spinners: u32,
+}
+
+struct DifficultyObject {
+    time: f64,
+    delta_time: f64,
+    strain_time: f64,
+    jump_distance: f64,
+    travel_distance: f64,
+    angle: Option<f64>,
+}
+
+struct Object {
+    time: f64,
+    position: Point,
+    /// Where the cursor is left after the object, the lazy end of sliders.
+    cursor_end: Point,
+    /// How far the cursor has to travel to follow a slider.
+    travel_distance: f64,
+    is_spinner: bool,
+}
+
+fn sub(a: Point, b: Point) -> Point {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn length(p: Point) -> f64 {
+    (p.0 * p.0 + p.1 * p.1).sqrt()
+}
+
+fn objects(map: &BeatmapFile, radius: f64, clock_rate: f64) -> Vec<Object> {
+    let follow_radius = radius * 3.0;
+
+    map.hit_objects
+        .iter()
+        .map(|h| {
+            let position = (h.x as f64, h.y as f64);
+            let mut object = Object {
+                time: h.time / clock_rate,
+                position,
+                cursor_end: position,
+                travel_distance: 0.0,
+                is_spinner: matches!(h.kind, HitObjectKind::Spinner { .. }),
+            };
+
+            if let HitObjectKind::Slider(slider) = &h.kind {
+                let path = SliderPath::new(position, slider);
+                let ticks = map.slide_tick_distances(h.time, slider);
+
+                // the cursor only has to stay within the follow circle of each
+                // tick, repeat and the tail
+                let mut nested = Vec::new();
+                for slide in 0..slider.slides {
+                    let reverse = slide % 2 == 1;
+                    for &d in &ticks {
+                        nested.push(path.position_at(if reverse { path.length - d } else { d }));
+                    }
+                    nested.push(path.position_at(if reverse { 0.0 } else { path.length }));
+                }
+
+                for p in nested {
+                    let diff = sub(p, object.cursor_end);
+                    let dist = length(diff);
+                    if dist > follow_radius {
+                        let moved = dist - follow_radius;
+                        object.cursor_end.0 += diff.0 / dist * moved;
+                        object.cursor_end.1 += diff.1 / dist * moved;
+                        object.travel_distance += moved;
+                    }
+                }
+            }
+
+            object
+        })
+        .collect()
+}
+
+fn difficulty_objects(objects: &[Object], scaling: f64) -> Vec<DifficultyObject> {
+    (1..objects.len())
+        .map(|i| {
+            let current = &objects[i];
+            let last = &objects[i - 1];
+            let delta_time = current.time - last.time;
+
+            let (jump_distance, travel_distance) = if current.is_spinner || last.is_spinner {
+                (0.0, 0.0)
+            } else {
+                (
+                    length(sub(current.position, last.cursor_end)) * scaling,
+                    last.travel_distance * scaling,
+                )
+            };
+
+            let angle = i.checked_sub(2).map(|j| {
+                let v1 = sub(objects[j].cursor_end, last.position);
+                let v2 = sub(current.position, last.cursor_end);
+                let dot = v1.0 * v2.0 + v1.1 * v2.1;
+                let det = v1.0 * v2.1 - v1.1 * v2.0;
+                det.atan2(dot).abs()
+            });
+
+            DifficultyObject {
+                time: current.time,
+                delta_time,
+                strain_time: delta_time.max(MIN_DELTA_TIME),
+                jump_distance,
+                travel_distance,
+                angle,
+            }
+        })
+        .collect()
+}
+
+fn aim_strain(current: &DifficultyObject, previous: Option<&DifficultyObject>) -> f64 {
+    const ANGLE_BONUS_BEGIN: f64 = PI / 3.0;
+    const TIMING_THRESHOLD: f64 = 107.0;
+
+    let mut result = 0.0;
+
+    if let (Some(previous), Some(angle)) = (previous, current.angle) {
+        if angle > ANGLE_BONUS_BEGIN {
+            const SCALE: f64 = 90.0;
+            let angle_bonus = ((previous.jump_distance - SCALE).max(0.0)
+                * (angle - ANGLE_BONUS_BEGIN).sin().powi(2)
+                * (current.jump_distance - SCALE).max(0.0))
+            .sqrt();
+            result =
+                1.5 * angle_bonus.max(0.0).powf(0.99) / previous.strain_time.max(TIMING_THRESHOLD);
+        }
+    }
+
+    let jump = current.jump_distance.powf(0.99);
+    let travel = current.travel_distance.powf(0.99);
+    let combined = jump + travel + (travel * jump).sqrt();
+
+    (result + combined / current.strain_time.max(TIMING_THRESHOLD))
+        .max(combined / current.strain_time)
+}
+
+fn speed_strain(current: &DifficultyObject) -> f64 {
+    const SINGLE_SPACING_THRESHOLD: f64 = 125.0;
+    const ANGLE_BONUS_BEGIN: f64 = 5.0 * PI / 6.0;
+    const MIN_SPEED_BONUS: f64 = 75.0;
+    const MAX_SPEED_BONUS: f64 = 45.0;
+    const SPEED_BALANCING_FACTOR: f64 = 40.0;
+
+    let distance = SINGLE_SPACING_THRESHOLD.min(current.travel_distance + current.jump_distance);
+    let delta_time = MAX_SPEED_BONUS.max(current.delta_time);
+
+    let mut speed_bonus = 1.0;
+    if delta_time < MIN_SPEED_BONUS {
+        speed_bonus += ((MIN_SPEED_BONUS - delta_time) / SPEED_BALANCING_FACTOR).powi(2);
+    }
+
+    let mut angle_bonus = 1.0;
+    if let Some(angle) = current.angle {
+        if angle < ANGLE_BONUS_BEGIN {
+            angle_bonus = 1.0 + (1.5 * (ANGLE_BONUS_BEGIN - angle)).sin().powi(2) / 3.57;
+
+            if angle < PI / 2.0 {
+                angle_bonus = 1.28;
+                if distance < 90.0 {
+                    let falloff = ((90.0 - distance) / 10.0).min(1.0);
+                    if angle < PI / 4.0 {
+                        angle_bonus += (1.0 - angle_bonus) * falloff;
+                    } else {
+                        angle_bonus +=
+                            (1.0 - angle_bonus) * falloff * ((PI / 2.0 - angle) / (PI / 4.0)).sin();
+                    }
+                }
+            }
+        }
+    }
+
+    (1.0 + (speed_bonus - 1.0) * 0.75)
+        * angle_bonus
+        * (0.95 + speed_bonus * (distance / SINGLE_SPACING_THRESHOLD).powf(3.5))
+        / current.strain_time
+}
+
+pub fn difficulty(map: &BeatmapFile, mods: Mods) -> Difficulty {
+    let adjusted = AdjustedDifficulty::new(map, mods);
+    let counts = map.object_counts();
+
+    let radius = 32.0 * (1.0 - 0.7 * (adjusted.cs - 5.0) / 5.0);
+    let mut scaling = NORMALISED_RADIUS / radius;
+    if radius < 30.0 {
+        // small circles are harder to hit than their spacing suggests
+        scaling *= 1.0 + (30.0 - radius).min(5.0) / 50.0;
+    }
+
+    let objects = objects(map, radius, adjusted.clock_rate);
+    let diff_objects = difficulty_objects(&objects, scaling);
+
+    let mut aim = StrainSkill::new(26.25, 0.15, 0.9);
+    let mut speed = StrainSkill::new(1400.0, 0.3, 0.9);
+    for (i, current) in diff_objects.iter().enumerate() {
+        let previous = i.checked_sub(1).map(|j| &diff_objects[j]);
+        aim.process(current.time, aim_strain(current, previous));
+        speed.process(current.time, speed_strain(current));
+    }
+
+    // relax and autopilot leave the star rating alone, they only change
+    // what the play is worth
+    let aim_rating = aim.difficulty_value().sqrt() * DIFFICULTY_MULTIPLIER;
+    let speed_rating = speed.difficulty_value().sqrt() * DIFFICULTY_MULTIPLIER;
+
+    Difficulty {
+        stars: aim_rating + speed_rating + (aim_rating - speed_rating).abs() / 2.0,
+        aim: aim_rating,
+        speed: speed_rating,
+        ar: adjusted.ar,
+        od: adjusted.od,
+        max_combo: map.max_combo(),
+        n_circles: counts.circles,
+        n_sliders: counts.sliders,
+        n_spinners: counts.spinners,
+    }
+}
+
+fn rating_value(rating: f64) -> f64 {
+    (5.0 * (rating / DIFFICULTY_MULTIPLIER).max(1.0) - 4.0).powi(3) / 100_000.0
+}
+
+pub fn performance(diff: &Difficulty, mods: Mods, state: &ScoreState) -> f64 {
+    let total_hits = (state.n300 + state.n100 + state.n50 + state.nmiss) as f64;
+    if total_hits == 0.0 {
+        return 0.0;
+    }
+
+    let accuracy =
+        (state.n300 as f64 * 6.0 + state.n100 as f64 * 2.0 + state.n50 as f64) / (total_hits * 6.0);
+
+    let mut misses = state.nmiss as f64;
+    if mods.contains(Mods::RELAX) {
+        // with relax 100s and 50s mostly come from aiming off the circle
+        let ok_multiplier = (1.0 - (diff.od / 13.33).powf(1.8)).max(0.0);
+        let meh_multiplier = (1.0 - (diff.od / 13.33).powi(5)).max(0.0);
+        misses = (misses + state.n100 as f64 * ok_multiplier + state.n50 as f64 * meh_multiplier)
+            .min(total_hits);
+    }
+
+    let mut multiplier = 1.12;
+    if mods.contains(Mods::NOFAIL) {
+        multiplier *= 0.9;
+    }
+    if mods.contains(Mods::SPUNOUT) {
+        multiplier *= 0.95;
+    }
+
+    let length_bonus = 0.95
+        + 0.4 * (total_hits / 2000.0).min(1.0)
+        + if total_hits > 2000.0 {
+            (total_hits / 2000.0).log10() * 0.5
+        } else {
+            0.0
+        };
+    let miss_penalty = 0.97 * (1.0 - (misses / total_hits).powf(0.775)).powf(misses);
+    let combo_scaling = if diff.max_combo > 0 {
+        ((state.max_combo as f64).powf(0.8) / (diff.max_combo as f64).powf(0.8)).min(1.0)
+    } else {
+        1.0
+    };
+
+    let mut ar_factor = 0.0;
+    if diff.ar > 10.33 {
+        ar_factor += 0.4 * (diff.ar - 10.33);
+    } else if diff.ar < 8.0 {
+        ar_factor += 0.01 * (8.0 - diff.ar);
+    }
+    let ar_bonus = 1.0 + ar_factor.min(ar_factor * total_hits / 1000.0);
+    let hidden_bonus = 1.0 + 0.04 * (12.0 - diff.ar);
+
+    let aim_value = if mods.contains(Mods::AUTOPILOT) {
+        0.0
+    } else {
+        let mut raw_aim = diff.aim;
+        if mods.contains(Mods::RELAX) {
+            // clicking is done for you, so only aiming is left
+            raw_aim *= 0.9;
+        }
+        if mods.contains(Mods::TOUCHSCREEN) {
+            raw_aim = raw_aim.powf(0.8);
+        }
+
+        let mut value = rating_value(raw_aim) * length_bonus * combo_scaling * ar_bonus;
+        if misses > 0.0 {
+            value *= miss_penalty;
+        }
+        if mods.contains(Mods::HIDDEN) {
+            value *= hidden_bonus;
+        }
+        if mods.contains(Mods::FLASHLIGHT) {
+            let mut flashlight_bonus = 1.0 + 0.35 * (total_hits / 200.0).min(1.0);
+            if total_hits > 200.0 {
+                flashlight_bonus += 0.3 * ((total_hits - 200.0) / 300.0).min(1.0);
+                if total_hits > 500.0 {
+                    flashlight_bonus += (total_hits - 500.0) / 1200.0;
+                }
+            }
+            value *= flashlight_bonus;
+        }
+
+        value * (0.5 + accuracy / 2.0) * (0.98 + diff.od.powi(2) / 2500.0)
+    };
+
+    let speed_value = if mods.contains(Mods::RELAX) {
+        0.0
+    } else {
+        let speed = match mods.contains(Mods::AUTOPILOT) {
+            true => diff.speed * 0.5,
+            false => diff.speed,
+        };
+        let mut value = rating_value(speed) * length_bonus * combo_scaling;
+        if misses > 0.0 {
+            value *= 0.97 * (1.0 - (misses / total_hits).powf(0.775)).powf(misses.powf(0.875));
+        }
+
+        let speed_ar_factor = if diff.ar > 10.33 {
+            0.4 * (diff.ar - 10.33)
+        } else {
+            0.0
+        };
+        value *= 1.0 + speed_ar_factor.min(speed_ar_factor * total_hits / 1000.0);
+
+        if mods.contains(Mods::HIDDEN) {
+            value *= hidden_bonus;
+        }
+
+        value *= (0.95 + diff.od.powi(2) / 750.0) * accuracy.powf((14.5 - diff.od.max(8.0)) / 2.0);
+
+        let free_mehs = total_hits / 500.0;
+        if (state.n50 as f64) >= free_mehs {
+            value *= 0.98_f64.powf(state.n50 as f64 - free_mehs);
+        }
+        value
+    };
+
+    // only circles have a hit window without score v2
+    let circles = diff.n_circles as f64;
+    let better_accuracy = if circles > 0.0 {
+        (((state.n300 as f64 - (total_hits - circles)) * 6.0
+            + state.n100 as f64 * 2.0
+            + state.n50 as f64)
+            / (circles * 6.0))
+            .max(0.0)
+    } else {
+        0.0
+    };
+
+    let mut acc_value = 1.52163_f64.powf(diff.od)
+        * better_accuracy.powi(24)
+        * 2.83
+        * (circles / 1000.0).powf(0.3).min(1.15);
+    if mods.contains(Mods::HIDDEN) {
+        acc_value *= 1.08;
+    }
+    if mods.contains(Mods::FLASHLIGHT) {
+        acc_value *= 1.02;
+    }
+
+    (aim_value.powf(1.1) + speed_value.powf(1.1) + acc_value.powf(1.1)).powf(1.0 / 1.1) * multiplier
+}
@@ -0,0 +1,188 @@
+use super::{AdjustedDifficulty, ScoreState, StrainSkill};
+use crate::constants::mods::Mods;
+use crate::utils::osu::beatmap::{BeatmapFile, HitObjectKind};
+
+const STAR_SCALING_FACTOR: f64 = 0.04125;
+const RHYTHM_CHANGE_BASE_THRESHOLD: f64 = 0.2;
+const RHYTHM_CHANGE_BASE: f64 = 2.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difficulty {
+    pub stars: f64,
+    /// The star rating without colour changes, which relax hits for you.
+    pub relax_stars: f64,
+    /// Great hit window in (clock adjusted) ms.
+    pub great_hit_window: f64,
+    pub max_combo: u32,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Hit {
+    /// Dons, the centre of the drum.
+    Centre,
+    /// Kats, the rim of the drum, from whistles and claps.
+    Rim,
+    /// Drumrolls and swells, converted from sliders and spinners.
+    Roll,
+}
+
+/// Tracks how long the current run of one colour is, a colour change is only
+/// hard when the parity of the runs changes.
+#[derive(Default)]
+struct ColourState {
+    same_colour_count: u32,
+    last_colour_switch_even: Option<bool>,
+}
+
+impl ColourState {
+    fn has_colour_change(&mut self, current: Hit, last: Hit) -> bool {
+        if current == last {
+            self.same_colour_count += 1;
+            return false;
+        }
+
+        let even = self.same_colour_count.is_multiple_of(2);
+        let change = self
+            .last_colour_switch_even
+            .is_some_and(|last| last != even);
+        self.last_colour_switch_even = Some(even);
+        self.same_colour_count = 1;
+        change
+    }
+
+    fn reset(&mut self) {
+        self.same_colour_count = 1;
+        self.last_colour_switch_even = None;
+    }
+}
+
+fn has_rhythm_change(delta_time: f64, last_delta_time: f64) -> bool {
+    if delta_time <= 0.0 || last_delta_time <= 0.0 {
+        return false;
+    }
+
+    let ratio = (last_delta_time / delta_time).max(delta_time / last_delta_time);
+    if ratio >= 8.0 {
+        return false;
+    }
+
+    let difference = ratio.log(RHYTHM_CHANGE_BASE) % 1.0;
+    difference > RHYTHM_CHANGE_BASE_THRESHOLD && difference < 1.0 - RHYTHM_CHANGE_BASE_THRESHOLD
+}
+
+pub fn difficulty(map: &BeatmapFile, mods: Mods) -> Difficulty {
+    let adjusted = AdjustedDifficulty::new(map, mods);
+
+    // converted sliders and spinners become drumrolls and swells
+    let objects: Vec<(f64, Hit)> = map
+        .hit_objects
+        .iter()
+        .map(|h| {
+            let hit = match h.kind {
+                HitObjectKind::Circle if h.hit_sound & (2 | 8) != 0 => Hit::Rim,
+                HitObjectKind::Circle => Hit::Centre,
+                _ => Hit::Roll,
+            };
+            (h.time / adjusted.clock_rate, hit)
+        })
+        .collect();
+
+    let mut strain = StrainSkill::new(1.0, 0.3, 0.9);
+    let mut relax_strain = StrainSkill::new(1.0, 0.3, 0.9);
+    let mut colour = ColourState::default();
+
+    for i in 1..objects.len() {
+        let (time, hit) = objects[i];
+        let (last_time, last_hit) = objects[i - 1];
+        let delta_time = time - last_time;
+
+        let mut addition = 1.0;
+        let mut colour_addition = 0.0;
+        if hit != Hit::Roll && last_hit != Hit::Roll && delta_time < 1000.0 {
+            if colour.has_colour_change(hit, last_hit) {
+                colour_addition = 0.75;
+            }
+
+            let last_delta_time = i.checked_sub(2).map_or(0.0, |j| last_time - objects[j].0);
+            if has_rhythm_change(delta_time, last_delta_time) {
+                addition += 1.0;
+            }
+        } else {
+            colour.reset();
+        }
+
+        let addition_factor = if delta_time < 50.0 {
+            0.4 + 0.6 * delta_time / 50.0
+        } else {
+            1.0
+        };
+
+        strain.process(time, addition_factor * (addition + colour_addition));
+        relax_strain.process(time, addition_factor * addition);
+    }
+
+    let mut great_hit_window = 50.0 - 3.0 * (map.difficulty.od * od_multiplier(mods)).min(10.0);
+    great_hit_window /= adjusted.clock_rate;
+
+    Difficulty {
+        stars: strain.difficulty_value() * STAR_SCALING_FACTOR,
+        relax_stars: relax_strain.difficulty_value() * STAR_SCALING_FACTOR,
+        great_hit_window,
+        max_combo: objects.iter().filter(|(_, hit)| *hit != Hit::Roll).count() as u32,
+    }
+}
+
+fn od_multiplier(mods: Mods) -> f64 {
+    if mods.contains(Mods::HARDROCK) {
+        1.4
+    } else if mods.contains(Mods::EASY) {
+        0.5
+    } else {
+        1.0
+    }
+}
+
+pub fn performance(diff: &Difficulty, mods: Mods, state: &ScoreState) -> f64 {
+    let total_hits = (state.n300 + state.n100 + state.nmiss) as f64;
+    if total_hits == 0.0 {
+        return 0.0;
+    }
+    let accuracy = (state.n300 as f64 + state.n100 as f64 * 0.5) / total_hits;
+
+    let mut multiplier = 1.1;
+    if mods.contains(Mods::NOFAIL) {
+        multiplier *= 0.9;
+    }
+    if mods.contains(Mods::HIDDEN) {
+        multiplier *= 1.1;
+    }
+
+    let length_bonus = 1.0 + 0.1 * (total_hits / 1500.0).min(1.0);
+
+    // with relax the colour is hit for you
+    let stars = match mods.contains(Mods::RELAX) {
+        true => diff.relax_stars,
+        false => diff.stars,
+    };
+    let mut strain_value = (5.0 * (stars / 0.0075).max(1.0) - 4.0).powi(2) / 100_000.0;
+    strain_value *= length_bonus;
+    strain_value *= 0.985_f64.powi(state.nmiss as i32);
+    if mods.contains(Mods::HIDDEN) {
+        strain_value *= 1.025;
+    }
+    if mods.contains(Mods::FLASHLIGHT) {
+        strain_value *= 1.05 * length_bonus;
+    }
+    strain_value *= accuracy;
+
+    let acc_value = if diff.great_hit_window <= 0.0 {
+        0.0
+    } else {
+        (150.0 / diff.great_hit_window).powf(1.1)
+            * accuracy.powi(15)
+            * 22.0
+            * (total_hits / 1500.0).powf(0.3).min(1.15)
+    };
+
+    (strain_value.powf(1.1) + acc_value.powf(1.1)).powf(1.0 / 1.1) * multiplier
+}
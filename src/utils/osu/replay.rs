@@ -0,0 +1,335 @@
+use std::io::Cursor;
+
+use super::packet_reader::PacketReader;
+use super::packet_writer::{write_f64, write_i16, write_i32, write_i64, write_string, write_u8};
+use crate::constants::mods::Mods;
+
+/// `w` of the frame the client appends to hold the seed for the random mod.
+pub const RNG_SEED_FRAME_TIME: i64 = -12345;
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Truncated(&'static str),
+    Lzma(String),
+    InvalidFrame(String),
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Truncated(field) => write!(f, "replay ended while reading {}", field),
+            ReplayError::Lzma(e) => write!(f, "failed to decompress frames: {}", e),
+            ReplayError::InvalidFrame(frame) => write!(f, "invalid frame {:?}", frame),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayFrame {
+    /// Milliseconds since the previous frame.
+    pub time_delta: i64,
+    pub x: f32,
+    pub y: f32,
+    /// Bitwise keys pressed (M1 = 1, M2 = 2, K1 = 4, K2 = 8, smoke = 16).
+    pub keys: i32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LifeBarPoint {
+    /// Milliseconds into the map.
+    pub time: i32,
+    /// Health from 0 to 1.
+    pub life: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    pub mode: u8,
+    pub version: i32,
+    pub beatmap_md5: String,
+    pub player_name: String,
+    pub replay_md5: String,
+
+    pub n300: i16,
+    pub n100: i16,
+    pub n50: i16,
+    pub ngeki: i16,
+    pub nkatu: i16,
+    pub nmiss: i16,
+
+    pub score: i32,
+    pub max_combo: i16,
+    pub perfect: bool,
+    pub mods: u32,
+
+    pub life_bar: Vec<LifeBarPoint>,
+    /// .NET ticks (100ns since 0001-01-01) at which the play was set.
+    pub timestamp: i64,
+    pub frames: Vec<ReplayFrame>,
+    pub score_id: i64,
+    /// Total accuracy of all hits, only present with target practice.
+    pub target_practice_accuracy: Option<f64>,
+}
+
+impl ReplayFrame {
+    /// Decompresses and parses the LZMA frame data of a replay, which is all
+    /// the client uploads with a score.
+    pub fn parse_all(compressed: &[u8]) -> Result<Vec<Self>, ReplayError> {
+        let mut data = Vec::new();
+        lzma_rs::lzma_decompress(&mut Cursor::new(compressed), &mut data)
+            .map_err(|e| ReplayError::Lzma(e.to_string()))?;
+
+        let text = String::from_utf8_lossy(&data);
+
+        text.split(',')
+            .filter(|f| !f.trim().is_empty())
+            .map(|f| {
+                let parts: Vec<&str> = f.split('|').collect();
+                if parts.len() != 4 {
+                    return Err(ReplayError::InvalidFrame(f.to_string()));
+                }
+
+                let invalid = || ReplayError::InvalidFrame(f.to_string());
+                Ok(ReplayFrame {
+                    time_delta: parts[0].parse().map_err(|_| invalid())?,
+                    x: parts[1].parse().map_err(|_| invalid())?,
+                    y: parts[2].parse().map_err(|_| invalid())?,
+                    keys: parts[3].parse().map_err(|_| invalid())?,
+                })
+            })
+            .collect()
+    }
+
+    /// Serialises and compresses frames the way the client stores them.
+    pub fn compress_all(frames: &[Self]) -> Vec<u8> {
+        let text = frames
+            .iter()
+            .map(|f| format!("{}|{}|{}|{},", f.time_delta, f.x, f.y, f.keys))
+            .collect::<String>();
+
+        let mut compressed = Vec::new();
+        lzma_rs::lzma_compress(&mut Cursor::new(text.as_bytes()), &mut compressed)
+            .expect("compressing into memory can't fail");
+        compressed
+    }
+}
+
+fn parse_life_bar(s: &str) -> Vec<LifeBarPoint> {
+    s.split(',')
+        .filter_map(|point| {
+            let (time, life) = point.split_once('|')?;
+            Some(LifeBarPoint {
+                time: time.parse().ok()?,
+                life: life.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+impl Replay {
+    pub fn parse(data: Vec<u8>) -> Result<Self, ReplayError> {
+        let mut reader = PacketReader::new(data);
+
+        macro_rules! read {
+            ($field:literal, $len:expr, $read:ident) => {{
+                if reader.remaining() < $len {
+                    return Err(ReplayError::Truncated($field));
+                }
+                reader.$read()
+            }};
+        }
+
+        macro_rules! read_string {
+            ($field:literal) => {{
+                if !reader.has_string() {
+                    return Err(ReplayError::Truncated($field));
+                }
+                reader.read_string()
+            }};
+        }
+
+        let mode = read!("mode", 1, read_u8);
+        let version = read!("version", 4, read_i32);
+        let beatmap_md5 = read_string!("beatmap md5");
+        let player_name = read_string!("player name");
+        let replay_md5 = read_string!("replay md5");
+        let n300 = read!("300s", 2, read_i16);
+        let n100 = read!("100s", 2, read_i16);
+        let n50 = read!("50s", 2, read_i16);
+        let ngeki = read!("gekis", 2, read_i16);
+        let nkatu = read!("katus", 2, read_i16);
+        let nmiss = read!("misses", 2, read_i16);
+        let score = read!("score", 4, read_i32);
+        let max_combo = read!("max combo", 2, read_i16);
+        let perfect = read!("perfect", 1, read_u8) == 1;
+        let mods = read!("mods", 4, read_u32);
+        let life_bar = parse_life_bar(&read_string!("life bar"));
+        let timestamp = read!("timestamp", 8, read_i64);
+
+        let frames_len = read!("frames length", 4, read_i32).max(0) as usize;
+        if reader.remaining() < frames_len {
+            return Err(ReplayError::Truncated("frames"));
+        }
+        let frames = match frames_len {
+            0 => Vec::new(),
+            len => ReplayFrame::parse_all(&reader.read_bytes(len))?,
+        };
+
+        let score_id = read!("score id", 8, read_i64);
+
+        let target_practice_accuracy = if Mods::from_bits_truncate(mods).contains(Mods::TARGET) {
+            Some(read!("target practice accuracy", 8, read_f64))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            mode,
+            version,
+            beatmap_md5,
+            player_name,
+            replay_md5,
+            n300,
+            n100,
+            n50,
+            ngeki,
+            nkatu,
+            nmiss,
+            score,
+            max_combo,
+            perfect,
+            mods,
+            life_bar,
+            timestamp,
+            frames,
+            score_id,
+            target_practice_accuracy,
+        })
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        let life_bar = self
+            .life_bar
+            .iter()
+            .map(|p| format!("{}|{}", p.time, p.life))
+            .collect::<Vec<_>>()
+            .join(",");
+        let frames = ReplayFrame::compress_all(&self.frames);
+
+        let mut r = Vec::new();
+        r.extend(write_u8(self.mode));
+        r.extend(write_i32(self.version));
+        r.extend(write_string(&self.beatmap_md5));
+        r.extend(write_string(&self.player_name));
+        r.extend(write_string(&self.replay_md5));
+        r.extend(write_i16(self.n300));
+        r.extend(write_i16(self.n100));
+        r.extend(write_i16(self.n50));
+        r.extend(write_i16(self.ngeki));
+        r.extend(write_i16(self.nkatu));
+        r.extend(write_i16(self.nmiss));
+        r.extend(write_i32(self.score));
+        r.extend(write_i16(self.max_combo));
+        r.extend(write_u8(self.perfect as u8));
+        r.extend(write_i32(self.mods as i32));
+        r.extend(write_string(&life_bar));
+        r.extend(write_i64(self.timestamp));
+        r.extend(write_i32(frames.len() as i32));
+        r.extend(frames);
+        r.extend(write_i64(self.score_id));
+        if let Some(accuracy) = self.target_practice_accuracy {
+            r.extend(write_f64(accuracy));
+        }
+        r
+    }
+
+    /// The seed the client used for the random mod, if the replay has one.
+    pub fn rng_seed(&self) -> Option<i32> {
+        self.frames
+            .iter()
+            .find(|f| f.time_delta == RNG_SEED_FRAME_TIME)
+            .map(|f| f.keys)
+    }
+
+    /// Total length of the replay in milliseconds.
+    pub fn length(&self) -> i64 {
+        self.frames
+            .iter()
+            .filter(|f| f.time_delta != RNG_SEED_FRAME_TIME)
+            .map(|f| f.time_delta.max(0))
+            .sum()
+    }
+}
+
+#[test]
+fn test_replay_roundtrip() {
+    let replay = Replay {
+        mode: 0,
+        version: 20230326,
+        beatmap_md5: "1cf5b2c2edfafd055536d2cefcb89c0e".to_string(),
+        player_name: "cmyui".to_string(),
+        replay_md5: "a".repeat(32),
+        n300: 727,
+        n100: 21,
+        n50: 3,
+        ngeki: 120,
+        nkatu: 10,
+        nmiss: 1,
+        score: 12_345_678,
+        max_combo: 1024,
+        perfect: false,
+        mods: (Mods::HIDDEN | Mods::DOUBLETIME).bits(),
+        life_bar: vec![
+            LifeBarPoint { time: 0, life: 1.0 },
+            LifeBarPoint {
+                time: 2000,
+                life: 0.5,
+            },
+        ],
+        timestamp: 638_000_000_000_000_000,
+        frames: vec![
+            ReplayFrame {
+                time_delta: 0,
+                x: 256.0,
+                y: -500.0,
+                keys: 0,
+            },
+            ReplayFrame {
+                time_delta: 16,
+                x: 100.5,
+                y: 200.25,
+                keys: 5,
+            },
+            ReplayFrame {
+                time_delta: RNG_SEED_FRAME_TIME,
+                x: 0.0,
+                y: 0.0,
+                keys: 727,
+            },
+        ],
+        score_id: 42,
+        target_practice_accuracy: None,
+    };
+
+    let parsed = Replay::parse(replay.write()).unwrap();
+    assert_eq!(parsed, replay);
+    assert_eq!(parsed.rng_seed(), Some(727));
+    assert_eq!(parsed.length(), 16);
+
+    // cut off anywhere, it's an error rather than a panic
+    let data = replay.write();
+    for len in 0..data.len() {
+        assert!(Replay::parse(data[..len].to_vec()).is_err(), "{} bytes", len);
+    }
+
+    // a string claiming to be longer than anything we could have been sent
+    let mut data = vec![0, 0, 0, 0, 0, 0x0B];
+    data.extend([0xFF; 9]);
+    data.push(0x01);
+    assert!(matches!(
+        Replay::parse(data),
+        Err(ReplayError::Truncated("beatmap md5"))
+    ));
+}